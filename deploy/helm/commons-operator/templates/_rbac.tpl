@@ -0,0 +1,87 @@
+{{/*
+Create the list of RBAC rules for the namespaced resources the operator watches and modifies.
+*/}}
+{{- define "operator.namespacedRules" -}}
+# Watch pods to detect expired restart annotations.
+# Watch configmaps and secrets to trigger rolling restarts of referencing workloads.
+- apiGroups:
+    - ""
+  resources:
+    - pods
+    - configmaps
+    - secrets
+  verbs:
+    - list
+    - watch
+# Watch and patch workloads (labelled restarter.stackable.tech/enabled=true)
+# to trigger rolling restarts when referenced ConfigMaps or Secrets change.
+- apiGroups:
+    - apps
+  resources:
+    - statefulsets
+    - deployments
+    - daemonsets
+    - replicasets
+  verbs:
+    - list
+    - watch
+    - patch
+{{- range .Values.restarter.workloads }}
+# Watch and patch additionally configured workloads, same as above.
+- apiGroups:
+    - {{ splitList "/" .apiVersion | first }}
+  resources:
+    - {{ .resource }}
+  verbs:
+    - list
+    - watch
+    - patch
+{{- end }}
+# Emit Kubernetes events from the restart controllers.
+- apiGroups:
+    - events.k8s.io
+  resources:
+    - events
+  verbs:
+    - create
+    - patch
+# Evict pods whose restarter.stackable.tech/expires-at.* annotation timestamp
+# has been reached.
+- apiGroups:
+    - ""
+  resources:
+    - pods/eviction
+  verbs:
+    - create
+# Report evictions that are blocked by a PodDisruptionBudget past the expiration
+# date using a Pod condition, and delete such pods after the configured deadline.
+- apiGroups:
+    - ""
+  resources:
+    - pods/status
+  verbs:
+    - patch
+- apiGroups:
+    - ""
+  resources:
+    - pods
+  verbs:
+    - delete
+# Label existing pods with restarter.stackable.tech/expires-at.* annotations as
+# restarter.stackable.tech/expiry-managed=true when the operator starts.
+- apiGroups:
+    - ""
+  resources:
+    - pods
+  verbs:
+    - patch
+# Check whether all pods of a StatefulSet or ReplicaSet are ready before evicting
+# the next one, if evictions are serialized.
+- apiGroups:
+    - apps
+  resources:
+    - statefulsets
+    - replicasets
+  verbs:
+    - get
+{{- end }}
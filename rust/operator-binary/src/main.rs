@@ -2,10 +2,20 @@
 // This will need changes in our and upstream error types.
 #![allow(clippy::large_enum_variant)]
 
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
 use anyhow::anyhow;
 use clap::Parser;
 use futures::{FutureExt, TryFutureExt};
-use restart_controller::statefulset::create_context;
+use metrics::Metrics;
+use restart_controller::{
+    config_object::ChangeDetection,
+    early_eviction::EarlyEvictionWindow,
+    eviction_escalation::EscalationPolicy,
+    rollout_budget::RolloutLimits,
+    workload::{self, MissingConfigPolicy, create_context},
+    workload_kind::{WorkloadKind, WorkloadRegistration},
+};
 use stackable_operator::{
     YamlSchema as _,
     cli::{Command, RunArguments},
@@ -16,10 +26,13 @@ use stackable_operator::{
     eos::EndOfSupportChecker,
     shared::yaml::SerializeOptions,
     telemetry::Tracing,
+    time::Duration,
     utils::signal::SignalWatcher,
 };
+use utils::{label_selector::LabelSelectorExpression, watched_namespaces::WatchedNamespaces};
 use webhooks::create_webhook_server;
 
+mod metrics;
 mod restart_controller;
 mod utils;
 mod webhooks;
@@ -43,12 +56,180 @@ pub struct CommonsOperatorRunArguments {
     #[command(flatten)]
     pub common: RunArguments,
 
-    /// Don't start the controller mutating webhook and maintain the MutatingWebhookConfiguration.
+    /// Don't start the workload mutating webhook and maintain its MutatingWebhookConfiguration.
     ///
-    /// The mutating webhook is used to prevent an unneeded restart of the first Pod of freshly
-    /// created StatefulSets. It can be turned off in case you can accept an unneeded Pod restart.
+    /// The webhook is used to prevent an unneeded restart of the first Pod of freshly created
+    /// workloads (such as StatefulSets). It can be turned off in case you can accept an unneeded
+    /// Pod restart.
     #[arg(long, env)]
     pub disable_restarter_mutating_webhook: bool,
+
+    /// Don't start the Pod mutating webhook and maintain its MutatingWebhookConfiguration.
+    ///
+    /// The webhook labels Pods with expiry annotations, so that they are watched by the Pod
+    /// restarter. If it is turned off, all Pods are watched (see `--restarter-watch-all-pods`).
+    #[arg(long, env)]
+    pub disable_restarter_pod_mutating_webhook: bool,
+
+    /// Additional workload kinds (such as custom resources) that embed a Pod template, which should
+    /// be restarted when referenced ConfigMaps or Secrets change.
+    ///
+    /// Every workload kind is given as `<group>/<version>/<kind>=<pod-template-json-pointer>`, e.g.
+    /// `argoproj.io/v1alpha1/Rollout=/spec/template`. StatefulSets, Deployments, DaemonSets and
+    /// ReplicaSets are always supported.
+    #[arg(
+        long = "restarter-workload",
+        env = "RESTARTER_WORKLOADS",
+        value_delimiter = ','
+    )]
+    pub restarter_workloads: Vec<WorkloadRegistration>,
+
+    /// Path to a YAML file listing additional workload kinds, see `--restarter-workload`.
+    ///
+    /// The file contains a list of objects with the keys `apiVersion`, `kind` and
+    /// `podTemplatePath`.
+    #[arg(long, env)]
+    pub restarter_workloads_file: Option<PathBuf>,
+
+    /// How changes of ConfigMaps and Secrets are detected.
+    ///
+    /// With `resource-version`, any modification (including e.g. added labels) restarts the
+    /// referencing workloads. With `content-hash`, only modifications of the data do so. Switching
+    /// between the modes restarts all workloads once.
+    #[arg(long, env, value_enum, default_value_t)]
+    pub restarter_change_detection: ChangeDetection,
+
+    /// How long the ConfigMaps and Secrets referenced by a workload need to stay unchanged before
+    /// the workload is restarted, e.g. `30s`.
+    ///
+    /// This coalesces bursts of changes into a single restart. It can be overridden per workload
+    /// using the `restarter.stackable.tech/settle-window` annotation. By default, workloads are
+    /// restarted immediately.
+    #[arg(long, env)]
+    pub restarter_settle_window: Option<Duration>,
+
+    /// Only report the restarts of workloads and evictions of Pods using events instead of
+    /// performing them.
+    ///
+    /// It can be overridden per object using the `restarter.stackable.tech/dry-run` annotation.
+    #[arg(long, env)]
+    pub restarter_dry_run: bool,
+
+    /// How workloads referencing ConfigMaps or Secrets that don't exist (yet) are handled.
+    ///
+    /// With `restart`, workloads are restarted once the objects are created. With `wait`, they are
+    /// not restarted when the objects are created, but pick them up with the next restart. With
+    /// `block`, workloads are not restarted at all while any referenced object is missing. It can
+    /// be overridden per workload using the `restarter.stackable.tech/missing-config-policy`
+    /// annotation.
+    #[arg(long, env, value_enum, default_value_t)]
+    pub restarter_missing_config_policy: MissingConfigPolicy,
+
+    /// Restart all workloads, not only the ones labelled with
+    /// `restarter.stackable.tech/enabled=true`.
+    ///
+    /// This is intended for dedicated clusters, where every workload should pick up configuration
+    /// changes.
+    #[arg(long, env)]
+    pub restarter_all_workloads: bool,
+
+    /// Additional label selector that workloads need to match to be restarted, e.g.
+    /// `tenant=a,tier in (frontend,backend)`.
+    #[arg(long, env)]
+    pub restarter_workload_selector: Option<LabelSelectorExpression>,
+
+    /// Label selector that the namespaces of workloads need to match to be restarted.
+    ///
+    /// This requires the permission to list and watch Namespaces.
+    #[arg(long, env)]
+    pub restarter_namespace_selector: Option<LabelSelectorExpression>,
+
+    /// Evict Pods this long before the TLS certificates in referenced Secrets expire, e.g. `1d`.
+    ///
+    /// The certificates are read from the `tls.crt` key of the Secrets. The resulting
+    /// `restarter.stackable.tech/expires-at.<secret>` annotations are set on the Pod templates of
+    /// the restarted workloads, which restarts them once when this is enabled. By default,
+    /// certificates are not tracked.
+    #[arg(long, env)]
+    pub restarter_tls_expiry_margin: Option<Duration>,
+
+    /// Maximum number of StatefulSets that may be rolled out at the same time due to restarts by
+    /// the restarter, further restarts are queued. Unlimited by default.
+    ///
+    /// When the operator starts, rollouts of StatefulSets that carry restarter annotations and are
+    /// still in progress are counted as well, regardless of who started them.
+    #[arg(long, env)]
+    pub restarter_max_concurrent_rollouts: Option<usize>,
+
+    /// Maximum number of StatefulSets in a single namespace that may be rolled out at the same time
+    /// due to restarts by the restarter, further restarts are queued. Unlimited by default.
+    #[arg(long, env)]
+    pub restarter_max_concurrent_rollouts_per_namespace: Option<usize>,
+
+    /// Watch all Pods for expiry annotations, instead of only the ones labelled with
+    /// `restarter.stackable.tech/expiry-managed=true`.
+    ///
+    /// This keeps the behaviour of previous versions. Otherwise, existing Pods with expiry
+    /// annotations that are not labelled yet are labelled when the operator starts (and by the Pod
+    /// mutating webhook when they are created or updated).
+    #[arg(long, env)]
+    pub restarter_watch_all_pods: bool,
+
+    /// Evict Pods during the last part of their lifetime (from their creation to their expiration
+    /// date) instead of when they expire, e.g. `10%`.
+    ///
+    /// Pods are spread deterministically across this window (Pods of StatefulSets by their ordinal,
+    /// other Pods by their UID), so that Pods expiring at the same time are not evicted at once. It
+    /// can be overridden per Pod using the `restarter.stackable.tech/early-eviction-window`
+    /// annotation. By default, Pods are evicted when they expire.
+    #[arg(long, env)]
+    pub restarter_early_eviction_window: Option<EarlyEvictionWindow>,
+
+    /// How long after their expiration date Pods whose eviction is blocked by a
+    /// PodDisruptionBudget are reported using a Warning event and a Pod condition.
+    ///
+    /// It can be overridden per Pod or Namespace using the
+    /// `restarter.stackable.tech/eviction-warning-after` annotation.
+    #[arg(long, env, default_value = "10m")]
+    pub restarter_eviction_warning_after: Duration,
+
+    /// How long after their expiration date Pods whose eviction is blocked by a
+    /// PodDisruptionBudget are deleted instead (ignoring the PodDisruptionBudget), e.g. `1h`.
+    ///
+    /// It can be overridden per Pod or Namespace using the `restarter.stackable.tech/force-after`
+    /// annotation. By default, Pods are never deleted.
+    #[arg(long, env)]
+    pub restarter_eviction_force_after: Option<Duration>,
+
+    /// Only evict one expired Pod per StatefulSet or ReplicaSet at a time, and only once all other
+    /// Pods of it are ready.
+    ///
+    /// This protects workloads without a PodDisruptionBudget from losing all expired Pods at once.
+    /// It can be overridden per Pod using the `restarter.stackable.tech/serialize-evictions`
+    /// annotation.
+    #[arg(long, env)]
+    pub restarter_serialize_evictions: bool,
+
+    /// Namespaces to watch, as an alternative to watching a single namespace (`--watch-namespace`)
+    /// or all namespaces.
+    #[arg(
+        long = "watch-namespaces",
+        env = "WATCH_NAMESPACES",
+        value_delimiter = ','
+    )]
+    pub watch_namespaces: Vec<String>,
+
+    /// Label selector of the namespaces to watch, as an alternative to watching a single namespace
+    /// (`--watch-namespace`) or all namespaces.
+    ///
+    /// Namespaces are watched (and no longer watched) as they start (and stop) matching the
+    /// selector. This requires the permission to list and watch Namespaces.
+    #[arg(long, env)]
+    pub watch_namespace_selector: Option<LabelSelectorExpression>,
+
+    /// Address the Prometheus metrics are served on (at `/metrics`).
+    #[arg(long, env, default_value = "0.0.0.0:8080")]
+    pub metrics_address: SocketAddr,
 }
 
 #[tokio::main]
@@ -73,6 +254,27 @@ async fn main() -> anyhow::Result<()> {
                     common,
                 },
             disable_restarter_mutating_webhook,
+            disable_restarter_pod_mutating_webhook,
+            restarter_workloads,
+            restarter_workloads_file,
+            restarter_change_detection,
+            restarter_settle_window,
+            restarter_dry_run,
+            restarter_missing_config_policy,
+            restarter_all_workloads,
+            restarter_workload_selector,
+            restarter_namespace_selector,
+            restarter_tls_expiry_margin,
+            restarter_max_concurrent_rollouts,
+            restarter_max_concurrent_rollouts_per_namespace,
+            restarter_watch_all_pods,
+            restarter_early_eviction_window,
+            restarter_eviction_warning_after,
+            restarter_eviction_force_after,
+            restarter_serialize_evictions,
+            watch_namespaces,
+            watch_namespace_selector,
+            metrics_address,
         }) => {
             // NOTE (@NickLarsenNZ): Before stackable-telemetry was used:
             // - The console log level was set by `COMMONS_OPERATOR_LOG`, and is now `CONSOLE_LOG` (when using Tracing::pre_configured).
@@ -100,44 +302,123 @@ async fn main() -> anyhow::Result<()> {
                     .run(sigterm_watcher.handle())
                     .map(anyhow::Ok);
 
+            let watched_namespaces = WatchedNamespaces::new(
+                watch_namespace,
+                watch_namespaces,
+                watch_namespace_selector,
+            )?;
+            tracing::info!(%watched_namespaces, "Watching namespaces");
+
             let client = stackable_operator::client::initialize_operator(
                 Some("commons.stackable.tech".to_string()),
                 &common.cluster_info,
             )
             .await?;
 
-            let (ctx, cm_store_tx, secret_store_tx) = create_context(client.clone());
+            let mut workload_kinds = WorkloadKind::builtin();
+            let workload_registrations = restarter_workloads_file
+                .as_deref()
+                .map(WorkloadRegistration::from_file)
+                .transpose()?
+                .into_iter()
+                .flatten()
+                .chain(restarter_workloads);
+            for workload_registration in workload_registrations {
+                let workload_kind = workload_registration
+                    .resolve(&client.as_kube_client())
+                    .await?;
+                // The last registration of a kind wins, which allows overriding the Pod template
+                // path of the builtin kinds
+                workload_kinds.retain(|existing| {
+                    existing.api_resource.group != workload_kind.api_resource.group
+                        || existing.api_resource.kind != workload_kind.api_resource.kind
+                });
+                workload_kinds.push(workload_kind);
+            }
+
+            let metrics = Arc::new(Metrics::default());
+            let (ctx, cm_store_tx, secret_store_tx) = create_context(
+                client.clone(),
+                workload_kinds,
+                workload::Options {
+                    settle_window: restarter_settle_window.unwrap_or(Duration::from_secs(0)),
+                    dry_run: restarter_dry_run,
+                    missing_config_policy: restarter_missing_config_policy,
+                    all_workloads: restarter_all_workloads,
+                    workload_selector: restarter_workload_selector,
+                    namespace_selector: restarter_namespace_selector,
+                    tls_expiry_margin: restarter_tls_expiry_margin,
+                    rollout_limits: RolloutLimits {
+                        total: restarter_max_concurrent_rollouts,
+                        per_namespace: restarter_max_concurrent_rollouts_per_namespace,
+                    },
+                    change_detection: restarter_change_detection,
+                },
+                metrics.clone(),
+            );
 
             let webhook_server = create_webhook_server(
                 ctx.clone(),
+                &watched_namespaces,
                 &operator_environment,
                 disable_restarter_mutating_webhook,
+                disable_restarter_pod_mutating_webhook,
                 maintenance.disable_crd_maintenance,
                 client.as_kube_client(),
             )
             .await?;
 
-            let sts_restart_controller = restart_controller::statefulset::start(
+            let workload_restart_controller = restart_controller::workload::start(
                 ctx,
                 cm_store_tx,
                 secret_store_tx,
-                &watch_namespace,
+                &watched_namespaces,
                 sigterm_watcher.handle(),
             )
             .map(anyhow::Ok);
 
-            let pod_restart_controller =
-                restart_controller::pod::start(&client, &watch_namespace, sigterm_watcher.handle())
-                    .map(anyhow::Ok);
+            // Without the Pod mutating webhook, Pods that get expiry annotations after the operator
+            // started would never be labelled
+            let restarter_watch_all_pods = if disable_restarter_pod_mutating_webhook
+                && !restarter_watch_all_pods
+            {
+                tracing::warn!(
+                    "The Pod mutating webhook is disabled, watching all Pods instead of only the ones labelled as expiry-managed"
+                );
+                true
+            } else {
+                restarter_watch_all_pods
+            };
+            let pod_restart_controller = restart_controller::pod::start(
+                &client,
+                &watched_namespaces,
+                restart_controller::pod::Options {
+                    dry_run: restarter_dry_run,
+                    watch_all_pods: restarter_watch_all_pods,
+                    early_eviction_window: restarter_early_eviction_window.unwrap_or_default(),
+                    escalation_policy: EscalationPolicy {
+                        warning_after: Some(restarter_eviction_warning_after),
+                        force_after: restarter_eviction_force_after,
+                    },
+                    serialize_evictions: restarter_serialize_evictions,
+                },
+                metrics.clone(),
+                sigterm_watcher.handle(),
+            )
+            .map(anyhow::Ok);
+
+            let metrics_server = metrics::serve(metrics, metrics_address, sigterm_watcher.handle())
+                .map_err(|err| anyhow!(err).context("failed to run metrics server"));
 
             let webhook_server = webhook_server
                 .run(sigterm_watcher.handle())
                 .map_err(|err| anyhow!(err).context("failed to run webhook"));
 
             futures::try_join!(
-                sts_restart_controller,
+                workload_restart_controller,
                 pod_restart_controller,
                 webhook_server,
+                metrics_server,
                 eos_checker,
             )?;
         }
@@ -0,0 +1,590 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Router,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use prometheus_client::{
+    collector::Collector,
+    encoding::{DescriptorEncoder, EncodeLabelSet, EncodeLabelValue, EncodeMetric, text},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::{ConstGauge, Gauge},
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::{Registry, Unit},
+};
+use snafu::{ResultExt, Snafu};
+use tokio::net::TcpListener;
+
+use crate::restart_controller::{rollout_budget::RolloutCounts, workload_index::ConfigKind};
+
+/// Prefix of the names of all metrics.
+const METRICS_PREFIX: &str = "commons_operator";
+
+/// Content type of the OpenMetrics text format.
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("failed to bind metrics server to {address}"))]
+    BindMetricsServer {
+        source: std::io::Error,
+        address: SocketAddr,
+    },
+
+    #[snafu(display("failed to run metrics server"))]
+    RunMetricsServer { source: std::io::Error },
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct WorkloadRestartLabels {
+    namespace: String,
+    kind: String,
+    cause: RestartCause,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MissingConfigLabels {
+    namespace: String,
+    config_kind: ConfigKind,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct NamespaceLabels {
+    namespace: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ControllerLabels {
+    controller: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct WebhookAdmissionLabels {
+    webhook: String,
+    outcome: AdmissionOutcome,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StoreLabels {
+    kind: String,
+}
+
+/// What caused the restart of a workload.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum RestartCause {
+    /// A referenced ConfigMap changed.
+    ConfigMap,
+
+    /// A referenced Secret (or the expiry of its certificates) changed.
+    Secret,
+
+    /// The restart was requested using the `restarter.stackable.tech/restart-requested-at`
+    /// annotation.
+    Requested,
+}
+
+impl From<ConfigKind> for RestartCause {
+    fn from(kind: ConfigKind) -> Self {
+        match kind {
+            ConfigKind::ConfigMap => Self::ConfigMap,
+            ConfigKind::Secret => Self::Secret,
+        }
+    }
+}
+
+/// The outcome of an admission request handled by a webhook.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum AdmissionOutcome {
+    /// The object was admitted unchanged.
+    Allowed,
+
+    /// The object was admitted with a patch.
+    Mutated,
+
+    /// The object was not admitted.
+    Rejected,
+}
+
+/// The metrics of the operator, which are served in the OpenMetrics text format on `/metrics`.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    workload_restarts: Family<WorkloadRestartLabels, Counter>,
+    missing_configs: Family<MissingConfigLabels, Gauge>,
+    pod_evictions: Family<NamespaceLabels, Counter>,
+    pod_evictions_blocked: Family<NamespaceLabels, Counter>,
+    pod_force_deletions: Family<NamespaceLabels, Counter>,
+    expiry_parse_failures: Family<NamespaceLabels, Counter>,
+    rollouts_in_progress: Family<NamespaceLabels, Gauge>,
+    rollouts_queued: Family<NamespaceLabels, Gauge>,
+    reconcile_duration: Family<ControllerLabels, Histogram, fn() -> Histogram>,
+    reconcile_queue_depth: Family<ControllerLabels, Gauge>,
+    webhook_admission_duration: Family<WebhookAdmissionLabels, Histogram, fn() -> Histogram>,
+    store_sizes: StoreSizes,
+
+    /// The objects waiting for reconciliation, by controller.
+    reconcile_queues: Mutex<HashMap<String, HashSet<String>>>,
+
+    /// The missing ConfigMaps and Secrets referenced by each workload.
+    missing_configs_by_workload: Mutex<HashMap<String, Vec<MissingConfigLabels>>>,
+
+    /// The namespaces with rollouts in progress or queued, as last recorded.
+    rollout_namespaces: Mutex<HashSet<String>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::with_prefix(METRICS_PREFIX);
+
+        let workload_restarts = Family::default();
+        registry.register(
+            "restarter_workload_restarts",
+            "Restarts of workloads due to changed ConfigMaps (cause=\"ConfigMap\") or Secrets \
+            (cause=\"Secret\"), or requested restarts (cause=\"Requested\")",
+            workload_restarts.clone(),
+        );
+        let missing_configs = Family::default();
+        registry.register(
+            "restarter_missing_config_references",
+            "References of workloads to ConfigMaps (config_kind=\"ConfigMap\") or Secrets \
+            (config_kind=\"Secret\") that don't exist",
+            missing_configs.clone(),
+        );
+        let pod_evictions = Family::default();
+        registry.register(
+            "restarter_pod_evictions",
+            "Evictions of expired Pods",
+            pod_evictions.clone(),
+        );
+        let pod_evictions_blocked = Family::default();
+        registry.register(
+            "restarter_pod_evictions_blocked",
+            "Evictions of expired Pods that were blocked by a PodDisruptionBudget",
+            pod_evictions_blocked.clone(),
+        );
+        let pod_force_deletions = Family::default();
+        registry.register(
+            "restarter_pod_force_deletions",
+            "Expired Pods that were deleted as their eviction stayed blocked by a PodDisruptionBudget",
+            pod_force_deletions.clone(),
+        );
+        let expiry_parse_failures = Family::default();
+        registry.register(
+            "restarter_pod_expiry_parse_failures",
+            "Pods whose expiry annotation could not be parsed",
+            expiry_parse_failures.clone(),
+        );
+        let rollouts_in_progress = Family::default();
+        registry.register(
+            "restarter_rollouts_in_progress",
+            "Rollouts of workloads started by the restarter that didn't complete yet",
+            rollouts_in_progress.clone(),
+        );
+        let rollouts_queued = Family::default();
+        registry.register(
+            "restarter_rollouts_queued",
+            "Restarts of workloads waiting for the number of concurrent rollouts to drop below the \
+            limit",
+            rollouts_queued.clone(),
+        );
+        let reconcile_duration = Family::new_with_constructor(duration_histogram as fn() -> _);
+        registry.register_with_unit(
+            "restarter_reconcile_duration",
+            "Duration of reconciliations",
+            Unit::Seconds,
+            reconcile_duration.clone(),
+        );
+        let reconcile_queue_depth = Family::default();
+        registry.register(
+            "restarter_reconcile_queue_depth",
+            "Objects waiting for reconciliation (not including scheduled requeues)",
+            reconcile_queue_depth.clone(),
+        );
+        let webhook_admission_duration =
+            Family::new_with_constructor(duration_histogram as fn() -> _);
+        registry.register_with_unit(
+            "webhook_admission_duration",
+            "Duration and outcome of admission requests handled by webhooks",
+            Unit::Seconds,
+            webhook_admission_duration.clone(),
+        );
+        let store_sizes = StoreSizes::default();
+        registry.register_collector(Box::new(store_sizes.clone()));
+
+        Self {
+            registry,
+            workload_restarts,
+            missing_configs,
+            pod_evictions,
+            pod_evictions_blocked,
+            pod_force_deletions,
+            expiry_parse_failures,
+            rollouts_in_progress,
+            rollouts_queued,
+            reconcile_duration,
+            reconcile_queue_depth,
+            webhook_admission_duration,
+            store_sizes,
+            reconcile_queues: Mutex::default(),
+            missing_configs_by_workload: Mutex::default(),
+            rollout_namespaces: Mutex::default(),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn workload_restarted(&self, namespace: &str, kind: &str, cause: RestartCause) {
+        self.workload_restarts
+            .get_or_create(&WorkloadRestartLabels {
+                namespace: namespace.to_owned(),
+                kind: kind.to_owned(),
+                cause,
+            })
+            .inc();
+    }
+
+    /// Records the kinds of the missing ConfigMaps and Secrets that are currently referenced by the
+    /// `workload`, replacing the previously recorded ones.
+    pub fn missing_configs_changed(
+        &self,
+        workload: &str,
+        namespace: &str,
+        config_kinds: impl IntoIterator<Item = ConfigKind>,
+    ) {
+        let missing_configs = config_kinds
+            .into_iter()
+            .map(|config_kind| MissingConfigLabels {
+                namespace: namespace.to_owned(),
+                config_kind,
+            })
+            .collect::<Vec<_>>();
+        let mut missing_configs_by_workload = self.missing_configs_by_workload.lock().unwrap();
+        for labels in missing_configs_by_workload
+            .remove(workload)
+            .into_iter()
+            .flatten()
+        {
+            self.missing_configs.get_or_create(&labels).dec();
+        }
+        for labels in &missing_configs {
+            self.missing_configs.get_or_create(labels).inc();
+        }
+        if !missing_configs.is_empty() {
+            missing_configs_by_workload.insert(workload.to_owned(), missing_configs);
+        }
+    }
+
+    pub fn pod_evicted(&self, namespace: &str) {
+        self.pod_evictions
+            .get_or_create(&namespace_labels(namespace))
+            .inc();
+    }
+
+    pub fn pod_eviction_blocked(&self, namespace: &str) {
+        self.pod_evictions_blocked
+            .get_or_create(&namespace_labels(namespace))
+            .inc();
+    }
+
+    pub fn pod_force_deleted(&self, namespace: &str) {
+        self.pod_force_deletions
+            .get_or_create(&namespace_labels(namespace))
+            .inc();
+    }
+
+    pub fn expiry_parse_failed(&self, namespace: &str) {
+        self.expiry_parse_failures
+            .get_or_create(&namespace_labels(namespace))
+            .inc();
+    }
+
+    /// Records the rollouts in progress and queued by namespace, replacing the previously recorded
+    /// ones.
+    pub fn rollouts_changed(&self, counts: &RolloutCounts) {
+        let mut rollout_namespaces = self.rollout_namespaces.lock().unwrap();
+        let namespaces = counts
+            .in_progress
+            .keys()
+            .chain(counts.queued.keys())
+            .cloned()
+            .collect::<HashSet<_>>();
+        for namespace in rollout_namespaces.union(&namespaces) {
+            let labels = namespace_labels(namespace);
+            let count = |counts: &BTreeMap<String, usize>| {
+                counts
+                    .get(namespace)
+                    .map_or(0, |count| i64::try_from(*count).unwrap_or(i64::MAX))
+            };
+            self.rollouts_in_progress
+                .get_or_create(&labels)
+                .set(count(&counts.in_progress));
+            self.rollouts_queued
+                .get_or_create(&labels)
+                .set(count(&counts.queued));
+        }
+        *rollout_namespaces = namespaces;
+    }
+
+    /// Records that the `object` is waiting to be reconciled by the `controller`.
+    ///
+    /// Objects that are queued multiple times are only counted once, as they are only reconciled
+    /// once.
+    pub fn reconcile_queued(&self, controller: &str, object: String) {
+        let mut queues = self.reconcile_queues.lock().unwrap();
+        let queue = queues.entry(controller.to_owned()).or_default();
+        queue.insert(object);
+        self.set_queue_depth(controller, queue.len());
+    }
+
+    /// Runs the `reconcile` of the `object` by the `controller`, removing it from the queue and
+    /// recording the duration.
+    pub async fn observe_reconcile<F: Future>(
+        &self,
+        controller: &str,
+        object: &str,
+        reconcile: F,
+    ) -> F::Output {
+        {
+            let mut queues = self.reconcile_queues.lock().unwrap();
+            let queue = queues.entry(controller.to_owned()).or_default();
+            queue.remove(object);
+            self.set_queue_depth(controller, queue.len());
+        }
+
+        let started_at = Instant::now();
+        let result = reconcile.await;
+        self.reconcile_duration
+            .get_or_create(&controller_labels(controller))
+            .observe(started_at.elapsed().as_secs_f64());
+        result
+    }
+
+    pub fn webhook_admitted(&self, webhook: &str, outcome: AdmissionOutcome, duration: Duration) {
+        self.webhook_admission_duration
+            .get_or_create(&WebhookAdmissionLabels {
+                webhook: webhook.to_owned(),
+                outcome,
+            })
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Registers a (reflector) store, whose number of objects of the given `kind` is reported on
+    /// every scrape using the `size` function.
+    pub fn register_store(&self, kind: &str, size: impl Fn() -> usize + Send + Sync + 'static) {
+        self.store_sizes
+            .stores
+            .lock()
+            .unwrap()
+            .insert(kind.to_owned(), Box::new(size));
+    }
+
+    /// Encodes all metrics in the OpenMetrics text format.
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut encoded = String::new();
+        text::encode(&mut encoded, &self.registry)?;
+        Ok(encoded)
+    }
+
+    fn set_queue_depth(&self, controller: &str, depth: usize) {
+        self.reconcile_queue_depth
+            .get_or_create(&controller_labels(controller))
+            .set(depth.try_into().unwrap_or(i64::MAX));
+    }
+}
+
+fn namespace_labels(namespace: &str) -> NamespaceLabels {
+    NamespaceLabels {
+        namespace: namespace.to_owned(),
+    }
+}
+
+fn controller_labels(controller: &str) -> ControllerLabels {
+    ControllerLabels {
+        controller: controller.to_owned(),
+    }
+}
+
+/// Buckets from 5ms to roughly 20s.
+fn duration_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.005, 2.0, 13))
+}
+
+/// Returns the number of objects in a store.
+type StoreSize = Box<dyn Fn() -> usize + Send + Sync>;
+
+/// Reports the number of objects in the registered stores on every scrape.
+#[derive(Clone, Default)]
+struct StoreSizes {
+    stores: Arc<Mutex<BTreeMap<String, StoreSize>>>,
+}
+
+impl Debug for StoreSizes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StoreSizes")
+            .field("kinds", &self.stores.lock().unwrap().keys())
+            .finish()
+    }
+}
+
+impl Collector for StoreSizes {
+    fn encode(&self, mut encoder: DescriptorEncoder) -> Result<(), std::fmt::Error> {
+        let stores = self.stores.lock().unwrap();
+        let mut metric_encoder = encoder.encode_descriptor(
+            "restarter_store_objects",
+            "Objects cached by the restarter, by kind.",
+            None,
+            prometheus_client::metrics::MetricType::Gauge,
+        )?;
+        for (kind, size) in stores.iter() {
+            let labels = StoreLabels { kind: kind.clone() };
+            let size = ConstGauge::new(i64::try_from(size()).unwrap_or(i64::MAX));
+            size.encode(metric_encoder.encode_family(&labels)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Serves the `metrics` on `/metrics` at the given `address` until the `shutdown_signal` fires.
+pub async fn serve(
+    metrics: Arc<Metrics>,
+    address: SocketAddr,
+    shutdown_signal: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), Error> {
+    let listener = TcpListener::bind(address)
+        .await
+        .context(BindMetricsServerSnafu { address })?;
+    let router = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(metrics);
+    tracing::info!(%address, "Serving metrics");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal)
+        .await
+        .context(RunMetricsServerSnafu)
+}
+
+async fn metrics_handler(State(metrics): State<Arc<Metrics>>) -> Response {
+    match metrics.encode() {
+        Ok(encoded) => {
+            ([(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], encoded).into_response()
+        }
+        Err(error) => {
+            tracing::warn!(
+                error = &error as &dyn std::error::Error,
+                "failed to encode metrics"
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_metrics() {
+        let metrics = Metrics::default();
+        metrics.workload_restarted("default", "StatefulSet", RestartCause::Secret);
+        metrics.pod_eviction_blocked("default");
+        metrics.pod_force_deleted("default");
+        metrics.register_store("ConfigMap", || 3);
+
+        let encoded = metrics.encode().unwrap();
+        for expected in [
+            r#"commons_operator_restarter_workload_restarts_total{namespace="default",kind="StatefulSet",cause="Secret"} 1"#,
+            r#"commons_operator_restarter_pod_evictions_blocked_total{namespace="default"} 1"#,
+            r#"commons_operator_restarter_pod_force_deletions_total{namespace="default"} 1"#,
+            r#"commons_operator_restarter_store_objects{kind="ConfigMap"} 3"#,
+        ] {
+            assert!(
+                encoded.contains(expected),
+                "{expected:?} is missing in {encoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_configs_are_replaced() {
+        let metrics = Metrics::default();
+        let missing = |config_kind| {
+            metrics
+                .missing_configs
+                .get_or_create(&MissingConfigLabels {
+                    namespace: "default".to_owned(),
+                    config_kind,
+                })
+                .get()
+        };
+        metrics.missing_configs_changed(
+            "a",
+            "default",
+            [ConfigKind::ConfigMap, ConfigKind::ConfigMap],
+        );
+        metrics.missing_configs_changed("b", "default", [ConfigKind::ConfigMap]);
+        assert_eq!(missing(ConfigKind::ConfigMap), 3);
+
+        metrics.missing_configs_changed("a", "default", [ConfigKind::Secret]);
+        assert_eq!(missing(ConfigKind::ConfigMap), 1);
+        assert_eq!(missing(ConfigKind::Secret), 1);
+
+        metrics.missing_configs_changed("b", "default", []);
+        assert_eq!(missing(ConfigKind::ConfigMap), 0);
+    }
+
+    #[test]
+    fn rollouts_are_replaced() {
+        let metrics = Metrics::default();
+        let gauges = |namespace| {
+            let labels = namespace_labels(namespace);
+            (
+                metrics.rollouts_in_progress.get_or_create(&labels).get(),
+                metrics.rollouts_queued.get_or_create(&labels).get(),
+            )
+        };
+        metrics.rollouts_changed(&RolloutCounts {
+            in_progress: BTreeMap::from([("a".to_owned(), 2), ("b".to_owned(), 1)]),
+            queued: BTreeMap::from([("a".to_owned(), 3)]),
+        });
+        assert_eq!(gauges("a"), (2, 3));
+        assert_eq!(gauges("b"), (1, 0));
+
+        metrics.rollouts_changed(&RolloutCounts {
+            in_progress: BTreeMap::from([("a".to_owned(), 1)]),
+            queued: BTreeMap::new(),
+        });
+        assert_eq!(gauges("a"), (1, 0));
+        assert_eq!(gauges("b"), (0, 0));
+    }
+
+    #[test]
+    fn queue_depth_counts_objects_once() {
+        let metrics = Metrics::default();
+        metrics.reconcile_queued("controller", "a".to_owned());
+        metrics.reconcile_queued("controller", "a".to_owned());
+        metrics.reconcile_queued("controller", "b".to_owned());
+        let depth = || {
+            metrics
+                .reconcile_queue_depth
+                .get_or_create(&controller_labels("controller"))
+                .get()
+        };
+        assert_eq!(depth(), 2);
+
+        futures::executor::block_on(metrics.observe_reconcile("controller", "a", async {}));
+        assert_eq!(depth(), 1);
+    }
+}
@@ -0,0 +1,559 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use jiff::Timestamp;
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+use stackable_operator::{
+    client::Client,
+    k8s_openapi::{
+        api::{apps::v1::StatefulSet, core::v1::Pod},
+        apimachinery::pkg::apis::meta::v1::Time,
+    },
+    kube::{self, api::ListParams, core::DynamicObject},
+    time::{Duration, DurationParseError},
+};
+
+use crate::restart_controller::maintenance_window::duration_until;
+
+/// Annotation enabling canary rollouts of StatefulSets, given as the number (e.g. `2`) or
+/// percentage (e.g. `10%`) of replicas that are restarted per step.
+pub const CANARY_ANNOTATION: &str = "restarter.stackable.tech/canary";
+
+/// Annotation overriding how long the restarted Pods of a step need to be ready before the next
+/// step starts.
+pub const CANARY_SOAK_TIME_ANNOTATION: &str = "restarter.stackable.tech/canary-soak-time";
+
+/// Annotation overriding how long restarted Pods may take to become ready before the rollout is
+/// paused.
+pub const CANARY_READY_TIMEOUT_ANNOTATION: &str = "restarter.stackable.tech/canary-ready-timeout";
+
+/// Annotation recording the partition of the StatefulSet that was set by the restarter, written
+/// together with the partition.
+///
+/// Only canary rollouts whose partition still matches are continued, so that a partition set by
+/// someone else is left alone.
+pub const CANARY_PARTITION_ANNOTATION: &str = "restarter.stackable.tech/canary-partition";
+
+const DEFAULT_SOAK_TIME: Duration = Duration::from_minutes_unchecked(5);
+const DEFAULT_READY_TIMEOUT: Duration = Duration::from_minutes_unchecked(10);
+
+/// Label of StatefulSet Pods, containing the revision of the StatefulSet they were created from.
+const CONTROLLER_REVISION_HASH_LABEL: &str = "controller-revision-hash";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "invalid canary size {value:?} in annotation {CANARY_ANNOTATION:?}, expected a positive \
+        number of replicas (e.g. \"2\") or a percentage (e.g. \"10%\")"
+    ))]
+    InvalidSize { value: String },
+
+    #[snafu(display("invalid soak time {value:?} in annotation {CANARY_SOAK_TIME_ANNOTATION:?}"))]
+    InvalidSoakTime {
+        source: DurationParseError,
+        value: String,
+    },
+
+    #[snafu(display(
+        "invalid ready timeout {value:?} in annotation {CANARY_READY_TIMEOUT_ANNOTATION:?}"
+    ))]
+    InvalidReadyTimeout {
+        source: DurationParseError,
+        value: String,
+    },
+
+    #[snafu(display("workload is not a valid StatefulSet"))]
+    InvalidStatefulSet {
+        source: kube::core::dynamic::ParseDynamicObjectError,
+    },
+
+    #[snafu(display("failed to list the Pods of the StatefulSet"))]
+    ListPods { source: kube::Error },
+}
+
+/// How many replicas are restarted per step of a canary rollout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CanarySize {
+    Replicas(i32),
+    Percentage(i32),
+}
+
+impl FromStr for CanarySize {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidSizeSnafu { value };
+        let size = match value.trim().strip_suffix('%') {
+            Some(percentage) => {
+                let percentage = percentage.trim().parse().ok().with_context(invalid)?;
+                ensure!((1..=100).contains(&percentage), invalid());
+                Self::Percentage(percentage)
+            }
+            None => {
+                let replicas = value.trim().parse().ok().with_context(invalid)?;
+                ensure!(replicas >= 1, invalid());
+                Self::Replicas(replicas)
+            }
+        };
+        Ok(size)
+    }
+}
+
+impl Display for CanarySize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Replicas(replicas) => write!(f, "{replicas}"),
+            Self::Percentage(percentage) => write!(f, "{percentage}%"),
+        }
+    }
+}
+
+impl CanarySize {
+    /// The number of replicas (out of `replicas`) restarted per step, at least one.
+    ///
+    /// Percentages are rounded up.
+    pub fn replicas_per_step(&self, replicas: i32) -> i32 {
+        let per_step = match *self {
+            Self::Replicas(per_step) => per_step,
+            Self::Percentage(percentage) => {
+                ((i64::from(replicas) * i64::from(percentage) + 99) / 100) as i32
+            }
+        };
+        per_step.max(1)
+    }
+}
+
+/// The configuration of canary rollouts of a StatefulSet.
+///
+/// Restarts only roll the Pods with the highest ordinals first (by setting the partition of the
+/// rolling update), and then step the partition down whenever the restarted Pods have been ready
+/// for the soak time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canary {
+    pub size: CanarySize,
+    pub soak_time: Duration,
+    pub ready_timeout: Duration,
+}
+
+impl Canary {
+    /// Parses the canary configuration from the [`CANARY_ANNOTATION`] and its related annotations,
+    /// [`None`] if canary rollouts are not enabled.
+    pub fn from_annotations(annotations: &BTreeMap<String, String>) -> Result<Option<Self>, Error> {
+        let Some(size) = annotations.get(CANARY_ANNOTATION) else {
+            return Ok(None);
+        };
+        let soak_time = match annotations.get(CANARY_SOAK_TIME_ANNOTATION) {
+            Some(soak_time) => soak_time
+                .parse()
+                .context(InvalidSoakTimeSnafu { value: soak_time })?,
+            None => DEFAULT_SOAK_TIME,
+        };
+        let ready_timeout = match annotations.get(CANARY_READY_TIMEOUT_ANNOTATION) {
+            Some(ready_timeout) => ready_timeout.parse().context(InvalidReadyTimeoutSnafu {
+                value: ready_timeout,
+            })?,
+            None => DEFAULT_READY_TIMEOUT,
+        };
+        Ok(Some(Self {
+            size: size.parse()?,
+            soak_time,
+            ready_timeout,
+        }))
+    }
+
+    /// The partition that starts a canary rollout, so that only the first step is restarted.
+    pub fn initial_partition(&self, rollout: &StatefulSetRollout) -> i32 {
+        let per_step = self.size.replicas_per_step(rollout.replicas);
+        rollout.first_ordinal + (rollout.replicas - per_step).max(0)
+    }
+
+    /// Determines how the canary rollout of the StatefulSet continues, given its current `pods`.
+    pub fn progress(
+        &self,
+        rollout: &StatefulSetRollout,
+        pods: &[CanaryPod],
+        now: Timestamp,
+    ) -> CanaryProgress {
+        let canary_pods = (rollout.partition..rollout.first_ordinal + rollout.replicas)
+            .map(|ordinal| pods.iter().find(|pod| pod.ordinal == ordinal))
+            .collect::<Vec<_>>();
+        let is_updated =
+            |pod: &CanaryPod| pod.revision.is_some() && pod.revision == rollout.update_revision;
+
+        let not_ready_pods = canary_pods
+            .iter()
+            .flatten()
+            .filter(|pod| {
+                is_updated(pod)
+                    && pod.not_ready_since.is_some_and(|not_ready_since| {
+                        duration_until(not_ready_since, now) >= *self.ready_timeout
+                    })
+            })
+            .map(|pod| pod.name.clone())
+            .collect::<Vec<_>>();
+        if !not_ready_pods.is_empty() {
+            return CanaryProgress::Paused { not_ready_pods };
+        }
+
+        let mut last_ready_at = None::<Timestamp>;
+        for pod in &canary_pods {
+            match pod {
+                Some(pod) if is_updated(pod) => match pod.ready_since {
+                    Some(ready_since) => {
+                        last_ready_at = last_ready_at.max(Some(ready_since));
+                    }
+                    None => return CanaryProgress::Updating,
+                },
+                _ => return CanaryProgress::Updating,
+            }
+        }
+
+        let soaked_for = last_ready_at.map_or(std::time::Duration::MAX, |last_ready_at| {
+            duration_until(last_ready_at, now)
+        });
+        if let Some(remaining) = self.soak_time.checked_sub(soaked_for)
+            && !remaining.is_zero()
+        {
+            return CanaryProgress::Soaking { remaining };
+        }
+
+        let per_step = self.size.replicas_per_step(rollout.replicas);
+        CanaryProgress::Step {
+            partition: (rollout.partition - per_step).max(rollout.first_ordinal),
+        }
+    }
+}
+
+/// How a canary rollout continues.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanaryProgress {
+    /// The Pods of the current step are still being restarted.
+    Updating,
+
+    /// The Pods of the current step are ready, but not for the whole soak time yet.
+    Soaking { remaining: std::time::Duration },
+
+    /// Restarted Pods of the current step didn't become ready in time.
+    Paused { not_ready_pods: Vec<String> },
+
+    /// The current step is done, the next one is started by lowering the partition.
+    Step { partition: i32 },
+}
+
+/// The parts of a StatefulSet that determine the state of its rollout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatefulSetRollout {
+    pub replicas: i32,
+    pub first_ordinal: i32,
+    pub partition: i32,
+
+    /// Whether the StatefulSet uses the `OnDelete` update strategy, which doesn't support
+    /// partitions.
+    pub on_delete: bool,
+
+    pub update_revision: Option<String>,
+
+    /// The partition recorded in the [`CANARY_PARTITION_ANNOTATION`], if the restarter set one.
+    restarter_partition: Option<i32>,
+
+    /// The `matchLabels` of the Pod selector, as a label selector.
+    pod_selector: String,
+    pod_name_prefix: String,
+}
+
+impl StatefulSetRollout {
+    pub fn from_workload(workload: &DynamicObject) -> Result<Self, Error> {
+        let stateful_set = workload
+            .clone()
+            .try_parse::<StatefulSet>()
+            .context(InvalidStatefulSetSnafu)?;
+        let spec = stateful_set.spec.unwrap_or_default();
+        let update_strategy = spec.update_strategy.unwrap_or_default();
+        Ok(Self {
+            replicas: spec.replicas.unwrap_or(1),
+            first_ordinal: spec
+                .ordinals
+                .and_then(|ordinals| ordinals.start)
+                .unwrap_or(0),
+            partition: update_strategy
+                .rolling_update
+                .and_then(|rolling_update| rolling_update.partition)
+                .unwrap_or(0),
+            on_delete: update_strategy.type_.as_deref() == Some("OnDelete"),
+            update_revision: stateful_set
+                .status
+                .and_then(|status| status.update_revision),
+            restarter_partition: stateful_set
+                .metadata
+                .annotations
+                .as_ref()
+                .and_then(|annotations| annotations.get(CANARY_PARTITION_ANNOTATION))
+                .and_then(|partition| partition.parse().ok()),
+            pod_selector: spec
+                .selector
+                .match_labels
+                .iter()
+                .flatten()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(","),
+            pod_name_prefix: format!("{}-", stateful_set.metadata.name.unwrap_or_default()),
+        })
+    }
+
+    /// Whether a canary rollout started by the restarter is in progress, i.e. not all Pods are
+    /// allowed to be updated.
+    ///
+    /// Partitions that were not set by the restarter (or changed since) don't count.
+    pub fn canary_in_progress(&self) -> bool {
+        !self.on_delete
+            && self.restarter_partition == Some(self.partition)
+            && self.partition > self.first_ordinal
+    }
+
+    /// Lists the Pods of the StatefulSet.
+    pub async fn list_pods(
+        &self,
+        client: &Client,
+        namespace: &str,
+    ) -> Result<Vec<CanaryPod>, Error> {
+        let pods = client
+            .get_api::<Pod>(namespace)
+            .list(&ListParams::default().labels(&self.pod_selector))
+            .await
+            .context(ListPodsSnafu)?;
+        Ok(pods
+            .iter()
+            .filter_map(|pod| CanaryPod::from_pod(pod, &self.pod_name_prefix))
+            .collect())
+    }
+}
+
+/// A Pod of a StatefulSet, as seen by a canary rollout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanaryPod {
+    pub name: String,
+    pub ordinal: i32,
+
+    /// The revision of the StatefulSet that the Pod was created from.
+    pub revision: Option<String>,
+
+    /// When the Pod became ready, [`None`] if it isn't ready.
+    pub ready_since: Option<Timestamp>,
+
+    /// Since when the Pod is not ready (or was created), [`None`] if it is ready.
+    pub not_ready_since: Option<Timestamp>,
+}
+
+impl CanaryPod {
+    fn from_pod(pod: &Pod, name_prefix: &str) -> Option<Self> {
+        let name = pod.metadata.name.clone()?;
+        let ordinal = name.strip_prefix(name_prefix)?.parse().ok()?;
+        let ready_condition = pod
+            .status
+            .iter()
+            .flat_map(|status| &status.conditions)
+            .flatten()
+            .find(|condition| condition.type_ == "Ready");
+        let to_timestamp = |time: &Time| time.0;
+        let (ready_since, not_ready_since) = match ready_condition {
+            Some(condition) if condition.status == "True" => (
+                condition
+                    .last_transition_time
+                    .as_ref()
+                    .map(to_timestamp)
+                    .or(Some(Timestamp::UNIX_EPOCH)),
+                None,
+            ),
+            Some(condition) => (
+                None,
+                condition
+                    .last_transition_time
+                    .as_ref()
+                    .or(pod.metadata.creation_timestamp.as_ref())
+                    .map(to_timestamp),
+            ),
+            None => (
+                None,
+                pod.metadata.creation_timestamp.as_ref().map(to_timestamp),
+            ),
+        };
+        Some(Self {
+            revision: pod
+                .metadata
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get(CONTROLLER_REVISION_HASH_LABEL))
+                .cloned(),
+            name,
+            ordinal,
+            ready_since,
+            not_ready_since,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        timestamp.parse().unwrap()
+    }
+
+    fn canary(size: &str) -> Canary {
+        Canary::from_annotations(&BTreeMap::from([(
+            CANARY_ANNOTATION.to_owned(),
+            size.to_owned(),
+        )]))
+        .unwrap()
+        .unwrap()
+    }
+
+    fn rollout(replicas: i32, partition: i32) -> StatefulSetRollout {
+        StatefulSetRollout {
+            replicas,
+            first_ordinal: 0,
+            partition,
+            on_delete: false,
+            update_revision: Some("new".to_owned()),
+            restarter_partition: Some(partition),
+            pod_selector: String::new(),
+            pod_name_prefix: "sts-".to_owned(),
+        }
+    }
+
+    fn pod(ordinal: i32, revision: &str, ready_since: Option<&str>) -> CanaryPod {
+        CanaryPod {
+            name: format!("sts-{ordinal}"),
+            ordinal,
+            revision: Some(revision.to_owned()),
+            ready_since: ready_since.map(ts),
+            not_ready_since: match ready_since {
+                Some(_) => None,
+                None => Some(ts("2024-01-01T00:00:00Z")),
+            },
+        }
+    }
+
+    #[test]
+    fn parse_size() {
+        assert_eq!("2".parse::<CanarySize>().unwrap(), CanarySize::Replicas(2));
+        assert_eq!(
+            " 10% ".parse::<CanarySize>().unwrap(),
+            CanarySize::Percentage(10)
+        );
+        for size in ["", "0", "-1", "0%", "101%", "foo", "1.5"] {
+            assert!(
+                size.parse::<CanarySize>().is_err(),
+                "{size:?} should be rejected"
+            );
+        }
+
+        assert_eq!(CanarySize::Percentage(10).replicas_per_step(25), 3);
+        assert_eq!(CanarySize::Percentage(10).replicas_per_step(3), 1);
+        assert_eq!(canary("10%").initial_partition(&rollout(25, 0)), 22);
+        assert_eq!(canary("5").initial_partition(&rollout(3, 0)), 0);
+    }
+
+    #[test]
+    fn progress_through_steps() {
+        let canary = canary("2");
+        let now = ts("2024-01-01T01:00:00Z");
+        let old_pods = (0..4).map(|ordinal| pod(ordinal, "old", Some("2023-01-01T00:00:00Z")));
+
+        // The canary Pods are still restarted
+        let pods = old_pods.clone().collect::<Vec<_>>();
+        assert_eq!(
+            canary.progress(&rollout(5, 3), &pods, now),
+            CanaryProgress::Updating
+        );
+        let pods = old_pods
+            .clone()
+            .chain([pod(4, "new", Some("2024-01-01T00:58:00Z"))])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            canary.progress(&rollout(5, 3), &pods, now),
+            CanaryProgress::Updating
+        );
+
+        // The canary Pods are ready, but not for the whole soak time
+        let pods = old_pods
+            .clone()
+            .take(3)
+            .chain([
+                pod(3, "new", Some("2024-01-01T00:50:00Z")),
+                pod(4, "new", Some("2024-01-01T00:58:00Z")),
+            ])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            canary.progress(&rollout(5, 3), &pods, now),
+            CanaryProgress::Soaking {
+                remaining: std::time::Duration::from_secs(3 * 60)
+            }
+        );
+
+        // The next steps roll out to more Pods, but never below the first ordinal
+        assert_eq!(
+            canary.progress(&rollout(5, 3), &pods, ts("2024-01-01T01:03:00Z")),
+            CanaryProgress::Step { partition: 1 }
+        );
+        let pods = (0..5)
+            .map(|ordinal| pod(ordinal, "new", Some("2024-01-01T00:00:00Z")))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            canary.progress(&rollout(5, 1), &pods, now),
+            CanaryProgress::Step { partition: 0 }
+        );
+    }
+
+    #[test]
+    fn only_continue_rollouts_started_by_restarter() {
+        let stateful_set = |restarter_partition: Option<&str>, partition: i32| {
+            let annotations = restarter_partition
+                .map(|partition| BTreeMap::from([(CANARY_PARTITION_ANNOTATION, partition)]));
+            let workload = serde_json::from_value::<DynamicObject>(serde_json::json!({
+                "apiVersion": "apps/v1",
+                "kind": "StatefulSet",
+                "metadata": {
+                    "name": "sts",
+                    "annotations": annotations,
+                },
+                "spec": {
+                    "replicas": 5,
+                    "selector": {"matchLabels": {"app": "sts"}},
+                    "template": {},
+                    "updateStrategy": {"rollingUpdate": {"partition": partition}},
+                },
+            }))
+            .unwrap();
+            StatefulSetRollout::from_workload(&workload).unwrap()
+        };
+
+        assert!(stateful_set(Some("3"), 3).canary_in_progress());
+        // The partition was set by the user ...
+        assert!(!stateful_set(None, 3).canary_in_progress());
+        // ... or changed by them during the rollout
+        assert!(!stateful_set(Some("3"), 4).canary_in_progress());
+        // The rollout is completed
+        assert!(!stateful_set(Some("0"), 0).canary_in_progress());
+    }
+
+    #[test]
+    fn pause_on_unready_canary_pods() {
+        let canary = canary("1");
+        let pods = [
+            pod(0, "old", Some("2023-01-01T00:00:00Z")),
+            pod(1, "new", None),
+        ];
+        assert_eq!(
+            canary.progress(&rollout(2, 1), &pods, ts("2024-01-01T00:09:59Z")),
+            CanaryProgress::Updating
+        );
+        assert_eq!(
+            canary.progress(&rollout(2, 1), &pods, ts("2024-01-01T00:10:00Z")),
+            CanaryProgress::Paused {
+                not_ready_pods: vec!["sts-1".to_owned()]
+            }
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use jiff::Timestamp;
+use snafu::{ResultExt, Snafu};
+use stackable_operator::k8s_openapi::{ByteString, api::core::v1::Secret};
+use x509_parser::{
+    error::{PEMError, X509Error},
+    pem::Pem,
+};
+
+/// Key of the certificate (chain) in Secrets of type `kubernetes.io/tls`.
+const TLS_CERTIFICATE_KEY: &str = "tls.crt";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("failed to read PEM block from {TLS_CERTIFICATE_KEY:?}"))]
+    ReadPem { source: PEMError },
+
+    #[snafu(display("failed to parse certificate from {TLS_CERTIFICATE_KEY:?}"))]
+    ParseCertificate {
+        source: x509_parser::nom::Err<X509Error>,
+    },
+
+    #[snafu(display("expiry of certificate from {TLS_CERTIFICATE_KEY:?} is out of range"))]
+    ExpiryOutOfRange { source: jiff::Error },
+}
+
+/// Returns when the certificates in the `tls.crt` key of the Secret expire, [`None`] if there are
+/// none.
+///
+/// If `tls.crt` contains a chain, the soonest `notAfter` of all certificates is returned, as the
+/// chain is only valid as long as all of them are.
+pub fn certificate_expiry(secret: &Secret) -> Result<Option<Timestamp>, Error> {
+    let Some(ByteString(tls_crt)) = secret
+        .data
+        .as_ref()
+        .and_then(|data| data.get(TLS_CERTIFICATE_KEY))
+    else {
+        return Ok(None);
+    };
+
+    let mut expiry = None::<Timestamp>;
+    for pem in Pem::iter_from_buffer(tls_crt) {
+        let pem = pem.context(ReadPemSnafu)?;
+        // `tls.crt` might also contain other blocks, such as the private key
+        if pem.label != "CERTIFICATE" {
+            continue;
+        }
+        let not_after = pem
+            .parse_x509()
+            .context(ParseCertificateSnafu)?
+            .validity()
+            .not_after
+            .timestamp();
+        let not_after = Timestamp::from_second(not_after).context(ExpiryOutOfRangeSnafu)?;
+        expiry = Some(expiry.map_or(not_after, |expiry| expiry.min(not_after)));
+    }
+    Ok(expiry)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Certificate for `CN=leaf`, valid until 2026-01-01, issued by [`CA_CERTIFICATE`].
+    const LEAF_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBYDCCAQagAwIBAgIUA55alI1C+loW3hndUEu1LGxAOhEwCgYIKoZIzj0EAwIw
+DTELMAkGA1UEAwwCY2EwHhcNMjUwMTAxMDAwMDAwWhcNMjYwMTAxMDAwMDAwWjAP
+MQ0wCwYDVQQDDARsZWFmMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEekiXiViI
+eqaijoPgJqsdn2VuizH4tlLs9pho2v8KB/FX2H4TAcmOoCrROlmP22U5+q0kHS+K
+x12p/X+PJFztsKNCMEAwHQYDVR0OBBYEFIamj5rzxgtBPguh84Hloh5pemtZMB8G
+A1UdIwQYMBaAFPctZLOhR8OOLv4tffe7GGbHrhR2MAoGCCqGSM49BAMCA0gAMEUC
+IDMZgRh8IWIqchIxlR6AYES088uDdowewBATkr6IUmX/AiEA+bQqOu9L3aAdpFqa
++K2cplUwu5GW/yO4a0ztNndgPBQ=
+-----END CERTIFICATE-----
+";
+
+    /// Self-signed certificate for `CN=ca`, valid until 2030-01-01.
+    const CA_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBbjCCARWgAwIBAgIUe0HdnOCfd9TSv5hifgDvvNnjjyEwCgYIKoZIzj0EAwIw
+DTELMAkGA1UEAwwCY2EwHhcNMjUwMTAxMDAwMDAwWhcNMzAwMTAxMDAwMDAwWjAN
+MQswCQYDVQQDDAJjYTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABC2DAZoqT47C
+ybQXEhs81sWZNmEcGSjvsB9S0JTE17MWMVxOnE6/9L0emRxtpHFEVZRvP5oI52dA
+Fp8LdgtzIv2jUzBRMB0GA1UdDgQWBBT3LWSzoUfDji7+LX33uxhmx64UdjAfBgNV
+HSMEGDAWgBT3LWSzoUfDji7+LX33uxhmx64UdjAPBgNVHRMBAf8EBTADAQH/MAoG
+CCqGSM49BAMCA0cAMEQCIFm7Bd4kdSCiNfyERXkH9unFrOwHbVxuBZ9hqnwk66C7
+AiAwiUcy5YxIWY59DIYD9SB3W6QS6b0whTKcWtInEkIzCg==
+-----END CERTIFICATE-----
+";
+
+    fn secret(data: &[(&str, &str)]) -> Secret {
+        Secret {
+            data: Some(
+                data.iter()
+                    .map(|(k, v)| (k.to_string(), ByteString(v.as_bytes().to_vec())))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+            ..Secret::default()
+        }
+    }
+
+    fn expiry(secret: &Secret) -> Option<String> {
+        certificate_expiry(secret)
+            .unwrap()
+            .map(|expiry| expiry.to_string())
+    }
+
+    #[test]
+    fn soonest_expiry_of_chain() {
+        assert_eq!(
+            expiry(&secret(&[("tls.crt", CA_CERTIFICATE)])).as_deref(),
+            Some("2030-01-01T00:00:00Z")
+        );
+        for chain in [
+            format!("{LEAF_CERTIFICATE}{CA_CERTIFICATE}"),
+            format!("{CA_CERTIFICATE}{LEAF_CERTIFICATE}"),
+        ] {
+            assert_eq!(
+                expiry(&secret(&[("tls.crt", &chain)])).as_deref(),
+                Some("2026-01-01T00:00:00Z")
+            );
+        }
+    }
+
+    #[test]
+    fn secrets_without_certificates() {
+        assert_eq!(expiry(&Secret::default()), None);
+        assert_eq!(expiry(&secret(&[("ca.crt", CA_CERTIFICATE)])), None);
+        assert_eq!(expiry(&secret(&[("tls.crt", "")])), None);
+        assert!(
+            certificate_expiry(&secret(&[(
+                "tls.crt",
+                "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n"
+            )]))
+            .is_err()
+        );
+    }
+}
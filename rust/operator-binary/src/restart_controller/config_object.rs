@@ -0,0 +1,363 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt::Debug, marker::PhantomData};
+
+use futures::{StreamExt, TryStreamExt, stream::BoxStream};
+use jiff::Timestamp;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use stackable_operator::{
+    k8s_openapi::{
+        ByteString,
+        api::core::v1::{ConfigMap, Secret},
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    },
+    kube::{
+        Api, Resource,
+        api::PartialObjectMeta,
+        runtime::{metadata_watcher, watcher},
+    },
+};
+
+use crate::restart_controller::{certificate_expiry, pod_refs::ReferencedKeys};
+
+/// How the restarter detects that a ConfigMap or Secret has changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChangeDetection {
+    /// Any change of the object (including its labels and annotations) counts as a change, as the
+    /// version is derived from the `uid` and `resourceVersion` of the object.
+    ///
+    /// Only the metadata of the objects is watched.
+    #[default]
+    ResourceVersion,
+
+    /// Only changes of the contents (`data` and `binaryData`) count as a change, as the version is
+    /// derived from a digest of them. If a Pod only consumes some keys of an object, only changes
+    /// of these keys count.
+    ///
+    /// The full objects are watched, but only the digests are kept in memory.
+    ContentHash,
+}
+
+/// SHA-256 digest of a single key-value pair of a ConfigMap or Secret.
+pub type KeyDigest = [u8; 32];
+
+/// A ConfigMap or Secret as seen by the restarter.
+///
+/// Only the metadata and (depending on the [`ChangeDetection`]) digests of the contents are kept,
+/// to keep the memory footprint of the stores small.
+#[derive(Clone, Debug)]
+pub struct ConfigObject<K> {
+    pub metadata: ObjectMeta,
+
+    /// Digests of the contents of the object by key, only known for
+    /// [`ChangeDetection::ContentHash`].
+    pub key_digests: Option<BTreeMap<String, KeyDigest>>,
+
+    /// When the TLS certificates contained in the object expire, only known if certificate
+    /// expiries are tracked.
+    pub certificate_expiry: Option<Timestamp>,
+
+    _kind: PhantomData<fn() -> K>,
+}
+
+impl<K> ConfigObject<K> {
+    fn new(
+        mut metadata: ObjectMeta,
+        key_digests: Option<BTreeMap<String, KeyDigest>>,
+        certificate_expiry: Option<Timestamp>,
+    ) -> Self {
+        // The managed fields are by far the biggest part of the metadata and not needed at all
+        metadata.managed_fields = None;
+        Self {
+            metadata,
+            key_digests,
+            certificate_expiry,
+            _kind: PhantomData,
+        }
+    }
+
+    /// The version of the object, which changes whenever the object is considered to be changed.
+    ///
+    /// If the contents of the object are known, only changes of the `referenced_keys` are
+    /// considered. Otherwise any change of the object is.
+    pub fn restarter_version(&self, referenced_keys: &ReferencedKeys) -> Option<String> {
+        match &self.key_digests {
+            Some(key_digests) => {
+                let mut hasher = Sha256::new();
+                match referenced_keys {
+                    ReferencedKeys::All => {
+                        for (key, key_digest) in key_digests {
+                            hasher.update(key_digest_entry(key, Some(key_digest)));
+                        }
+                    }
+                    ReferencedKeys::Only(keys) => {
+                        // A referenced key that is missing is still relevant, as it might be
+                        // added later on
+                        for key in keys {
+                            hasher.update(key_digest_entry(key, key_digests.get(key)));
+                        }
+                    }
+                }
+                Some(format!("sha256:{:x}", hasher.finalize()))
+            }
+            None => {
+                let uid = self.metadata.uid.as_ref()?;
+                let resource_version = self.metadata.resource_version.as_ref()?;
+                Some(format!("{uid}/{resource_version}"))
+            }
+        }
+    }
+}
+
+impl<K: Resource<DynamicType = ()>> Resource for ConfigObject<K> {
+    type DynamicType = ();
+    type Scope = K::Scope;
+
+    fn kind(dt: &()) -> Cow<'_, str> {
+        K::kind(dt)
+    }
+
+    fn group(dt: &()) -> Cow<'_, str> {
+        K::group(dt)
+    }
+
+    fn version(dt: &()) -> Cow<'_, str> {
+        K::version(dt)
+    }
+
+    fn plural(dt: &()) -> Cow<'_, str> {
+        K::plural(dt)
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
+
+/// Objects whose contents can be digested for [`ChangeDetection::ContentHash`].
+pub trait DigestContent {
+    /// Returns the digests of all key-value pairs of the object.
+    fn key_digests(&self) -> BTreeMap<String, KeyDigest>;
+
+    /// Returns when the TLS certificates contained in the object expire, if any.
+    fn certificate_expiry(&self) -> Result<Option<Timestamp>, certificate_expiry::Error> {
+        Ok(None)
+    }
+}
+
+impl DigestContent for ConfigMap {
+    fn key_digests(&self) -> BTreeMap<String, KeyDigest> {
+        // Keys are unique across `data` and `binaryData`
+        let data = self.data.iter().flatten().map(|(k, v)| (k, v.as_bytes()));
+        let binary_data = self
+            .binary_data
+            .iter()
+            .flatten()
+            .map(|(k, ByteString(v))| (k, v.as_slice()));
+        data.chain(binary_data)
+            .map(|(k, v)| (k.clone(), Sha256::digest(v).into()))
+            .collect()
+    }
+}
+
+impl DigestContent for Secret {
+    fn key_digests(&self) -> BTreeMap<String, KeyDigest> {
+        // `stringData` is write-only and merged into `data` by the API server, so it is already
+        // covered by `data`.
+        self.data
+            .iter()
+            .flatten()
+            .map(|(k, ByteString(v))| (k.clone(), Sha256::digest(v).into()))
+            .collect()
+    }
+
+    fn certificate_expiry(&self) -> Result<Option<Timestamp>, certificate_expiry::Error> {
+        certificate_expiry::certificate_expiry(self)
+    }
+}
+
+/// Serializes a key and the digest of its value (if present) for the digest of a whole object.
+///
+/// The key is length-prefixed and the digest is prefixed with its presence, so that no two
+/// different sets of entries can produce the same input to the hash function.
+fn key_digest_entry(key: &str, key_digest: Option<&KeyDigest>) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(8 + key.len() + 1 + 32);
+    entry.extend_from_slice(&(key.len() as u64).to_be_bytes());
+    entry.extend_from_slice(key.as_bytes());
+    match key_digest {
+        Some(key_digest) => {
+            entry.push(1);
+            entry.extend_from_slice(key_digest);
+        }
+        None => entry.push(0),
+    }
+    entry
+}
+
+/// Watches ConfigMaps or Secrets, turning them into [`ConfigObject`]s as configured by the
+/// [`ChangeDetection`].
+///
+/// If `track_certificate_expiry` is set, the full objects are watched to determine when the TLS
+/// certificates contained in them expire.
+pub fn config_object_watcher<K>(
+    api: Api<K>,
+    config: watcher::Config,
+    change_detection: ChangeDetection,
+    track_certificate_expiry: bool,
+) -> BoxStream<'static, watcher::Result<watcher::Event<ConfigObject<K>>>>
+where
+    K: Resource<DynamicType = ()>
+        + DigestContent
+        + Clone
+        + DeserializeOwned
+        + Debug
+        + Send
+        + Sync
+        + 'static,
+{
+    match (change_detection, track_certificate_expiry) {
+        (ChangeDetection::ResourceVersion, false) => metadata_watcher(api, config)
+            .map_ok(|event| {
+                map_event(event, |obj: PartialObjectMeta<K>| {
+                    ConfigObject::new(obj.metadata, None, None)
+                })
+            })
+            .boxed(),
+        (change_detection, track_certificate_expiry) => watcher(api, config)
+            .map_ok(move |event| {
+                map_event(event, |obj: K| {
+                    let key_digests = (change_detection == ChangeDetection::ContentHash)
+                        .then(|| obj.key_digests());
+                    let certificate_expiry = if track_certificate_expiry {
+                        obj.certificate_expiry().unwrap_or_else(|error| {
+                            // The object is still tracked, but its certificates are not
+                            tracing::warn!(
+                                object = %obj.meta().name.as_deref().unwrap_or_default(),
+                                namespace = %obj.meta().namespace.as_deref().unwrap_or_default(),
+                                error = &error as &dyn std::error::Error,
+                                "failed to determine the expiry of certificates"
+                            );
+                            None
+                        })
+                    } else {
+                        None
+                    };
+                    ConfigObject::new(obj.meta().clone(), key_digests, certificate_expiry)
+                })
+            })
+            .boxed(),
+    }
+}
+
+fn map_event<A, B>(event: watcher::Event<A>, f: impl FnOnce(A) -> B) -> watcher::Event<B> {
+    match event {
+        watcher::Event::Apply(obj) => watcher::Event::Apply(f(obj)),
+        watcher::Event::Delete(obj) => watcher::Event::Delete(f(obj)),
+        watcher::Event::Init => watcher::Event::Init,
+        watcher::Event::InitApply(obj) => watcher::Event::InitApply(f(obj)),
+        watcher::Event::InitDone => watcher::Event::InitDone,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_map(data: &[(&str, &str)], binary_data: &[(&str, &[u8])]) -> ConfigMap {
+        ConfigMap {
+            data: Some(
+                data.iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+            binary_data: Some(
+                binary_data
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), ByteString(v.to_vec())))
+                    .collect(),
+            ),
+            ..ConfigMap::default()
+        }
+    }
+
+    fn version(config_map: &ConfigMap, referenced_keys: &ReferencedKeys) -> String {
+        ConfigObject::<ConfigMap>::new(
+            config_map.metadata.clone(),
+            Some(config_map.key_digests()),
+            None,
+        )
+        .restarter_version(referenced_keys)
+        .unwrap()
+    }
+
+    fn only(keys: &[&str]) -> ReferencedKeys {
+        ReferencedKeys::Only(keys.iter().map(|key| key.to_string()).collect())
+    }
+
+    #[test]
+    fn version_ignores_metadata() {
+        let mut labelled = config_map(&[("a", "1")], &[]);
+        labelled.metadata.labels = Some(BTreeMap::from([("foo".to_owned(), "bar".to_owned())]));
+        labelled.metadata.resource_version = Some("42".to_owned());
+        assert_eq!(
+            version(&config_map(&[("a", "1")], &[]), &ReferencedKeys::All),
+            version(&labelled, &ReferencedKeys::All)
+        );
+    }
+
+    #[test]
+    fn version_changes_with_data() {
+        let versions = [
+            version(&config_map(&[], &[]), &ReferencedKeys::All),
+            version(&config_map(&[("a", "1")], &[]), &ReferencedKeys::All),
+            version(&config_map(&[("a", "2")], &[]), &ReferencedKeys::All),
+            version(
+                &config_map(&[("a", "1"), ("b", "")], &[]),
+                &ReferencedKeys::All,
+            ),
+            version(&config_map(&[("a1", "")], &[]), &ReferencedKeys::All),
+            version(
+                &config_map(&[("a", "1")], &[("b", b"1")]),
+                &ReferencedKeys::All,
+            ),
+        ];
+        for (i, a) in versions.iter().enumerate() {
+            for b in &versions[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn version_is_shared_between_data_and_binary_data() {
+        assert_eq!(
+            version(&config_map(&[("a", "1")], &[]), &ReferencedKeys::All),
+            version(&config_map(&[], &[("a", b"1")]), &ReferencedKeys::All)
+        );
+    }
+
+    #[test]
+    fn version_only_considers_referenced_keys() {
+        let before = config_map(&[("a", "1"), ("b", "1")], &[]);
+        let after = config_map(&[("a", "1"), ("b", "2"), ("c", "1")], &[]);
+        assert_eq!(
+            version(&before, &only(&["a"])),
+            version(&after, &only(&["a"]))
+        );
+        assert_ne!(
+            version(&before, &only(&["a", "b"])),
+            version(&after, &only(&["a", "b"]))
+        );
+        assert_ne!(
+            version(&before, &only(&["c"])),
+            version(&after, &only(&["c"]))
+        );
+        assert_ne!(
+            version(&before, &ReferencedKeys::All),
+            version(&after, &ReferencedKeys::All)
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use jiff::{SignedDuration, Timestamp};
+use snafu::{OptionExt, Snafu};
+use stackable_operator::k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+/// Annotation overriding the share of their lifetime before their expiration date during which Pods
+/// can be evicted, e.g. `10%`.
+pub const EARLY_EVICTION_WINDOW_ANNOTATION: &str = "restarter.stackable.tech/early-eviction-window";
+
+/// Label set by the StatefulSet controller on its Pods, containing the ordinal of the Pod.
+const POD_INDEX_LABEL: &str = "apps.kubernetes.io/pod-index";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "early eviction window {window:?} is not a percentage of at least 0% and below 100%"
+    ))]
+    InvalidWindow { window: String },
+}
+
+/// The last part of the lifetime of a Pod (from its creation to its expiration date) during which
+/// it is evicted, as a percentage of the lifetime such as `10%`.
+///
+/// Pods are spread across the window (see [`spread_position`]), so that Pods that expire at the same
+/// time (e.g. because their certificates were issued together) are not all evicted at once. Pods are
+/// evicted when they expire if the window is `0%` (the default).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EarlyEvictionWindow {
+    /// At least 0 and below 100.
+    percentage: f64,
+}
+
+impl FromStr for EarlyEvictionWindow {
+    type Err = Error;
+
+    fn from_str(window: &str) -> Result<Self, Self::Err> {
+        let percentage = window.trim();
+        let percentage = percentage
+            .strip_suffix('%')
+            .unwrap_or(percentage)
+            .trim_end()
+            .parse::<f64>()
+            .ok()
+            .filter(|percentage| (0.0..100.0).contains(percentage))
+            .context(InvalidWindowSnafu { window })?;
+        Ok(Self { percentage })
+    }
+}
+
+impl Display for EarlyEvictionWindow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}%", self.percentage)
+    }
+}
+
+impl EarlyEvictionWindow {
+    /// Parses the window from the [`EARLY_EVICTION_WINDOW_ANNOTATION`], falling back to the
+    /// `default` if it is not present.
+    pub fn from_annotations(
+        annotations: &BTreeMap<String, String>,
+        default: Self,
+    ) -> Result<Self, Error> {
+        annotations
+            .get(EARLY_EVICTION_WINDOW_ANNOTATION)
+            .map_or(Ok(default), |window| window.parse())
+    }
+
+    /// Returns when a Pod that was created at `created_at` and expires at `expires_at` is evicted.
+    ///
+    /// `position` is the position of the Pod within the window (at least 0 and below 1), see
+    /// [`spread_position`].
+    pub fn eviction_time(
+        &self,
+        created_at: Timestamp,
+        expires_at: Timestamp,
+        position: f64,
+    ) -> Timestamp {
+        let lifetime = expires_at.duration_since(created_at);
+        if self.percentage == 0.0 || lifetime <= SignedDuration::ZERO {
+            return expires_at;
+        }
+        // The factor is below 1, so the eviction time is between the creation and the expiration
+        expires_at - lifetime.mul_f64(self.percentage / 100.0 * (1.0 - position))
+    }
+}
+
+/// Returns the position of the Pod within its early eviction window, at least 0 and below 1.
+///
+/// Pods of a StatefulSet are spread by their ordinal, so that any number of replicas is spread
+/// evenly (ordinals 0, 1, 2 and 3 are placed at 0, 1/2, 1/4 and 3/4 of the window), with an offset
+/// depending on the StatefulSet (so that StatefulSets expiring at the same time don't evict their
+/// Pods at the same time). Other Pods are placed depending on their UID.
+pub fn spread_position(pod: &ObjectMeta) -> f64 {
+    match (ordinal(pod), pod.owner_references.iter().flatten().next()) {
+        (Some(ordinal), Some(owner)) => (van_der_corput(ordinal) + unit_hash(&owner.uid)).fract(),
+        _ => unit_hash(
+            pod.uid
+                .as_deref()
+                .or(pod.name.as_deref())
+                .unwrap_or_default(),
+        ),
+    }
+}
+
+/// Returns the ordinal of a Pod that is owned by a StatefulSet.
+fn ordinal(pod: &ObjectMeta) -> Option<u64> {
+    let owner = pod
+        .owner_references
+        .iter()
+        .flatten()
+        .find(|owner| owner.kind == "StatefulSet")?;
+    if let Some(pod_index) = pod
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(POD_INDEX_LABEL))
+    {
+        return pod_index.parse().ok();
+    }
+    pod.name
+        .as_deref()?
+        .strip_prefix(&owner.name)?
+        .strip_prefix('-')?
+        .parse()
+        .ok()
+}
+
+/// The `n`th element of the base 2 van der Corput sequence, which spreads any prefix of the
+/// sequence evenly across `[0, 1)`.
+fn van_der_corput(n: u64) -> f64 {
+    to_unit(n.reverse_bits())
+}
+
+/// Maps `value` to `[0, 1)` using the FNV-1a hash, which (unlike the hasher of the standard
+/// library) is stable across operator versions.
+fn unit_hash(value: &str) -> f64 {
+    let hash = value.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    to_unit(hash)
+}
+
+fn to_unit(bits: u64) -> f64 {
+    // f64 has 53 bits of precision, dropping the lower bits keeps the result below 1
+    (bits >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+
+    use super::*;
+
+    fn pod(name: &str, owner_kind: &str, labels: &[(&str, &str)]) -> ObjectMeta {
+        ObjectMeta {
+            name: Some(name.to_owned()),
+            uid: Some(format!("{name}-uid")),
+            labels: Some(
+                labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            owner_references: Some(vec![OwnerReference {
+                kind: owner_kind.to_owned(),
+                name: "kafka-broker".to_owned(),
+                uid: "kafka-broker-uid".to_owned(),
+                ..OwnerReference::default()
+            }]),
+            ..ObjectMeta::default()
+        }
+    }
+
+    #[test]
+    fn parse_window() {
+        assert_eq!(
+            "10%".parse::<EarlyEvictionWindow>().unwrap(),
+            EarlyEvictionWindow { percentage: 10.0 }
+        );
+        assert_eq!(
+            " 25 ".parse::<EarlyEvictionWindow>().unwrap(),
+            EarlyEvictionWindow { percentage: 25.0 }
+        );
+        assert_eq!(
+            "0%".parse::<EarlyEvictionWindow>().unwrap().to_string(),
+            "0%"
+        );
+        for invalid in ["", "%", "100%", "-1%", "ten%", "NaN"] {
+            assert!(
+                invalid.parse::<EarlyEvictionWindow>().is_err(),
+                "{invalid:?} should be rejected"
+            );
+        }
+
+        let annotations =
+            BTreeMap::from([(EARLY_EVICTION_WINDOW_ANNOTATION.to_owned(), "5%".to_owned())]);
+        let default = EarlyEvictionWindow { percentage: 10.0 };
+        assert_eq!(
+            EarlyEvictionWindow::from_annotations(&annotations, default).unwrap(),
+            EarlyEvictionWindow { percentage: 5.0 }
+        );
+        assert_eq!(
+            EarlyEvictionWindow::from_annotations(&BTreeMap::new(), default).unwrap(),
+            default
+        );
+    }
+
+    #[test]
+    fn eviction_time_within_window() {
+        let created_at = "2026-01-01T00:00:00Z".parse::<Timestamp>().unwrap();
+        let expires_at = "2026-01-11T00:00:00Z".parse::<Timestamp>().unwrap();
+        let window = EarlyEvictionWindow { percentage: 10.0 };
+        assert_eq!(
+            window.eviction_time(created_at, expires_at, 0.0),
+            "2026-01-10T00:00:00Z".parse::<Timestamp>().unwrap()
+        );
+        assert_eq!(
+            window.eviction_time(created_at, expires_at, 0.5),
+            "2026-01-10T12:00:00Z".parse::<Timestamp>().unwrap()
+        );
+        assert_eq!(
+            EarlyEvictionWindow::default().eviction_time(created_at, expires_at, 0.5),
+            expires_at
+        );
+        // Pods created after their expiration date have no lifetime to spread across
+        assert_eq!(
+            window.eviction_time(expires_at, created_at, 0.5),
+            created_at
+        );
+    }
+
+    #[test]
+    fn spread_stateful_set_pods_by_ordinal() {
+        assert_eq!(
+            (0..8).map(van_der_corput).collect::<Vec<_>>(),
+            [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875]
+        );
+
+        assert_eq!(ordinal(&pod("kafka-broker-3", "StatefulSet", &[])), Some(3));
+        // The pod index label takes precedence over the name
+        assert_eq!(
+            ordinal(&pod(
+                "kafka-broker-3",
+                "StatefulSet",
+                &[(POD_INDEX_LABEL, "1")]
+            )),
+            Some(1)
+        );
+        assert_eq!(ordinal(&pod("kafka-broker-3", "ReplicaSet", &[])), None);
+        assert_eq!(ordinal(&pod("zookeeper-3", "StatefulSet", &[])), None);
+
+        // Pods of the same StatefulSet are shifted by the same offset
+        let broker_0 = spread_position(&pod("kafka-broker-0", "StatefulSet", &[]));
+        let broker_1 = spread_position(&pod("kafka-broker-1", "StatefulSet", &[]));
+        assert!((0.0..1.0).contains(&broker_0) && (0.0..1.0).contains(&broker_1));
+        assert!(((broker_0 - broker_1).abs() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn spread_other_pods_by_uid() {
+        let a = spread_position(&pod("kafka-broker-abc12", "ReplicaSet", &[]));
+        let b = spread_position(&pod("kafka-broker-def34", "ReplicaSet", &[]));
+        assert_ne!(a, b);
+        assert!((0.0..1.0).contains(&a) && (0.0..1.0).contains(&b));
+        // The position is deterministic
+        assert_eq!(
+            a,
+            spread_position(&pod("kafka-broker-abc12", "ReplicaSet", &[]))
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use stackable_operator::{
+    k8s_openapi::api::core::v1::Pod,
+    kube::{api::PartialObjectMeta, runtime::reflector::ObjectRef},
+};
+
+/// Delay before retrying the first failed reconciliation of a Pod, doubled for every further
+/// consecutive failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Maximum delay between retries of failed reconciliations.
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// The number of consecutive failed reconciliations per Pod.
+#[derive(Debug, Default)]
+pub struct ErrorBackoff {
+    failures: Mutex<HashMap<ObjectRef<PartialObjectMeta<Pod>>, u32>>,
+}
+
+impl ErrorBackoff {
+    /// Records that the reconciliation of the Pod failed (again) and returns the delay before
+    /// retrying it, which grows exponentially with the number of consecutive failures.
+    pub fn failed(&self, pod_ref: &ObjectRef<PartialObjectMeta<Pod>>) -> Duration {
+        let mut failures = self.failures.lock().unwrap();
+        let failures = failures.entry(pod_ref.clone()).or_default();
+        *failures = failures.saturating_add(1);
+        INITIAL_BACKOFF
+            .saturating_mul(2_u32.saturating_pow(*failures - 1))
+            .min(MAX_BACKOFF)
+    }
+
+    /// Forgets the failures of the Pod, e.g. because it was reconciled successfully.
+    pub fn forget(&self, pod_ref: &ObjectRef<PartialObjectMeta<Pod>>) {
+        self.failures.lock().unwrap().remove(pod_ref);
+    }
+
+    /// Forgets the failures of all Pods that don't `exist` anymore, e.g. because they were deleted
+    /// by someone else.
+    pub fn retain(&self, exists: impl Fn(&ObjectRef<PartialObjectMeta<Pod>>) -> bool) {
+        self.failures
+            .lock()
+            .unwrap()
+            .retain(|pod_ref, _| exists(pod_ref));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_off_exponentially_per_pod() {
+        let error_backoff = ErrorBackoff::default();
+        let pod_ref = ObjectRef::new("kafka-broker-0").within("default");
+        let backoffs = (0..8)
+            .map(|_| error_backoff.failed(&pod_ref).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(backoffs, [5, 10, 20, 40, 80, 160, 300, 300]);
+
+        let other_pod_ref = ObjectRef::new("kafka-broker-1").within("default");
+        assert_eq!(error_backoff.failed(&other_pod_ref).as_secs(), 5);
+
+        error_backoff.forget(&pod_ref);
+        assert_eq!(error_backoff.failed(&pod_ref).as_secs(), 5);
+
+        error_backoff.retain(|pod_ref| pod_ref == &other_pod_ref);
+        assert_eq!(error_backoff.failed(&pod_ref).as_secs(), 5);
+        assert_eq!(error_backoff.failed(&other_pod_ref).as_secs(), 10);
+    }
+}
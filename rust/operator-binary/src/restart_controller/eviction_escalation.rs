@@ -0,0 +1,282 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use snafu::{ResultExt, Snafu};
+use stackable_operator::{
+    k8s_openapi::api::core::v1::Pod,
+    kube::{api::PartialObjectMeta, runtime::reflector::ObjectRef},
+    time::{Duration, DurationParseError},
+};
+
+/// Annotation (on Pods or their Namespace) overriding how long after their expiration date Pods
+/// whose eviction is blocked by a PodDisruptionBudget are reported, e.g. `10m`.
+pub const EVICTION_WARNING_AFTER_ANNOTATION: &str =
+    "restarter.stackable.tech/eviction-warning-after";
+
+/// Annotation (on Pods or their Namespace) stating how long after their expiration date Pods whose
+/// eviction is blocked by a PodDisruptionBudget are deleted instead, e.g. `1h`.
+pub const FORCE_AFTER_ANNOTATION: &str = "restarter.stackable.tech/force-after";
+
+/// Type of the Pod condition that is set once a blocked eviction is reported.
+pub const EVICTION_BLOCKED_CONDITION: &str = "restarter.stackable.tech/EvictionBlocked";
+
+/// Delay before retrying the first blocked eviction of a Pod, doubled for every further attempt.
+const INITIAL_BACKOFF: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Maximum delay between retries of blocked evictions.
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("invalid duration {value:?} in annotation {annotation:?}"))]
+    InvalidDuration {
+        source: DurationParseError,
+        annotation: String,
+        value: String,
+    },
+}
+
+/// How Pods are handled whose eviction is blocked by a PodDisruptionBudget past their expiration
+/// date.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EscalationPolicy {
+    /// After which time a `EvictionBlocked` event is emitted and the [`EVICTION_BLOCKED_CONDITION`]
+    /// is set, never if [`None`].
+    pub warning_after: Option<Duration>,
+
+    /// After which time the Pod is deleted instead of evicted (ignoring the PodDisruptionBudget),
+    /// never if [`None`].
+    pub force_after: Option<Duration>,
+}
+
+/// The next step of the [`EscalationPolicy`] that is due.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Escalation {
+    /// Keep retrying the eviction.
+    Retry,
+
+    /// Report that the eviction is blocked (and keep retrying).
+    Warn,
+
+    /// Delete the Pod.
+    ForceDelete,
+}
+
+impl EscalationPolicy {
+    /// Overrides the policy using the [`EVICTION_WARNING_AFTER_ANNOTATION`] and the
+    /// [`FORCE_AFTER_ANNOTATION`], if present.
+    pub fn with_overrides(self, annotations: &BTreeMap<String, String>) -> Result<Self, Error> {
+        let duration = |annotation: &str| {
+            annotations
+                .get(annotation)
+                .map(|value| {
+                    value
+                        .parse::<Duration>()
+                        .context(InvalidDurationSnafu { annotation, value })
+                })
+                .transpose()
+        };
+        Ok(Self {
+            warning_after: duration(EVICTION_WARNING_AFTER_ANNOTATION)?.or(self.warning_after),
+            force_after: duration(FORCE_AFTER_ANNOTATION)?.or(self.force_after),
+        })
+    }
+
+    /// Returns the step that is due for a Pod that expired `overdue` ago.
+    ///
+    /// Blocked evictions are only reported once, `warned` states whether this happened already.
+    pub fn escalation(&self, overdue: std::time::Duration, warned: bool) -> Escalation {
+        if self
+            .force_after
+            .is_some_and(|force_after| overdue >= *force_after)
+        {
+            Escalation::ForceDelete
+        } else if !warned
+            && self
+                .warning_after
+                .is_some_and(|warning_after| overdue >= *warning_after)
+        {
+            Escalation::Warn
+        } else {
+            Escalation::Retry
+        }
+    }
+
+    /// Returns the time until the next step is due for a Pod that expired `overdue` ago, if any.
+    pub fn time_until_next_escalation(
+        &self,
+        overdue: std::time::Duration,
+        warned: bool,
+    ) -> Option<std::time::Duration> {
+        let warning_after = self.warning_after.filter(|_| !warned);
+        [warning_after, self.force_after]
+            .into_iter()
+            .flatten()
+            .filter_map(|after| after.checked_sub(overdue))
+            .filter(|until| !until.is_zero())
+            .min()
+    }
+}
+
+/// The Pods whose eviction is currently blocked by a PodDisruptionBudget.
+#[derive(Debug, Default)]
+pub struct BlockedEvictions {
+    pods: Mutex<HashMap<ObjectRef<PartialObjectMeta<Pod>>, BlockedEviction>>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockedEviction {
+    /// The number of consecutive evictions of the Pod that were blocked.
+    pub attempts: u32,
+
+    /// Whether the blocked eviction was reported already.
+    pub warned: bool,
+}
+
+impl BlockedEvictions {
+    /// Records that the eviction of the Pod was blocked (again).
+    pub fn blocked(&self, pod_ref: &ObjectRef<PartialObjectMeta<Pod>>) -> BlockedEviction {
+        let mut pods = self.pods.lock().unwrap();
+        let blocked = pods.entry(pod_ref.clone()).or_default();
+        blocked.attempts = blocked.attempts.saturating_add(1);
+        *blocked
+    }
+
+    /// Records that the blocked eviction of the Pod was reported.
+    pub fn warned(&self, pod_ref: &ObjectRef<PartialObjectMeta<Pod>>) {
+        if let Some(blocked) = self.pods.lock().unwrap().get_mut(pod_ref) {
+            blocked.warned = true;
+        }
+    }
+
+    /// Forgets the Pod, e.g. because it was evicted.
+    pub fn forget(&self, pod_ref: &ObjectRef<PartialObjectMeta<Pod>>) {
+        self.pods.lock().unwrap().remove(pod_ref);
+    }
+
+    /// Forgets all Pods that don't `exist` anymore, e.g. because they were deleted by someone else.
+    pub fn retain(&self, exists: impl Fn(&ObjectRef<PartialObjectMeta<Pod>>) -> bool) {
+        self.pods
+            .lock()
+            .unwrap()
+            .retain(|pod_ref, _| exists(pod_ref));
+    }
+}
+
+impl BlockedEviction {
+    /// The delay before retrying the eviction, which grows exponentially with the number of
+    /// attempts.
+    pub fn backoff(&self) -> std::time::Duration {
+        INITIAL_BACKOFF
+            .saturating_mul(2_u32.saturating_pow(self.attempts.saturating_sub(1)))
+            .min(MAX_BACKOFF)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(minutes: u64) -> std::time::Duration {
+        std::time::Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn override_policy() {
+        let defaults = EscalationPolicy {
+            warning_after: Some(Duration::from_minutes_unchecked(10)),
+            force_after: None,
+        };
+        let namespace_annotations =
+            BTreeMap::from([(FORCE_AFTER_ANNOTATION.to_owned(), "1h".to_owned())]);
+        let pod_annotations = BTreeMap::from([(
+            EVICTION_WARNING_AFTER_ANNOTATION.to_owned(),
+            "5m".to_owned(),
+        )]);
+        assert_eq!(
+            defaults
+                .with_overrides(&namespace_annotations)
+                .unwrap()
+                .with_overrides(&pod_annotations)
+                .unwrap(),
+            EscalationPolicy {
+                warning_after: Some(Duration::from_minutes_unchecked(5)),
+                force_after: Some(Duration::from_minutes_unchecked(60)),
+            }
+        );
+
+        let invalid = BTreeMap::from([(FORCE_AFTER_ANNOTATION.to_owned(), "soon".to_owned())]);
+        assert!(defaults.with_overrides(&invalid).is_err());
+    }
+
+    #[test]
+    fn escalate() {
+        let policy = EscalationPolicy {
+            warning_after: Some(Duration::from_minutes_unchecked(10)),
+            force_after: Some(Duration::from_minutes_unchecked(60)),
+        };
+        assert_eq!(policy.escalation(minutes(5), false), Escalation::Retry);
+        assert_eq!(
+            policy.time_until_next_escalation(minutes(5), false),
+            Some(minutes(5))
+        );
+        assert_eq!(policy.escalation(minutes(10), false), Escalation::Warn);
+        // Blocked evictions are only reported once
+        assert_eq!(policy.escalation(minutes(10), true), Escalation::Retry);
+        assert_eq!(
+            policy.time_until_next_escalation(minutes(10), true),
+            Some(minutes(50))
+        );
+        assert_eq!(
+            policy.escalation(minutes(60), true),
+            Escalation::ForceDelete
+        );
+        assert_eq!(policy.time_until_next_escalation(minutes(60), true), None);
+
+        assert_eq!(
+            EscalationPolicy::default().escalation(minutes(60), false),
+            Escalation::Retry
+        );
+    }
+
+    #[test]
+    fn back_off_exponentially() {
+        let blocked_evictions = BlockedEvictions::default();
+        let pod_ref = ObjectRef::new("kafka-broker-0").within("default");
+        let backoffs = (0..8)
+            .map(|_| blocked_evictions.blocked(&pod_ref).backoff().as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(backoffs, [5, 10, 20, 40, 80, 160, 300, 300]);
+
+        blocked_evictions.warned(&pod_ref);
+        assert_eq!(
+            blocked_evictions.blocked(&pod_ref),
+            BlockedEviction {
+                attempts: 9,
+                warned: true
+            }
+        );
+        blocked_evictions.forget(&pod_ref);
+        assert_eq!(
+            blocked_evictions.blocked(&pod_ref),
+            BlockedEviction {
+                attempts: 1,
+                warned: false
+            }
+        );
+
+        let other_pod_ref = ObjectRef::new("kafka-broker-1").within("default");
+        blocked_evictions.blocked(&other_pod_ref);
+        blocked_evictions.retain(|pod_ref| pod_ref == &other_pod_ref);
+        assert_eq!(
+            blocked_evictions.blocked(&pod_ref),
+            BlockedEviction {
+                attempts: 1,
+                warned: false
+            }
+        );
+        assert_eq!(blocked_evictions.blocked(&other_pod_ref).attempts, 2);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use snafu::{ResultExt, Snafu};
+
+/// Prefix of the workload annotations listing ConfigMaps whose changes don't restart the workload.
+pub const IGNORE_CONFIG_MAP_ANNOTATION_PREFIX: &str = "restarter.stackable.tech/ignore-configmap.";
+
+/// Prefix of the workload annotations listing Secrets whose changes don't restart the workload.
+pub const IGNORE_SECRET_ANNOTATION_PREFIX: &str = "restarter.stackable.tech/ignore-secret.";
+
+/// Prefix of patterns that are (anchored) regular expressions rather than globs.
+const REGEX_PREFIX: &str = "re:";
+
+#[derive(Debug, Snafu)]
+#[snafu(display("invalid pattern {pattern:?} in annotation {annotation:?}"))]
+pub struct Error {
+    source: regex::Error,
+    annotation: String,
+    pattern: String,
+}
+
+/// The names of ConfigMaps or Secrets that are ignored by a workload.
+///
+/// Every pattern is either a glob (where `*` matches any number of characters and `?` matches a
+/// single character, a name without these characters only matches itself), or a regular expression
+/// prefixed by `re:`. Both need to match the whole name.
+#[derive(Debug, Default)]
+pub struct IgnorePatterns {
+    patterns: Vec<Regex>,
+}
+
+impl IgnorePatterns {
+    /// Parses the patterns of all annotations starting with `annotation_prefix`.
+    ///
+    /// Invalid patterns are skipped (so they never match) and returned separately.
+    pub fn from_annotations(
+        annotations: &BTreeMap<String, String>,
+        annotation_prefix: &str,
+    ) -> (Self, Vec<Error>) {
+        let mut patterns = Vec::new();
+        let mut errors = Vec::new();
+        for (annotation, pattern) in annotations {
+            if !annotation.starts_with(annotation_prefix) {
+                continue;
+            }
+            match parse_pattern(pattern).context(Snafu {
+                annotation,
+                pattern,
+            }) {
+                Ok(pattern) => patterns.push(pattern),
+                Err(error) => errors.push(error),
+            }
+        }
+        (Self { patterns }, errors)
+    }
+
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(name))
+    }
+}
+
+/// Returns the invalid ConfigMap and Secret ignore patterns of a workload.
+pub fn invalid_patterns(annotations: &BTreeMap<String, String>) -> Vec<Error> {
+    [
+        IGNORE_CONFIG_MAP_ANNOTATION_PREFIX,
+        IGNORE_SECRET_ANNOTATION_PREFIX,
+    ]
+    .into_iter()
+    .flat_map(|prefix| IgnorePatterns::from_annotations(annotations, prefix).1)
+    .collect()
+}
+
+fn parse_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    let regex = match pattern.strip_prefix(REGEX_PREFIX) {
+        Some(regex) => regex.to_owned(),
+        None => pattern
+            .split_inclusive(['*', '?'])
+            .map(|part| match part.strip_suffix('*') {
+                Some(literal) => format!("{}.*", regex::escape(literal)),
+                None => match part.strip_suffix('?') {
+                    Some(literal) => format!("{}.", regex::escape(literal)),
+                    None => regex::escape(part),
+                },
+            })
+            .collect(),
+    };
+    Regex::new(&format!("^(?:{regex})$"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> (IgnorePatterns, Vec<Error>) {
+        let annotations = patterns
+            .iter()
+            .enumerate()
+            .map(|(i, pattern)| {
+                (
+                    format!("{IGNORE_CONFIG_MAP_ANNOTATION_PREFIX}{i}"),
+                    pattern.to_string(),
+                )
+            })
+            .chain([(
+                format!("{IGNORE_SECRET_ANNOTATION_PREFIX}0"),
+                "secret".to_owned(),
+            )])
+            .collect();
+        IgnorePatterns::from_annotations(&annotations, IGNORE_CONFIG_MAP_ANNOTATION_PREFIX)
+    }
+
+    #[test]
+    fn exact_names() {
+        let (patterns, errors) = patterns(&["simple-trino.coordinator"]);
+        assert!(errors.is_empty());
+        assert!(patterns.is_ignored("simple-trino.coordinator"));
+        assert!(!patterns.is_ignored("simple-trino-coordinator"));
+        assert!(!patterns.is_ignored("simple-trino.coordinator-0"));
+        // Other annotation prefixes are not considered
+        assert!(!patterns.is_ignored("secret"));
+    }
+
+    #[test]
+    fn globs() {
+        let (patterns, errors) = patterns(&["*-discovery", "trino-?"]);
+        assert!(errors.is_empty());
+        assert!(patterns.is_ignored("simple-trino-discovery"));
+        assert!(patterns.is_ignored("-discovery"));
+        assert!(!patterns.is_ignored("simple-trino-discovery-0"));
+        assert!(patterns.is_ignored("trino-0"));
+        assert!(!patterns.is_ignored("trino-10"));
+    }
+
+    #[test]
+    fn regexes() {
+        let (patterns, errors) = patterns(&[r"re:simple-trino-coordinator-default-[0-9a-f]+"]);
+        assert!(errors.is_empty());
+        assert!(patterns.is_ignored("simple-trino-coordinator-default-1a2b"));
+        // Regular expressions are anchored
+        assert!(!patterns.is_ignored("x-simple-trino-coordinator-default-1a2b"));
+        assert!(!patterns.is_ignored("simple-trino-coordinator-default-1a2b-x"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let (patterns, errors) = patterns(&["re:(unclosed", "valid"]);
+        assert_eq!(errors.len(), 1);
+        assert!(patterns.is_ignored("valid"));
+        assert!(!patterns.is_ignored("(unclosed"));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use jiff::{SignedDuration, Timestamp};
+use snafu::{OptionExt, ResultExt, Snafu};
+use stackable_operator::time::{Duration, DurationParseError};
+
+use crate::utils::cron::{self, CronSchedule};
+
+/// Annotation restricting restarts (of workloads) or evictions (of Pods) to maintenance windows.
+pub const MAINTENANCE_WINDOW_ANNOTATION: &str = "restarter.stackable.tech/maintenance-window";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "maintenance window {window:?} is not of the form \
+        \"[CRON_TZ=<time-zone>] <minute> <hour> <day-of-month> <month> <day-of-week> <duration>\""
+    ))]
+    MalformedWindow { window: String },
+
+    #[snafu(display("invalid duration of maintenance window {window:?}"))]
+    InvalidDuration {
+        source: DurationParseError,
+        window: String,
+    },
+
+    #[snafu(display("invalid schedule of maintenance window {window:?}"))]
+    InvalidSchedule { source: cron::Error, window: String },
+}
+
+/// One or more maintenance windows, separated by `;`.
+///
+/// Every window opens whenever its cron schedule matches and stays open for its duration, e.g.
+/// `CRON_TZ=Europe/Berlin 0 2 * * SAT,SUN 4h` is open on weekends from 2 to 6 o'clock (Berlin time).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaintenanceWindows {
+    expression: String,
+    windows: Vec<MaintenanceWindow>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct MaintenanceWindow {
+    schedule: CronSchedule,
+    duration: SignedDuration,
+}
+
+impl FromStr for MaintenanceWindows {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let windows = expression
+            .split(';')
+            .filter(|window| !window.trim().is_empty())
+            .map(|window| {
+                let (schedule, duration) = window
+                    .trim()
+                    .rsplit_once(char::is_whitespace)
+                    .context(MalformedWindowSnafu { window })?;
+                let duration = duration
+                    .parse::<Duration>()
+                    .context(InvalidDurationSnafu { window })?;
+                Ok(MaintenanceWindow {
+                    schedule: schedule.parse().context(InvalidScheduleSnafu { window })?,
+                    duration: SignedDuration::try_from(*duration).unwrap_or(SignedDuration::MAX),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if windows.is_empty() {
+            return MalformedWindowSnafu { window: expression }.fail();
+        }
+        Ok(Self {
+            expression: expression.trim().to_owned(),
+            windows,
+        })
+    }
+}
+
+impl Display for MaintenanceWindows {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl MaintenanceWindows {
+    /// Parses the maintenance windows from the [`MAINTENANCE_WINDOW_ANNOTATION`], if present.
+    pub fn from_annotations(annotations: &BTreeMap<String, String>) -> Result<Option<Self>, Error> {
+        annotations
+            .get(MAINTENANCE_WINDOW_ANNOTATION)
+            .map(|windows| windows.parse())
+            .transpose()
+    }
+
+    /// Whether any of the maintenance windows is open at `now`.
+    pub fn is_open(&self, now: Timestamp) -> bool {
+        self.windows.iter().any(|window| {
+            let opened_after = now.checked_sub(window.duration).unwrap_or(Timestamp::MIN);
+            window
+                .schedule
+                .last_at_or_before(now, opened_after)
+                .is_some_and(|opened_at| opened_at > opened_after)
+        })
+    }
+
+    /// Returns when the next maintenance window opens after `now`, or [`None`] if none ever
+    /// opens.
+    pub fn next_opening(&self, now: Timestamp) -> Option<Timestamp> {
+        self.windows
+            .iter()
+            .filter_map(|window| window.schedule.next_after(now))
+            .min()
+    }
+}
+
+/// Returns the (non-negative) time from `now` until `until`.
+pub fn duration_until(now: Timestamp, until: Timestamp) -> std::time::Duration {
+    std::time::Duration::try_from(until.duration_since(now)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        timestamp.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_windows() {
+        let windows: MaintenanceWindows =
+            "CRON_TZ=Europe/Berlin 0 2 * * SAT,SUN 4h; 30 22 * * * 1h30m"
+                .parse()
+                .unwrap();
+        assert_eq!(windows.windows.len(), 2);
+        assert_eq!(windows.windows[0].duration, SignedDuration::from_hours(4));
+        assert_eq!(windows.windows[1].duration, SignedDuration::from_mins(90));
+
+        // A trailing separator is fine
+        assert!("0 2 * * * 4h;".parse::<MaintenanceWindows>().is_ok());
+
+        for windows in ["", ";", "0 2 * * *", "0 2 * * * foo", "0 2 * * 4h"] {
+            assert!(
+                windows.parse::<MaintenanceWindows>().is_err(),
+                "{windows:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn window_is_open() {
+        // 2024-03-02 is a Saturday, Berlin is at UTC+1
+        let windows: MaintenanceWindows =
+            "CRON_TZ=Europe/Berlin 0 2 * * SAT,SUN 4h".parse().unwrap();
+        assert!(!windows.is_open(ts("2024-03-02T00:59:59Z")));
+        assert!(windows.is_open(ts("2024-03-02T01:00:00Z")));
+        assert!(windows.is_open(ts("2024-03-02T04:59:59Z")));
+        assert!(!windows.is_open(ts("2024-03-02T05:00:00Z")));
+        assert!(windows.is_open(ts("2024-03-03T03:00:00Z")));
+        assert!(!windows.is_open(ts("2024-03-04T03:00:00Z")));
+    }
+
+    #[test]
+    fn windows_spanning_midnight() {
+        let windows: MaintenanceWindows = "0 22 * * FRI 8h".parse().unwrap();
+        // 2024-03-01 is a Friday
+        assert!(windows.is_open(ts("2024-03-02T05:59:00Z")));
+        assert!(!windows.is_open(ts("2024-03-02T06:00:00Z")));
+    }
+
+    #[test]
+    fn next_opening() {
+        let windows: MaintenanceWindows = "0 2 * * SAT 4h; 0 12 * * WED 1h".parse().unwrap();
+        // 2024-02-28 is a Wednesday
+        assert_eq!(
+            windows.next_opening(ts("2024-02-28T00:00:00Z")),
+            Some(ts("2024-02-28T12:00:00Z"))
+        );
+        assert_eq!(
+            windows.next_opening(ts("2024-02-28T12:00:00Z")),
+            Some(ts("2024-03-02T02:00:00Z"))
+        );
+    }
+}
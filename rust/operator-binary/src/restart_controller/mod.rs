@@ -1,2 +1,57 @@
+use std::collections::BTreeMap;
+
+pub mod canary;
+pub mod certificate_expiry;
+pub mod config_object;
+pub mod early_eviction;
+pub mod error_backoff;
+pub mod eviction_escalation;
+pub mod ignore_patterns;
+pub mod maintenance_window;
+pub mod namespace_selection;
+pub mod owner_evictions;
+pub mod pending_restarts;
 pub mod pod;
-pub mod statefulset;
+pub mod pod_refs;
+pub mod restart_order;
+pub mod restart_policy;
+pub mod rollout_budget;
+pub mod workload;
+pub mod workload_index;
+pub mod workload_kind;
+
+/// Annotation overriding whether restarts (of workloads) or evictions (of Pods) are only reported
+/// instead of performed.
+pub const DRY_RUN_ANNOTATION: &str = "restarter.stackable.tech/dry-run";
+
+/// Prefix of the annotations stating when a Pod expires (e.g. because its certificates expire), it
+/// is evicted once the soonest of them is reached.
+pub const EXPIRES_AT_ANNOTATION_PREFIX: &str = "restarter.stackable.tech/expires-at.";
+
+/// Label opting Pods into being evicted once they expire, only Pods labelled with `true` are watched
+/// by the Pod restarter (unless it watches all Pods).
+///
+/// It is set by the Pod mutating webhook on Pods that [expire](expires), but can also be set by the
+/// component managing the Pods (such as the secret-operator).
+pub const EXPIRY_MANAGED_LABEL: &str = "restarter.stackable.tech/expiry-managed";
+
+/// Whether Pods with the given `annotations` expire at some point, either due to
+/// [expiry annotations](EXPIRES_AT_ANNOTATION_PREFIX) or [restart policies](restart_policy).
+pub fn expires(annotations: &BTreeMap<String, String>) -> bool {
+    annotations.keys().any(|key| {
+        key.starts_with(EXPIRES_AT_ANNOTATION_PREFIX)
+            || key == restart_policy::MAX_AGE_ANNOTATION
+            || key == restart_policy::RESTART_SCHEDULE_ANNOTATION
+    })
+}
+
+/// Whether restarts or evictions of an object with the given `annotations` are only reported.
+///
+/// The [`DRY_RUN_ANNOTATION`] (`true` or `false`) takes precedence over the global setting.
+pub fn is_dry_run(globally_enabled: bool, annotations: &BTreeMap<String, String>) -> bool {
+    match annotations.get(DRY_RUN_ANNOTATION).map(String::as_str) {
+        Some("true") => true,
+        Some("false") => false,
+        _ => globally_enabled,
+    }
+}
@@ -0,0 +1,134 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use stackable_operator::kube::{ResourceExt, runtime::watcher};
+
+use crate::utils::label_selector::LabelSelectorExpression;
+
+/// The namespaces matching the namespace selector of the restarter, only workloads in these
+/// namespaces are restarted.
+///
+/// It is kept up to date by feeding it the watcher events of the namespaces matching the
+/// `label_selector`. Namespaces are only selected once they have been listed.
+#[derive(Debug)]
+pub struct NamespaceSelection {
+    label_selector: LabelSelectorExpression,
+    state: Mutex<SelectionState>,
+}
+
+#[derive(Debug, Default)]
+struct SelectionState {
+    namespaces: HashSet<String>,
+
+    /// The namespaces seen during the current (re-)list, [`None`] if no list is in progress
+    relisted_namespaces: Option<HashSet<String>>,
+}
+
+impl NamespaceSelection {
+    pub fn new(label_selector: LabelSelectorExpression) -> Self {
+        Self {
+            label_selector,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn label_selector(&self) -> &LabelSelectorExpression {
+        &self.label_selector
+    }
+
+    /// Updates the selected namespaces according to a watcher event of the namespaces.
+    ///
+    /// Returns the namespace if it became selected by this event.
+    pub fn apply_watcher_event<K: ResourceExt>(&self, event: &watcher::Event<K>) -> Option<String> {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            watcher::Event::Apply(namespace) => {
+                let name = namespace.name_any();
+                state.namespaces.insert(name.clone()).then_some(name)
+            }
+            watcher::Event::Delete(namespace) => {
+                // Namespaces that don't match the label selector anymore are deleted as well
+                state.namespaces.remove(&namespace.name_any());
+                None
+            }
+            watcher::Event::Init => {
+                state.relisted_namespaces = Some(HashSet::new());
+                None
+            }
+            watcher::Event::InitApply(namespace) => {
+                let name = namespace.name_any();
+                if let Some(relisted_namespaces) = &mut state.relisted_namespaces {
+                    relisted_namespaces.insert(name.clone());
+                }
+                state.namespaces.insert(name.clone()).then_some(name)
+            }
+            watcher::Event::InitDone => {
+                // Namespaces that were not part of the relist are not selected anymore
+                if let Some(relisted_namespaces) = state.relisted_namespaces.take() {
+                    state.namespaces = relisted_namespaces;
+                }
+                None
+            }
+        }
+    }
+
+    pub fn contains(&self, namespace: &str) -> bool {
+        self.state.lock().unwrap().namespaces.contains(namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::{k8s_openapi::api::core::v1::Namespace, kube::api::PartialObjectMeta};
+
+    use super::*;
+
+    fn namespace(name: &str) -> PartialObjectMeta<Namespace> {
+        let mut namespace = PartialObjectMeta::<Namespace>::default();
+        namespace.metadata.name = Some(name.to_owned());
+        namespace
+    }
+
+    #[test]
+    fn track_selected_namespaces() {
+        let selection = NamespaceSelection::new("tenant=a".parse().unwrap());
+        assert!(!selection.contains("a"));
+
+        assert_eq!(
+            selection.apply_watcher_event(&watcher::Event::Apply(namespace("a"))),
+            Some("a".to_owned())
+        );
+        // Changes of already selected namespaces don't select them again
+        assert_eq!(
+            selection.apply_watcher_event(&watcher::Event::Apply(namespace("a"))),
+            None
+        );
+        assert!(selection.contains("a"));
+
+        selection.apply_watcher_event(&watcher::Event::Delete(namespace("a")));
+        assert!(!selection.contains("a"));
+    }
+
+    #[test]
+    fn relist_drops_unselected_namespaces() {
+        let selection = NamespaceSelection::new("tenant=a".parse().unwrap());
+        selection.apply_watcher_event(&watcher::Event::Apply(namespace("a")));
+        selection.apply_watcher_event(&watcher::Event::Apply(namespace("b")));
+
+        selection.apply_watcher_event(&watcher::Event::<PartialObjectMeta<Namespace>>::Init);
+        assert_eq!(
+            selection.apply_watcher_event(&watcher::Event::InitApply(namespace("b"))),
+            None
+        );
+        assert_eq!(
+            selection.apply_watcher_event(&watcher::Event::InitApply(namespace("c"))),
+            Some("c".to_owned())
+        );
+        // The old state is kept until the relist is complete
+        assert!(selection.contains("a"));
+
+        selection.apply_watcher_event(&watcher::Event::<PartialObjectMeta<Namespace>>::InitDone);
+        assert!(!selection.contains("a"));
+        assert!(selection.contains("b"));
+        assert!(selection.contains("c"));
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use stackable_operator::k8s_openapi::{
+    api::core::v1::Pod,
+    apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference},
+};
+
+/// Annotation overriding whether expired Pods are evicted one at a time per owning StatefulSet or
+/// ReplicaSet.
+pub const SERIALIZE_EVICTIONS_ANNOTATION: &str = "restarter.stackable.tech/serialize-evictions";
+
+/// Whether evictions of Pods with the given `annotations` are serialized per owner.
+///
+/// The [`SERIALIZE_EVICTIONS_ANNOTATION`] (`true` or `false`) takes precedence over the global
+/// setting.
+pub fn is_serialized(globally_enabled: bool, annotations: &BTreeMap<String, String>) -> bool {
+    match annotations
+        .get(SERIALIZE_EVICTIONS_ANNOTATION)
+        .map(String::as_str)
+    {
+        Some("true") => true,
+        Some("false") => false,
+        _ => globally_enabled,
+    }
+}
+
+/// Returns the StatefulSet or ReplicaSet controlling the Pod, if any.
+pub fn owner(pod: &ObjectMeta) -> Option<&OwnerReference> {
+    pod.owner_references.iter().flatten().find(|owner| {
+        owner.controller == Some(true)
+            && owner.api_version == "apps/v1"
+            && matches!(owner.kind.as_str(), "StatefulSet" | "ReplicaSet")
+    })
+}
+
+/// Whether all replicas of a StatefulSet or ReplicaSet are ready, given the `pods` it owns.
+///
+/// This is based on the Pods rather than on the status of the owner, which might still count an
+/// evicted Pod as ready for a while after its eviction. Pods that are being deleted are not
+/// considered to be ready, so that the replacement of an evicted Pod needs to be ready.
+pub fn all_replicas_ready<'a>(
+    replicas: Option<i32>,
+    pods: impl IntoIterator<Item = &'a Pod>,
+) -> bool {
+    let ready_pods = pods
+        .into_iter()
+        .filter(|pod| pod.metadata.deletion_timestamp.is_none() && is_ready(pod))
+        .count();
+    ready_pods >= usize::try_from(replicas.unwrap_or(1)).unwrap_or_default()
+}
+
+/// Whether the Pod has the condition `Ready`.
+fn is_ready(pod: &Pod) -> bool {
+    pod.status
+        .iter()
+        .flat_map(|status| &status.conditions)
+        .flatten()
+        .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+}
+
+/// The Pods that are currently evicted, by the UID of their owner.
+#[derive(Debug, Default)]
+pub struct OwnerEvictions {
+    evictions: Mutex<HashMap<String, EvictedPod>>,
+}
+
+/// A Pod that was evicted, identified by its UID as StatefulSets reuse the names of their Pods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvictedPod {
+    pub namespace: String,
+    pub name: String,
+    pub uid: String,
+}
+
+impl EvictedPod {
+    pub fn new(pod: &ObjectMeta) -> Self {
+        Self {
+            namespace: pod.namespace.clone().unwrap_or_default(),
+            name: pod.name.clone().unwrap_or_default(),
+            uid: pod.uid.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl OwnerEvictions {
+    /// Reserves the eviction of the Pod for its owner, unless another Pod of the owner is still being
+    /// evicted, which is returned instead.
+    ///
+    /// Evicted Pods are forgotten once they don't `exist` anymore. This must only be called once
+    /// [all replicas of the owner are ready](all_replicas_ready), so that the reservation is kept
+    /// until the replacement of the evicted Pod is ready.
+    pub fn reserve(
+        &self,
+        owner_uid: &str,
+        pod: EvictedPod,
+        exists: impl Fn(&EvictedPod) -> bool,
+    ) -> Result<(), EvictedPod> {
+        let mut evictions = self.evictions.lock().unwrap();
+        evictions.retain(|_, evicted_pod| exists(evicted_pod));
+        match evictions.get(owner_uid) {
+            Some(evicted_pod) if evicted_pod.uid != pod.uid => Err(evicted_pod.clone()),
+            _ => {
+                evictions.insert(owner_uid.to_owned(), pod);
+                Ok(())
+            }
+        }
+    }
+
+    /// Releases the reservation of the Pod, e.g. because it was not evicted after all.
+    pub fn release(&self, owner_uid: &str, pod_uid: &str) {
+        let mut evictions = self.evictions.lock().unwrap();
+        if evictions
+            .get(owner_uid)
+            .is_some_and(|evicted_pod| evicted_pod.uid == pod_uid)
+        {
+            evictions.remove(owner_uid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashSet};
+
+    use stackable_operator::k8s_openapi::{
+        api::core::v1::{PodCondition, PodStatus},
+        apimachinery::pkg::apis::meta::v1::Time,
+    };
+
+    use super::*;
+
+    fn pod(name: &str, uid: &str) -> EvictedPod {
+        EvictedPod {
+            namespace: "default".to_owned(),
+            name: name.to_owned(),
+            uid: uid.to_owned(),
+        }
+    }
+
+    #[test]
+    fn resolve_owner() {
+        let owner_reference = |api_version: &str, kind: &str, controller| OwnerReference {
+            api_version: api_version.to_owned(),
+            kind: kind.to_owned(),
+            name: "owner".to_owned(),
+            controller: Some(controller),
+            ..OwnerReference::default()
+        };
+        let pod = |owner_references| ObjectMeta {
+            owner_references: Some(owner_references),
+            ..ObjectMeta::default()
+        };
+        assert_eq!(
+            owner(&pod(vec![
+                owner_reference("apps/v1", "StatefulSet", false),
+                owner_reference("apps/v1", "ReplicaSet", true),
+            ])),
+            Some(&owner_reference("apps/v1", "ReplicaSet", true))
+        );
+        assert_eq!(
+            owner(&pod(vec![owner_reference("apps/v1", "DaemonSet", true)])),
+            None
+        );
+        assert_eq!(
+            owner(&pod(vec![owner_reference("batch/v1", "Job", true)])),
+            None
+        );
+        assert_eq!(owner(&ObjectMeta::default()), None);
+    }
+
+    #[test]
+    fn wait_for_ready_replacement() {
+        let pod = |name: &str, ready: bool, deleted: bool| Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_owned()),
+                deletion_timestamp: deleted.then_some(Time(jiff::Timestamp::UNIX_EPOCH)),
+                ..ObjectMeta::default()
+            },
+            status: Some(PodStatus {
+                conditions: Some(vec![PodCondition {
+                    type_: "Ready".to_owned(),
+                    status: if ready { "True" } else { "False" }.to_owned(),
+                    ..PodCondition::default()
+                }]),
+                ..PodStatus::default()
+            }),
+            ..Pod::default()
+        };
+
+        assert!(all_replicas_ready(
+            Some(3),
+            &[
+                pod("a-0", true, false),
+                pod("a-1", true, false),
+                pod("a-2", true, false)
+            ]
+        ));
+        // Right after the eviction, the status of the owner might still count the evicted Pod as
+        // ready, while it is terminating
+        assert!(!all_replicas_ready(
+            Some(3),
+            &[
+                pod("a-0", true, true),
+                pod("a-1", true, false),
+                pod("a-2", true, false)
+            ]
+        ));
+        // ... or once it is gone, while its replacement is not ready yet
+        assert!(!all_replicas_ready(
+            Some(3),
+            &[
+                pod("a-0", false, false),
+                pod("a-1", true, false),
+                pod("a-2", true, false)
+            ]
+        ));
+        assert!(!all_replicas_ready(
+            Some(3),
+            &[pod("a-1", true, false), pod("a-2", true, false)]
+        ));
+
+        assert!(!all_replicas_ready(None, &[]));
+        assert!(all_replicas_ready(Some(0), &[]));
+    }
+
+    #[test]
+    fn one_eviction_per_owner() {
+        let owner_evictions = OwnerEvictions::default();
+        let existing = RefCell::new(HashSet::from(["a-0", "a-1", "b-0"]));
+        let exists = |pod: &EvictedPod| existing.borrow().contains(pod.uid.as_str());
+
+        assert_eq!(
+            owner_evictions.reserve("a", pod("a-0", "a-0"), exists),
+            Ok(())
+        );
+        // Retrying the same Pod is fine
+        assert_eq!(
+            owner_evictions.reserve("a", pod("a-0", "a-0"), exists),
+            Ok(())
+        );
+        assert_eq!(
+            owner_evictions.reserve("a", pod("a-1", "a-1"), exists),
+            Err(pod("a-0", "a-0"))
+        );
+        // Other owners are independent
+        assert_eq!(
+            owner_evictions.reserve("b", pod("b-0", "b-0"), exists),
+            Ok(())
+        );
+
+        // The replacement of the evicted Pod has the same name, but a different UID
+        existing.borrow_mut().remove("a-0");
+        existing.borrow_mut().insert("a-0-replacement");
+        assert_eq!(
+            owner_evictions.reserve("a", pod("a-1", "a-1"), exists),
+            Ok(())
+        );
+
+        owner_evictions.release("a", "a-1");
+        assert_eq!(
+            owner_evictions.reserve("a", pod("a-0", "a-0-replacement"), exists),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use stackable_operator::kube::{core::DynamicObject, runtime::reflector::ObjectRef};
+
+/// Restarts of workloads that are waiting for their ConfigMaps and Secrets to settle, so that a
+/// burst of changes results in a single restart.
+#[derive(Debug, Default)]
+pub struct PendingRestarts {
+    pending: Mutex<HashMap<ObjectRef<DynamicObject>, PendingRestart>>,
+}
+
+#[derive(Debug)]
+struct PendingRestart {
+    /// The restarter annotations the restart would apply.
+    annotations: BTreeMap<String, String>,
+
+    /// When the annotations were last seen to change.
+    changed_at: Instant,
+}
+
+/// Whether a restart can be applied.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SettleState {
+    /// No change happened during the whole settle window, the restart can be applied.
+    Settled,
+
+    /// The restart needs to wait for (at least) `remaining`.
+    Pending {
+        remaining: Duration,
+
+        /// Whether the restart became pending or its annotations changed just now.
+        changed: bool,
+    },
+}
+
+impl PendingRestarts {
+    /// Checks whether the restart of the workload with the given (updated) `annotations` has
+    /// settled, which is the case once the annotations didn't change for the `settle_window`.
+    ///
+    /// The restart stays pending until it is [removed](Self::remove).
+    pub fn settle(
+        &self,
+        workload_ref: &ObjectRef<DynamicObject>,
+        annotations: &BTreeMap<String, String>,
+        settle_window: Duration,
+        now: Instant,
+    ) -> SettleState {
+        if settle_window.is_zero() {
+            return SettleState::Settled;
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(workload_ref) {
+            Some(pending_restart) if &pending_restart.annotations == annotations => {
+                let elapsed = now.saturating_duration_since(pending_restart.changed_at);
+                match settle_window.checked_sub(elapsed) {
+                    Some(remaining) if !remaining.is_zero() => SettleState::Pending {
+                        remaining,
+                        changed: false,
+                    },
+                    _ => SettleState::Settled,
+                }
+            }
+            _ => {
+                pending.insert(
+                    workload_ref.clone(),
+                    PendingRestart {
+                        annotations: annotations.clone(),
+                        changed_at: now,
+                    },
+                );
+                SettleState::Pending {
+                    remaining: settle_window,
+                    changed: true,
+                }
+            }
+        }
+    }
+
+    /// Forgets the pending restart of the workload, e.g. because it was applied or is not needed
+    /// anymore.
+    pub fn remove(&self, workload_ref: &ObjectRef<DynamicObject>) {
+        self.pending.lock().unwrap().remove(workload_ref);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::{k8s_openapi::api::apps::v1::StatefulSet, kube::api::ApiResource};
+
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(30);
+
+    fn workload_ref() -> ObjectRef<DynamicObject> {
+        ObjectRef::new_with("sts", ApiResource::erase::<StatefulSet>(&())).within("default")
+    }
+
+    fn annotations(version: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(
+            "configmap.restarter.stackable.tech/config".to_owned(),
+            version.to_owned(),
+        )])
+    }
+
+    #[test]
+    fn no_settle_window() {
+        let pending_restarts = PendingRestarts::default();
+        assert_eq!(
+            pending_restarts.settle(
+                &workload_ref(),
+                &annotations("1"),
+                Duration::ZERO,
+                Instant::now()
+            ),
+            SettleState::Settled
+        );
+    }
+
+    #[test]
+    fn settle_after_window() {
+        let pending_restarts = PendingRestarts::default();
+        let start = Instant::now();
+        assert_eq!(
+            pending_restarts.settle(&workload_ref(), &annotations("1"), WINDOW, start),
+            SettleState::Pending {
+                remaining: WINDOW,
+                changed: true
+            }
+        );
+        assert_eq!(
+            pending_restarts.settle(
+                &workload_ref(),
+                &annotations("1"),
+                WINDOW,
+                start + Duration::from_secs(10)
+            ),
+            SettleState::Pending {
+                remaining: Duration::from_secs(20),
+                changed: false
+            }
+        );
+        assert_eq!(
+            pending_restarts.settle(&workload_ref(), &annotations("1"), WINDOW, start + WINDOW),
+            SettleState::Settled
+        );
+    }
+
+    #[test]
+    fn changes_restart_the_window() {
+        let pending_restarts = PendingRestarts::default();
+        let start = Instant::now();
+        pending_restarts.settle(&workload_ref(), &annotations("1"), WINDOW, start);
+        assert_eq!(
+            pending_restarts.settle(
+                &workload_ref(),
+                &annotations("2"),
+                WINDOW,
+                start + Duration::from_secs(20)
+            ),
+            SettleState::Pending {
+                remaining: WINDOW,
+                changed: true
+            }
+        );
+        assert_eq!(
+            pending_restarts.settle(&workload_ref(), &annotations("2"), WINDOW, start + WINDOW),
+            SettleState::Pending {
+                remaining: Duration::from_secs(20),
+                changed: false
+            }
+        );
+    }
+
+    #[test]
+    fn removed_restarts_start_over() {
+        let pending_restarts = PendingRestarts::default();
+        let start = Instant::now();
+        pending_restarts.settle(&workload_ref(), &annotations("1"), WINDOW, start);
+        pending_restarts.remove(&workload_ref());
+        assert_eq!(
+            pending_restarts.settle(&workload_ref(), &annotations("1"), WINDOW, start + WINDOW),
+            SettleState::Pending {
+                remaining: WINDOW,
+                changed: true
+            }
+        );
+    }
+}
@@ -1,33 +1,87 @@
 use std::{future::Future, ops::Deref, sync::Arc, time::Duration};
 
-use chrono::{DateTime, FixedOffset, Utc};
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt};
 use http::StatusCode;
-use snafu::{OptionExt, ResultExt, Snafu};
+use snafu::{OptionExt, Report, ResultExt, Snafu};
 use stackable_operator::{
     client::Client,
-    k8s_openapi::api::core::v1::Pod,
+    k8s_openapi::{
+        api::{
+            apps::v1::{ReplicaSet, StatefulSet},
+            core::v1::{Namespace, Pod, PodCondition},
+        },
+        apimachinery::pkg::apis::meta::v1::{OwnerReference, Time},
+    },
     kube::{
-        self,
-        api::{EvictParams, PartialObjectMeta},
+        self, Api, Resource, ResourceExt,
+        api::{
+            DeleteParams, EvictParams, ListParams, PartialObjectMeta, Patch, PatchParams,
+            PostParams,
+        },
         core::{DynamicObject, Status},
         runtime::{
-            Controller,
-            controller::{self, Action},
-            events::{Recorder, Reporter},
-            reflector::ObjectRef,
+            Config, WatchStreamExt, applier,
+            controller::{self, Action, trigger_self},
+            events::{Event, EventType, Recorder, Reporter},
+            reflector::{self, ObjectRef, reflector},
             watcher,
         },
     },
     logging::controller::{ReconcilerError, report_controller_reconciled},
-    namespace::WatchNamespace,
 };
 use strum::{EnumDiscriminants, IntoStaticStr};
 
+use crate::{
+    metrics::Metrics,
+    restart_controller::{
+        self,
+        early_eviction::{self, EarlyEvictionWindow},
+        error_backoff::ErrorBackoff,
+        eviction_escalation::{
+            self, BlockedEvictions, EVICTION_BLOCKED_CONDITION, Escalation, EscalationPolicy,
+        },
+        maintenance_window::{self, MaintenanceWindows, duration_until},
+        owner_evictions::{self, EvictedPod, OwnerEvictions},
+        restart_policy,
+    },
+    utils::watched_namespaces::{self, WatchedNamespaces},
+};
+
 const FULL_CONTROLLER_NAME: &str = "pod.restarter.commons.stackable.tech";
 
+/// Number of Pods listed per request when labelling expiring Pods.
+const LABEL_EXPIRING_PODS_PAGE_SIZE: u32 = 500;
+
+/// Delay before checking again whether a Pod whose eviction is serialized per owner can be evicted.
+const SERIALIZED_EVICTION_RECHECK_DELAY: Duration = Duration::from_secs(10);
+
+/// Options of the Pod restarter that apply to all Pods.
+pub struct Options {
+    /// Only report evictions instead of performing them, unless overridden by the Pod.
+    pub dry_run: bool,
+
+    /// Watch all Pods, not only the ones labelled with `restarter.stackable.tech/expiry-managed`.
+    pub watch_all_pods: bool,
+
+    /// The early eviction window of Pods without the early eviction window annotation.
+    pub early_eviction_window: EarlyEvictionWindow,
+
+    /// How blocked evictions are escalated, unless overridden by the Pod or its Namespace.
+    pub escalation_policy: EscalationPolicy,
+
+    /// Only evict one Pod per StatefulSet or ReplicaSet at a time, unless overridden by the Pod.
+    pub serialize_evictions: bool,
+}
+
 struct Ctx {
     client: Client,
+    event_recorder: Arc<Recorder>,
+    options: Options,
+    blocked_evictions: Arc<BlockedEvictions>,
+    owner_evictions: OwnerEvictions,
+    error_backoff: Arc<ErrorBackoff>,
+    pods: reflector::Store<PartialObjectMeta<Pod>>,
+    metrics: Arc<Metrics>,
 }
 
 #[derive(Snafu, Debug, EnumDiscriminants)]
@@ -41,12 +95,43 @@ enum Error {
         "failed to parse expiry timestamp annotation ({annotation:?}: {value:?}) as RFC 3999"
     ))]
     UnparsableExpiryTimestamp {
-        source: chrono::ParseError,
+        source: jiff::Error,
         annotation: String,
         value: String,
     },
     #[snafu(display("failed to evict Pod"))]
     EvictPod { source: kube::Error },
+    #[snafu(display("invalid maintenance window"))]
+    InvalidMaintenanceWindow { source: maintenance_window::Error },
+    #[snafu(display("invalid early eviction window"))]
+    InvalidEarlyEvictionWindow { source: early_eviction::Error },
+    #[snafu(display("invalid restart policy"))]
+    InvalidRestartPolicy { source: restart_policy::Error },
+    #[snafu(display("failed to get Namespace {namespace:?}"))]
+    GetNamespace {
+        source: kube::Error,
+        namespace: String,
+    },
+    #[snafu(display("invalid eviction escalation policy"))]
+    InvalidEscalationPolicy { source: eviction_escalation::Error },
+    #[snafu(display("failed to delete Pod"))]
+    ForceDeletePod { source: kube::Error },
+    #[snafu(display("failed to get owner {kind} {name:?}"))]
+    GetOwner {
+        source: kube::Error,
+        kind: String,
+        name: String,
+    },
+    #[snafu(display("failed to list the Pods of {kind} {name:?}"))]
+    ListOwnerPods {
+        source: kube::Error,
+        kind: String,
+        name: String,
+    },
+    #[snafu(display("failed to determine the watched namespaces"))]
+    GetWatchedNamespaces { source: watched_namespaces::Error },
+    #[snafu(display("failed to list Pods"))]
+    ListPods { source: kube::Error },
 }
 
 impl ReconcilerError for Error {
@@ -64,20 +149,105 @@ impl ReconcilerError for Error {
                 value: _,
             } => None,
             Error::EvictPod { source: _ } => None,
+            Error::InvalidMaintenanceWindow { source: _ } => None,
+            Error::InvalidEarlyEvictionWindow { source: _ } => None,
+            Error::InvalidRestartPolicy { source: _ } => None,
+            Error::GetNamespace {
+                source: _,
+                namespace: _,
+            } => None,
+            Error::InvalidEscalationPolicy { source: _ } => None,
+            Error::ForceDeletePod { source: _ } => None,
+            Error::GetOwner {
+                source: _,
+                kind: _,
+                name: _,
+            } => None,
+            Error::ListOwnerPods {
+                source: _,
+                kind: _,
+                name: _,
+            } => None,
+            Error::GetWatchedNamespaces { source: _ } => None,
+            Error::ListPods { source: _ } => None,
         }
     }
 }
 
-pub async fn start<F>(client: &Client, watch_namespace: &WatchNamespace, shutdown_signal: F)
-where
+pub async fn start<F>(
+    client: &Client,
+    watched_namespaces: &WatchedNamespaces,
+    options: Options,
+    metrics: Arc<Metrics>,
+    shutdown_signal: F,
+) where
     F: Future<Output = ()> + Send + Sync + 'static,
 {
-    let controller = Controller::new(
-        watch_namespace.get_api::<PartialObjectMeta<Pod>>(client),
-        // TODO: Can we only watch a subset of Pods with a specify label, e.g.
-        // vendor=Stackable to reduce the memory footprint?
-        watcher::Config::default(),
-    );
+    let pod_store = reflector::store::Writer::<PartialObjectMeta<Pod>>::new(());
+    let pod_reader = pod_store.as_reader();
+    metrics.register_store("Pod", {
+        let pod_reader = pod_reader.clone();
+        move || pod_reader.state().len()
+    });
+    // Only caching the Pods that opted in keeps the memory footprint low on large clusters
+    let mut pod_watcher_config = watcher::Config::default();
+    if !options.watch_all_pods {
+        match label_expiring_pods(client, watched_namespaces).await {
+            Ok(labelled) => {
+                tracing::info!(labelled, "Labelled existing expiring Pods");
+                pod_watcher_config = pod_watcher_config.labels(&format!(
+                    "{label}=true",
+                    label = restart_controller::EXPIRY_MANAGED_LABEL
+                ));
+            }
+            // Existing expiring Pods that are not labelled would never be evicted otherwise
+            Err(error) => tracing::warn!(
+                error = &error as &dyn std::error::Error,
+                "failed to label existing expiring Pods, watching all Pods instead"
+            ),
+        }
+    }
+    let blocked_evictions = Arc::new(BlockedEvictions::default());
+    let error_backoff = Arc::new(ErrorBackoff::default());
+    let pod_watcher = reflector(
+        pod_store,
+        watched_namespaces.watch(client, pod_reader.clone(), {
+            let client = client.clone();
+            move |watch_namespace| {
+                watcher(
+                    watch_namespace.get_api::<PartialObjectMeta<Pod>>(&client),
+                    pod_watcher_config.clone(),
+                )
+            }
+        }),
+    )
+    // The state of Pods that are gone (e.g. because they were deleted by someone else) is not
+    // needed anymore
+    .inspect_ok({
+        let pod_reader = pod_reader.clone();
+        let blocked_evictions = blocked_evictions.clone();
+        let error_backoff = error_backoff.clone();
+        move |event| match event {
+            watcher::Event::Delete(pod) => {
+                let pod_ref = ObjectRef::from_obj(pod);
+                blocked_evictions.forget(&pod_ref);
+                error_backoff.forget(&pod_ref);
+            }
+            // Pods deleted while the watch was interrupted are only dropped from the store
+            watcher::Event::InitDone => {
+                let exists = |pod_ref: &ObjectRef<_>| pod_reader.get(pod_ref).is_some();
+                blocked_evictions.retain(exists);
+                error_backoff.retain(exists);
+            }
+            _ => {}
+        }
+    })
+    .applied_objects();
+    let reconcile_requests = trigger_self(pod_watcher, ()).inspect_ok({
+        let metrics = metrics.clone();
+        move |request| metrics.reconcile_queued(FULL_CONTROLLER_NAME, request.obj_ref.to_string())
+    });
+
     let event_recorder = Arc::new(Recorder::new(
         client.as_kube_client(),
         Reporter {
@@ -85,26 +255,116 @@ where
             instance: None,
         },
     ));
-    controller
-        .graceful_shutdown_on(shutdown_signal)
-        .run(
-            reconcile,
-            error_policy,
-            Arc::new(Ctx {
-                client: client.clone(),
-            }),
-        )
-        // We can let the reporting happen in the background
-        .for_each_concurrent(
-            16, // concurrency limit
-            |result| {
-                // The event_recorder needs to be shared across all invocations, so that
-                // events are correctly aggregated
-                let event_recorder = event_recorder.clone();
-                async move { report_result(result, event_recorder).await }
-            },
-        )
-        .await;
+    applier(
+        |pod, ctx| {
+            let pod_ref = ObjectRef::from_obj(pod.as_ref());
+            Box::pin(async move {
+                let result = ctx
+                    .metrics
+                    .observe_reconcile(
+                        FULL_CONTROLLER_NAME,
+                        &pod_ref.to_string(),
+                        reconcile(pod, ctx.clone()),
+                    )
+                    .await;
+                if result.is_ok() {
+                    ctx.error_backoff.forget(&pod_ref);
+                }
+                result
+            })
+        },
+        error_policy,
+        Arc::new(Ctx {
+            client: client.clone(),
+            event_recorder: event_recorder.clone(),
+            options,
+            blocked_evictions,
+            owner_evictions: OwnerEvictions::default(),
+            error_backoff,
+            pods: pod_reader.clone(),
+            metrics: metrics.clone(),
+        }),
+        pod_reader,
+        // This uses the same mechanism as kube's Controller does under the hood, see
+        // https://github.com/kube-rs/kube/blob/8bcdcb52e1e13c1c1ec59f6118fbed575ac10a4b/kube-runtime/src/controller/mod.rs#L1671
+        reconcile_requests.take_until(shutdown_signal),
+        Config::default(),
+    )
+    // We can let the reporting happen in the background
+    .for_each_concurrent(
+        16, // concurrency limit
+        |result| {
+            // The event_recorder needs to be shared across all invocations, so that
+            // events are correctly aggregated
+            let event_recorder = event_recorder.clone();
+            let metrics = metrics.clone();
+            async move { report_result(result, event_recorder, &metrics).await }
+        },
+    )
+    .await;
+}
+
+/// Labels the Pods that [expire](restart_controller::expires) but are not labelled with the
+/// [`EXPIRY_MANAGED_LABEL`](restart_controller::EXPIRY_MANAGED_LABEL) yet, so that they are watched.
+///
+/// The label is usually set by the Pod mutating webhook, but Pods created before it was installed
+/// (e.g. by previous versions of the operator) are missing it. This also happens in dry runs, as it
+/// doesn't disrupt the Pods. Returns the number of labelled Pods.
+async fn label_expiring_pods(
+    client: &Client,
+    watched_namespaces: &WatchedNamespaces,
+) -> Result<usize, Error> {
+    let label = restart_controller::EXPIRY_MANAGED_LABEL;
+    let patch = serde_json::json!({ "metadata": { "labels": { label: "true" } } });
+    let mut labelled = 0;
+    for watch_namespace in watched_namespaces
+        .current(client)
+        .await
+        .context(GetWatchedNamespacesSnafu)?
+    {
+        let pods = watch_namespace.get_api::<Pod>(client);
+        // Pods with any value of the label were already taken care of
+        let mut list_params = ListParams::default()
+            .labels(&format!("!{label}"))
+            .limit(LABEL_EXPIRING_PODS_PAGE_SIZE);
+        loop {
+            let page = pods
+                .list_metadata(&list_params)
+                .await
+                .context(ListPodsSnafu)?;
+            for pod in page
+                .items
+                .iter()
+                .filter(|pod| restart_controller::expires(pod.annotations()))
+            {
+                let result = client
+                    .get_api::<Pod>(&pod.namespace().unwrap_or_default())
+                    .patch_metadata(
+                        &pod.name_any(),
+                        &PatchParams::default(),
+                        &Patch::Merge(&patch),
+                    )
+                    .await;
+                match result {
+                    Ok(_) => labelled += 1,
+                    // The Pod might have been deleted in the meantime
+                    Err(error) => tracing::warn!(
+                        pod = %pod.name_any(),
+                        namespace = %pod.namespace().unwrap_or_default(),
+                        error = &error as &dyn std::error::Error,
+                        "failed to label expiring Pod"
+                    ),
+                }
+            }
+            match page.metadata.continue_ {
+                Some(continue_token) if !continue_token.is_empty() => {
+                    list_params = list_params.continue_token(&continue_token);
+                }
+                _ => break,
+            }
+        }
+    }
+    Ok(labelled)
 }
 
 async fn reconcile(pod: Arc<PartialObjectMeta<Pod>>, ctx: Arc<Ctx>) -> Result<Action, Error> {
@@ -112,6 +372,8 @@ async fn reconcile(pod: Arc<PartialObjectMeta<Pod>>, ctx: Arc<Ctx>) -> Result<Ac
     if pod.metadata.deletion_timestamp.is_some() {
         // Object is already being deleted, no point trying again
         tracing::info!("Pod is already being deleted, taking no action!");
+        ctx.blocked_evictions
+            .forget(&ObjectRef::from_obj(pod.as_ref()));
         return Ok(Action::await_change());
     }
 
@@ -126,79 +388,601 @@ async fn reconcile(pod: Arc<PartialObjectMeta<Pod>>, ctx: Arc<Ctx>) -> Result<Ac
     let pod_expires_at = annotations
         .iter()
         .flatten()
-        .filter(|(k, _)| k.starts_with("restarter.stackable.tech/expires-at."))
+        .filter(|(k, _)| k.starts_with(restart_controller::EXPIRES_AT_ANNOTATION_PREFIX))
         .map(|(k, v)| {
-            DateTime::parse_from_rfc3339(v).context(UnparsableExpiryTimestampSnafu {
-                annotation: k,
-                value: v,
-            })
+            v.parse::<jiff::Timestamp>()
+                .context(UnparsableExpiryTimestampSnafu {
+                    annotation: k,
+                    value: v,
+                })
         })
         .min_by_key(|res| {
             // Prefer propagating errors over successful cases
             (res.is_ok(), res.as_ref().ok().cloned())
         })
         .transpose()?;
+    // The relative restart policies make the Pod expire as well, depending on when it was created.
+    // An invalid restart policy is ignored, so that it does not prevent evicting the Pod once it
+    // expires due to its expiry annotations.
+    let restart_policy_expires_at = match created_at(&pod)
+        .map(|created_at| restart_policy::expires_at(pod.annotations(), created_at))
+        .transpose()
+        .context(InvalidRestartPolicySnafu)
+    {
+        Ok(expires_at) => expires_at.flatten(),
+        Err(error) => {
+            report_invalid_annotation(&ctx, &pod, error).await;
+            None
+        }
+    };
+    let pod_expires_at = restart_policy_expires_at
+        .into_iter()
+        .chain(pod_expires_at)
+        .min();
 
     tracing::debug!(
         pod.expires_at = ?pod_expires_at,
         "Proceeding with closest expiration time",
     );
-    let now = DateTime::<FixedOffset>::from(Utc::now());
+    // An invalid maintenance window must not prevent the eviction of expired Pods, so it is
+    // reported and ignored
+    let maintenance_windows = match MaintenanceWindows::from_annotations(pod.annotations())
+        .context(InvalidMaintenanceWindowSnafu)
+    {
+        Ok(maintenance_windows) => maintenance_windows,
+        Err(error) => {
+            if pod_expires_at.is_some() {
+                report_invalid_annotation(&ctx, &pod, error).await;
+            }
+            None
+        }
+    };
+    let now = jiff::Timestamp::now();
 
-    // Calculate the time remaining from now until the stated expiration time by subtraction
-    // The call to `chrono::Duration::to_std()` returns an error if the resulting duration is
-    // negative -> i.e. when the pod has expired.
-    let time_until_pod_expires = pod_expires_at.map(|expires_at| (expires_at - now).to_std());
+    // Calculate the time remaining from now until the stated expiration time
+    // The conversion to `std::time::Duration` returns an error if the duration is negative -> i.e.
+    // when the pod has expired.
+    let time_until_pod_expires = pod_expires_at.map(|expires_at| time_until(now, expires_at));
 
     // Match on result of subtraction, possible cases:
     // Some(Error<...>) -> duration was negative, cert has expired
-    // Some(Ok<Duration<>>) -> duration was positive, cert still valid
+    // Some(Ok<Duration<>>) -> duration was positive, cert still valid (and the Pod might be
+    //                         evicted earlier during a maintenance window)
     // None -> there were no annotations to process, pod is not in scope for this code
-    match time_until_pod_expires {
-        Some(Err(_has_already_expired)) => {
+    match (time_until_pod_expires, maintenance_windows) {
+        // Pods can inherit their expiry date from the Pod template of their workload (see
+        // `--restarter-tls-expiry-margin`), their replacements would be expired right away as well
+        (Some(Err(_has_already_expired)), _)
+            if created_at(&pod)
+                .zip(pod_expires_at)
+                .is_some_and(|(created_at, expires_at)| created_at >= expires_at) =>
+        {
             tracing::info!(
                 pod.expires_at = ?pod_expires_at,
-                "Evicting pod, due to stated expiration date being reached",
+                "Pod was created after its stated expiration date, evicting it would not help, ignoring pod!",
             );
-            let pods = ctx.client.get_api::<Pod>(
-                pod.metadata
-                    .namespace
-                    .as_deref()
-                    .context(PodHasNoNamespaceSnafu)?,
-            );
-            pods.evict(
-                pod.metadata.name.as_deref().context(PodHasNoNameSnafu)?,
-                &EvictParams::default(),
-            )
-            .await
-            .context(EvictPodSnafu)?;
             Ok(Action::await_change())
         }
-
-        Some(Ok(time_until_pod_expires)) => {
+        (Some(Err(_has_already_expired)), _) => {
             tracing::info!(
                 pod.expires_at = ?pod_expires_at,
-                recheck_delay = ?time_until_pod_expires,
-                "Pod still valid, rescheduling check",
+                "Evicting pod, due to stated expiration date being reached",
             );
-            Ok(Action::requeue(time_until_pod_expires))
+            evict_pod(&pod, &ctx, pod_expires_at).await
         }
-        None => {
+
+        // If the Pod should only be restarted during maintenance windows, it is evicted during the
+        // last maintenance window before it expires. It is still evicted when it expires if there
+        // is no such window.
+        (Some(Ok(time_until_pod_expires)), Some(maintenance_windows)) => {
+            let expires_at = now + time_until_pod_expires;
+            match maintenance_windows.next_opening(now) {
+                Some(next_opening) if next_opening < expires_at => {
+                    tracing::info!(
+                        pod.expires_at = ?pod_expires_at,
+                        %maintenance_windows,
+                        %next_opening,
+                        "Pod still valid, rescheduling check for the next maintenance window",
+                    );
+                    Ok(Action::requeue(duration_until(now, next_opening)))
+                }
+                _ if maintenance_windows.is_open(now) => {
+                    tracing::info!(
+                        pod.expires_at = ?pod_expires_at,
+                        %maintenance_windows,
+                        "Evicting pod during the last maintenance window before its expiration date",
+                    );
+                    evict_pod(&pod, &ctx, pod_expires_at).await
+                }
+                _ => {
+                    tracing::info!(
+                        pod.expires_at = ?pod_expires_at,
+                        recheck_delay = ?time_until_pod_expires,
+                        %maintenance_windows,
+                        "Pod still valid, but no maintenance window opens before its expiration date, rescheduling check",
+                    );
+                    Ok(Action::requeue(time_until_pod_expires))
+                }
+            }
+        }
+
+        // Otherwise, the Pod is evicted at its position within the early eviction window, so that
+        // Pods expiring at the same time are not evicted at once
+        (Some(Ok(time_until_pod_expires)), None) => {
+            let early_eviction_window = match EarlyEvictionWindow::from_annotations(
+                pod.annotations(),
+                ctx.options.early_eviction_window,
+            )
+            .context(InvalidEarlyEvictionWindowSnafu)
+            {
+                Ok(early_eviction_window) => early_eviction_window,
+                Err(error) => {
+                    report_invalid_annotation(&ctx, &pod, error).await;
+                    ctx.options.early_eviction_window
+                }
+            };
+            let pod_evict_at =
+                created_at(&pod)
+                    .zip(pod_expires_at)
+                    .map(|(created_at, expires_at)| {
+                        early_eviction_window.eviction_time(
+                            created_at,
+                            expires_at,
+                            early_eviction::spread_position(&pod.metadata),
+                        )
+                    });
+            let time_until_eviction = match pod_evict_at {
+                Some(evict_at) => time_until(now, evict_at),
+                None => Ok(time_until_pod_expires),
+            };
+            match time_until_eviction {
+                Err(_eviction_time_reached) => {
+                    tracing::info!(
+                        pod.expires_at = ?pod_expires_at,
+                        pod.evict_at = ?pod_evict_at,
+                        %early_eviction_window,
+                        "Evicting pod within its early eviction window",
+                    );
+                    evict_pod(&pod, &ctx, pod_expires_at).await
+                }
+                Ok(time_until_eviction) => {
+                    tracing::info!(
+                        pod.expires_at = ?pod_expires_at,
+                        pod.evict_at = ?pod_evict_at,
+                        recheck_delay = ?time_until_eviction,
+                        "Pod still valid, rescheduling check",
+                    );
+                    Ok(Action::requeue(time_until_eviction))
+                }
+            }
+        }
+        (None, _) => {
             tracing::info!("No expiry annotations found, ignoring pod!");
             Ok(Action::await_change())
         }
     }
 }
 
-/// Reports the result of reconciliation.
+/// Returns when the Pod was created.
+fn created_at(pod: &PartialObjectMeta<Pod>) -> Option<jiff::Timestamp> {
+    Some(pod.metadata.creation_timestamp.as_ref()?.0)
+}
+
+/// Returns the time from `now` until `time`, or an error if `time` has already passed.
+fn time_until(now: jiff::Timestamp, time: jiff::Timestamp) -> Result<Duration, jiff::Error> {
+    Duration::try_from(time.duration_since(now))
+}
+
+/// Evicts the Pod, escalating according to the [`EscalationPolicy`] if the eviction is blocked by
+/// a PodDisruptionBudget past the expiration date of the Pod.
+///
+/// If evictions are serialized, the eviction is deferred while other Pods of the owner of the Pod
+/// are unavailable, see [`defer_eviction`].
+async fn evict_pod(
+    pod: &PartialObjectMeta<Pod>,
+    ctx: &Ctx,
+    expires_at: Option<jiff::Timestamp>,
+) -> Result<Action, Error> {
+    let pods = ctx.client.get_api::<Pod>(
+        pod.metadata
+            .namespace
+            .as_deref()
+            .context(PodHasNoNamespaceSnafu)?,
+    );
+    let dry_run = restart_controller::is_dry_run(ctx.options.dry_run, pod.annotations());
+    // Workloads without a PodDisruptionBudget would otherwise lose all expired Pods at once
+    let owner = owner_evictions::is_serialized(ctx.options.serialize_evictions, pod.annotations())
+        .then(|| owner_evictions::owner(&pod.metadata))
+        .flatten();
+    if let Some(owner) = owner
+        && let Some(action) = defer_eviction(pod, ctx, owner).await?
+    {
+        return Ok(action);
+    }
+    // A server-side dry run still checks whether the eviction would violate a PodDisruptionBudget
+    let evicted = pods
+        .evict(
+            pod.metadata.name.as_deref().context(PodHasNoNameSnafu)?,
+            &EvictParams {
+                post_options: PostParams {
+                    dry_run,
+                    ..PostParams::default()
+                },
+                ..EvictParams::default()
+            },
+        )
+        .await;
+    if let Some(owner) = owner
+        && (dry_run || evicted.is_err())
+    {
+        // Only Pods that are actually evicted hold up the other Pods of their owner
+        ctx.owner_evictions
+            .release(&owner.uid, pod.uid().as_deref().unwrap_or_default());
+    }
+    match evicted {
+        Ok(_) => ctx.blocked_evictions.forget(&ObjectRef::from_obj(pod)),
+        Err(error) if is_blocked_by_disruption_budget(&error) => {
+            return handle_blocked_eviction(pod, ctx, expires_at, &error).await;
+        }
+        Err(error) => return Err(error).context(EvictPodSnafu),
+    }
+
+    if dry_run {
+        tracing::info!("Dry run, not evicting Pod");
+        publish_event(
+            ctx,
+            pod,
+            EventType::Normal,
+            "WouldEvict",
+            "Dry run: would evict the Pod".to_owned(),
+            "Evict",
+        )
+        .await;
+    } else {
+        ctx.metrics
+            .pod_evicted(pod.namespace().as_deref().unwrap_or_default());
+    }
+    Ok(Action::await_change())
+}
+
+/// Defers the eviction of the Pod (by returning when to check again) until the Pod that was evicted
+/// before from the same `owner` is gone and its replacement is ready, in effect allowing at most
+/// one unavailable Pod per owner.
+async fn defer_eviction(
+    pod: &PartialObjectMeta<Pod>,
+    ctx: &Ctx,
+    owner: &OwnerReference,
+) -> Result<Option<Action>, Error> {
+    if !all_owner_replicas_ready(ctx, &pod.namespace().unwrap_or_default(), owner).await? {
+        tracing::info!(
+            owner.kind = %owner.kind,
+            owner.name = %owner.name,
+            recheck_delay = ?SERIALIZED_EVICTION_RECHECK_DELAY,
+            "Deferring eviction until all Pods of the owner are ready",
+        );
+        return Ok(Some(Action::requeue(SERIALIZED_EVICTION_RECHECK_DELAY)));
+    }
+    // Reserving the eviction prevents Pods of the same owner that are reconciled concurrently from
+    // being evicted before the owner notices that a Pod is gone
+    let reserved =
+        ctx.owner_evictions
+            .reserve(&owner.uid, EvictedPod::new(&pod.metadata), |evicted_pod| {
+                ctx.pods
+                    .get(&ObjectRef::new(&evicted_pod.name).within(&evicted_pod.namespace))
+                    .is_some_and(|pod| {
+                        pod.metadata.uid.as_deref() == Some(evicted_pod.uid.as_str())
+                    })
+            });
+    if let Err(evicted_pod) = reserved {
+        tracing::info!(
+            owner.kind = %owner.kind,
+            owner.name = %owner.name,
+            evicted_pod.name = %evicted_pod.name,
+            recheck_delay = ?SERIALIZED_EVICTION_RECHECK_DELAY,
+            "Deferring eviction until the Pod evicted before from the same owner is gone",
+        );
+        return Ok(Some(Action::requeue(SERIALIZED_EVICTION_RECHECK_DELAY)));
+    }
+    Ok(None)
+}
+
+/// Whether all replicas of the StatefulSet or ReplicaSet `owner` are ready, which is trivially the
+/// case if it doesn't exist anymore.
+///
+/// The readiness is determined from the Pods of the owner, see
+/// [`owner_evictions::all_replicas_ready`].
+async fn all_owner_replicas_ready(
+    ctx: &Ctx,
+    namespace: &str,
+    owner: &OwnerReference,
+) -> Result<bool, Error> {
+    let get_owner_snafu = || GetOwnerSnafu {
+        kind: &owner.kind,
+        name: &owner.name,
+    };
+    let is_owner = |uid: &Option<String>| uid.as_deref() == Some(owner.uid.as_str());
+    let owner_spec = match owner.kind.as_str() {
+        "StatefulSet" => ctx
+            .client
+            .get_api::<StatefulSet>(namespace)
+            .get_opt(&owner.name)
+            .await
+            .context(get_owner_snafu())?
+            .filter(|stateful_set| is_owner(&stateful_set.metadata.uid))
+            .and_then(|stateful_set| stateful_set.spec)
+            .map(|spec| (spec.replicas, spec.selector)),
+        "ReplicaSet" => ctx
+            .client
+            .get_api::<ReplicaSet>(namespace)
+            .get_opt(&owner.name)
+            .await
+            .context(get_owner_snafu())?
+            .filter(|replica_set| is_owner(&replica_set.metadata.uid))
+            .and_then(|replica_set| replica_set.spec)
+            .map(|spec| (spec.replicas, spec.selector)),
+        _ => None,
+    };
+    let Some((replicas, selector)) = owner_spec else {
+        return Ok(true);
+    };
+
+    // The labels of the selector narrow down the Pods to list, the owner references determine
+    // which of them are actually owned by the owner
+    let label_selector = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(",");
+    let pods = ctx
+        .client
+        .get_api::<Pod>(namespace)
+        .list(&ListParams::default().labels(&label_selector))
+        .await
+        .context(ListOwnerPodsSnafu {
+            kind: &owner.kind,
+            name: &owner.name,
+        })?;
+    Ok(owner_evictions::all_replicas_ready(
+        replicas,
+        pods.items.iter().filter(|pod| {
+            owner_evictions::owner(&pod.metadata)
+                .is_some_and(|pod_owner| pod_owner.uid == owner.uid)
+        }),
+    ))
+}
+
+/// Whether the eviction failed because it would violate a PodDisruptionBudget.
 ///
-/// The Pod restart controller has special handling, as it produced lot's of error messages below.
-/// They are expected, as we intentionally use the `Evict` API to restart Pods before e.g. the
+/// This is expected, as we intentionally use the `Evict` API to restart Pods before e.g. the
 /// certificate expires. We roll out PDBs by default. If we try to restart multiple Pods that are
 /// part of a PDB, we get this errors.
-/// Because of this, we don't emit an error for this case, but only product a INFO trace.
 ///
-/// `ERROR stackable_operator::logging::controller: Failed to reconcile object controller.name="pod.restarter.commons.stackable.tech" error=reconciler for object Pod.v1./trino-worker-default-0.default failed error.sources=[failed to evict Pod, ApiError: Cannot evict pod as it would violate the pod's disruption budget.: TooManyRequests (ErrorResponse { status: "Failure", message: "Cannot evict pod as it would violate the pod's disruption budget.", reason: "TooManyRequests", code: 429 }), Cannot evict pod as it would violate the pod's disruption budget.: TooManyRequests]`
+/// `failed to evict Pod, ApiError: Cannot evict pod as it would violate the pod's disruption budget.: TooManyRequests (ErrorResponse { status: "Failure", message: "Cannot evict pod as it would violate the pod's disruption budget.", reason: "TooManyRequests", code: 429 }), Cannot evict pod as it would violate the pod's disruption budget.: TooManyRequests`
+fn is_blocked_by_disruption_budget(error: &kube::Error) -> bool {
+    const TOO_MANY_REQUESTS_HTTP_CODE: u16 = StatusCode::TOO_MANY_REQUESTS.as_u16();
+    // We can not blanket silence all 429 responses, as it could be something else.
+    // E.g. I have seen "storage is re-initializing" in the past.
+    const EVICT_ERROR_MESSAGE: &str =
+        "Cannot evict pod as it would violate the pod's disruption budget.";
+
+    if let kube::Error::Api(s) = error
+        && let Status {
+            code: TOO_MANY_REQUESTS_HTTP_CODE,
+            message: error_message,
+            ..
+        } = s.deref()
+    {
+        error_message == EVICT_ERROR_MESSAGE
+    } else {
+        false
+    }
+}
+
+/// Retries a blocked eviction with an exponential backoff, escalating according to the
+/// [`EscalationPolicy`] once the Pod is past its expiration date.
+async fn handle_blocked_eviction(
+    pod: &PartialObjectMeta<Pod>,
+    ctx: &Ctx,
+    expires_at: Option<jiff::Timestamp>,
+    error: &kube::Error,
+) -> Result<Action, Error> {
+    let pod_ref = ObjectRef::from_obj(pod);
+    let namespace = pod.namespace().unwrap_or_default();
+    ctx.metrics.pod_eviction_blocked(&namespace);
+    let blocked = ctx.blocked_evictions.blocked(&pod_ref);
+    let backoff = blocked.backoff();
+    tracing::info!(
+        %error,
+        attempts = blocked.attempts,
+        retry_delay = ?backoff,
+        "Tried to evict Pod, but wasn't allowed to do so, as it would violate the Pod's disruption budget. Retrying later"
+    );
+
+    // Pods that are evicted early (e.g. during a maintenance window) are simply retried until they
+    // expire
+    let now = jiff::Timestamp::now();
+    let Some(overdue) = expires_at.and_then(|expires_at| time_until(expires_at, now).ok()) else {
+        return Ok(Action::requeue(backoff));
+    };
+
+    let namespace_annotations = Api::<Namespace>::all(ctx.client.as_kube_client())
+        .get_metadata_opt(&namespace)
+        .await
+        .context(GetNamespaceSnafu {
+            namespace: &namespace,
+        })?
+        .and_then(|namespace| namespace.metadata.annotations)
+        .unwrap_or_default();
+    let escalation_policy = ctx
+        .options
+        .escalation_policy
+        .with_overrides(&namespace_annotations)
+        .and_then(|policy| policy.with_overrides(pod.annotations()))
+        .context(InvalidEscalationPolicySnafu)?;
+    // Round to seconds for a readable duration in messages
+    let overdue_for = stackable_operator::time::Duration::from_secs(overdue.as_secs());
+    let dry_run = restart_controller::is_dry_run(ctx.options.dry_run, pod.annotations());
+
+    let mut warned = blocked.warned;
+    match escalation_policy.escalation(overdue, warned) {
+        Escalation::ForceDelete => {
+            tracing::warn!(
+                overdue = %overdue_for,
+                force_after = ?escalation_policy.force_after,
+                "Deleting pod, as its eviction is still blocked by its disruption budget",
+            );
+            ctx.client
+                .get_api::<Pod>(&namespace)
+                .delete(
+                    &pod.name_any(),
+                    &DeleteParams {
+                        dry_run,
+                        ..DeleteParams::default()
+                    },
+                )
+                .await
+                .context(ForceDeletePodSnafu)?;
+            ctx.blocked_evictions.forget(&pod_ref);
+            let (reason, note) = if dry_run {
+                (
+                    "WouldForceDelete",
+                    format!(
+                        "Dry run: would delete the Pod, as it expired {overdue_for} ago and \
+                        evicting it would still violate its PodDisruptionBudget"
+                    ),
+                )
+            } else {
+                ctx.metrics.pod_force_deleted(&namespace);
+                (
+                    "ForceDeleted",
+                    format!(
+                        "Deleted the Pod, as it expired {overdue_for} ago and evicting it would \
+                        still violate its PodDisruptionBudget"
+                    ),
+                )
+            };
+            publish_event(ctx, pod, EventType::Warning, reason, note, "Delete").await;
+            return Ok(Action::await_change());
+        }
+        Escalation::Warn => {
+            let message = format!(
+                "The Pod expired {overdue_for} ago, but evicting it would violate its \
+                PodDisruptionBudget"
+            );
+            set_eviction_blocked_condition(pod, ctx, &message, dry_run).await;
+            publish_event(
+                ctx,
+                pod,
+                EventType::Warning,
+                "EvictionBlocked",
+                message,
+                "Evict",
+            )
+            .await;
+            ctx.blocked_evictions.warned(&pod_ref);
+            warned = true;
+        }
+        Escalation::Retry => {}
+    }
+
+    // Retry early enough to escalate on time
+    Ok(Action::requeue(
+        escalation_policy
+            .time_until_next_escalation(overdue, warned)
+            .map_or(backoff, |until_escalation| until_escalation.min(backoff)),
+    ))
+}
+
+/// Sets the [`EVICTION_BLOCKED_CONDITION`] on the Pod, which is kept until the Pod is evicted.
+async fn set_eviction_blocked_condition(
+    pod: &PartialObjectMeta<Pod>,
+    ctx: &Ctx,
+    message: &str,
+    dry_run: bool,
+) {
+    let condition = PodCondition {
+        type_: EVICTION_BLOCKED_CONDITION.to_owned(),
+        status: "True".to_owned(),
+        reason: Some("DisruptionBudgetViolated".to_owned()),
+        message: Some(message.to_owned()),
+        last_transition_time: Some(Time(jiff::Timestamp::now())),
+        ..PodCondition::default()
+    };
+    // Conditions are merged by their type, so the conditions maintained by the kubelet are kept
+    let patch = serde_json::json!({ "status": { "conditions": [condition] } });
+    if let Err(error) = ctx
+        .client
+        .get_api::<Pod>(&pod.namespace().unwrap_or_default())
+        .patch_status(
+            &pod.name_any(),
+            &PatchParams {
+                dry_run,
+                ..PatchParams::default()
+            },
+            &Patch::Strategic(patch),
+        )
+        .await
+    {
+        tracing::warn!(
+            error = &error as &dyn std::error::Error,
+            "failed to set the {EVICTION_BLOCKED_CONDITION} condition"
+        );
+    }
+}
+
+/// Reports an invalid annotation of the Pod (such as an invalid maintenance window), which is
+/// ignored instead of failing the reconciliation, so that it does not prevent the eviction of the
+/// Pod once it expires.
+async fn report_invalid_annotation(ctx: &Ctx, pod: &PartialObjectMeta<Pod>, error: Error) {
+    tracing::warn!(
+        error = &error as &dyn std::error::Error,
+        "Ignoring invalid annotation"
+    );
+    let reason: &'static str = ErrorDiscriminants::from(&error).into();
+    publish_event(
+        ctx,
+        pod,
+        EventType::Warning,
+        reason,
+        Report::from_error(error).to_string(),
+        "Evict",
+    )
+    .await;
+}
+
+async fn publish_event(
+    ctx: &Ctx,
+    pod: &PartialObjectMeta<Pod>,
+    type_: EventType,
+    reason: &str,
+    note: String,
+    action: &str,
+) {
+    let event = Event {
+        type_,
+        reason: reason.to_owned(),
+        note: Some(note),
+        action: action.to_owned(),
+        secondary: None,
+    };
+    if let Err(error) = ctx
+        .event_recorder
+        .publish(&event, &pod.object_ref(&()))
+        .await
+    {
+        tracing::warn!(
+            error = &error as &dyn std::error::Error,
+            "failed to publish event"
+        );
+    }
+}
+
+/// Reports the result of reconciliation.
+///
+/// Evictions blocked by a PodDisruptionBudget are not reported as errors, but retried with a
+/// backoff by [`handle_blocked_eviction`].
 #[allow(clippy::type_complexity)] // The result type complexity comes from kube-rs and is what it is
 async fn report_result(
     result: Result<
@@ -206,40 +990,27 @@ async fn report_result(
         controller::Error<Error, watcher::Error>,
     >,
     event_recorder: Arc<Recorder>,
+    metrics: &Metrics,
 ) {
     if let Err(controller::Error::ReconcilerFailed(
-        Error::EvictPod {
-            source: evict_pod_error,
-        },
+        Error::UnparsableExpiryTimestamp { .. },
         pod_ref,
     )) = &result
     {
-        const TOO_MANY_REQUESTS_HTTP_CODE: u16 = StatusCode::TOO_MANY_REQUESTS.as_u16();
-        // We can not blanket silence all 429 responses, as it could be something else.
-        // E.g. I have seen "storage is re-initializing" in the past.
-        const EVICT_ERROR_MESSAGE: &str =
-            "Cannot evict pod as it would violate the pod's disruption budget.";
-
-        if let kube::Error::Api(s) = evict_pod_error
-            && let Status {
-                code: TOO_MANY_REQUESTS_HTTP_CODE,
-                message: error_message,
-                ..
-            } = s.deref()
-            && error_message == EVICT_ERROR_MESSAGE
-        {
-            tracing::info!(
-                k8s.object.ref = %pod_ref,
-                error = %evict_pod_error,
-                "Tried to evict Pod, but wasn't allowed to do so, as it would violate the Pod's disruption budget. Retrying later"
-            );
-            return;
-        }
+        metrics.expiry_parse_failed(pod_ref.namespace.as_deref().unwrap_or_default());
     }
 
     report_controller_reconciled(&event_recorder, FULL_CONTROLLER_NAME, &result).await;
 }
 
-fn error_policy(_obj: Arc<PartialObjectMeta<Pod>>, _error: &Error, _ctx: Arc<Ctx>) -> Action {
-    Action::requeue(Duration::from_secs(5))
+fn error_policy(obj: Arc<PartialObjectMeta<Pod>>, error: &Error, ctx: Arc<Ctx>) -> Action {
+    match error {
+        // The expiration date of the Pod is unknown, will be requeued when modified anyway. Other
+        // invalid annotations of the Pod are reported and ignored by `reconcile`.
+        Error::UnparsableExpiryTimestamp { .. } => Action::await_change(),
+
+        // The eviction escalation policy may also be invalid due to the annotations of the
+        // Namespace, which is not watched
+        _ => Action::requeue(ctx.error_backoff.failed(&ObjectRef::from_obj(obj.as_ref()))),
+    }
 }
@@ -0,0 +1,345 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use stackable_operator::k8s_openapi::api::core::v1::{
+    EnvFromSource, EnvVar, KeyToPath, PodSpec, Volume, VolumeProjection,
+};
+
+/// The keys of a ConfigMap or Secret that are consumed by a Pod.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReferencedKeys {
+    /// The whole object is consumed, e.g. by `envFrom` or a volume without `items`.
+    All,
+
+    /// Only the listed keys are consumed, e.g. by `configMapKeyRef` or a volume with `items`.
+    Only(BTreeSet<String>),
+}
+
+impl ReferencedKeys {
+    fn from_key(key: &str) -> Self {
+        Self::Only(BTreeSet::from([key.to_owned()]))
+    }
+
+    /// Volumes only project the listed `items`, or all keys if there are none.
+    fn from_items(items: Option<&Vec<KeyToPath>>) -> Self {
+        match items {
+            Some(items) if !items.is_empty() => {
+                Self::Only(items.iter().map(|item| item.key.clone()).collect())
+            }
+            _ => Self::All,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        match (self, other) {
+            (Self::Only(keys), Self::Only(other_keys)) => keys.extend(other_keys),
+            (this, _) => *this = Self::All,
+        }
+    }
+}
+
+/// A reference to a ConfigMap or Secret, as found in a Pod spec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PodRef {
+    pub keys: ReferencedKeys,
+
+    /// Whether the Pod can start without the referenced object.
+    pub optional: bool,
+}
+
+impl PodRef {
+    fn new(keys: ReferencedKeys, optional: Option<bool>) -> Self {
+        Self {
+            keys,
+            optional: optional.unwrap_or(false),
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.keys.merge(other.keys);
+        // The object is only optional if all references to it are optional
+        self.optional &= other.optional;
+    }
+}
+
+/// Returns all ConfigMaps referenced by the Pod spec by name.
+pub fn config_map_refs(pod_spec: &PodSpec) -> BTreeMap<String, PodRef> {
+    find_pod_refs(
+        pod_spec,
+        |volume| {
+            let volume_ref = volume.config_map.as_ref().map(|config_map| {
+                (
+                    config_map.name.clone(),
+                    PodRef::new(
+                        ReferencedKeys::from_items(config_map.items.as_ref()),
+                        config_map.optional,
+                    ),
+                )
+            });
+            let projected_refs = projected_sources(volume).filter_map(|source| {
+                let config_map = source.config_map.as_ref()?;
+                Some((
+                    config_map.name.clone(),
+                    PodRef::new(
+                        ReferencedKeys::from_items(config_map.items.as_ref()),
+                        config_map.optional,
+                    ),
+                ))
+            });
+            volume_ref.into_iter().chain(projected_refs).collect()
+        },
+        |env_var| {
+            let key_ref = env_var.value_from.as_ref()?.config_map_key_ref.as_ref()?;
+            Some((
+                key_ref.name.clone(),
+                PodRef::new(ReferencedKeys::from_key(&key_ref.key), key_ref.optional),
+            ))
+        },
+        |env_from| {
+            let env_ref = env_from.config_map_ref.as_ref()?;
+            Some((
+                env_ref.name.clone(),
+                PodRef::new(ReferencedKeys::All, env_ref.optional),
+            ))
+        },
+    )
+}
+
+/// Returns all Secrets referenced by the Pod spec by name.
+pub fn secret_refs(pod_spec: &PodSpec) -> BTreeMap<String, PodRef> {
+    find_pod_refs(
+        pod_spec,
+        |volume| {
+            let volume_ref = volume.secret.as_ref().and_then(|secret| {
+                Some((
+                    secret.secret_name.clone()?,
+                    PodRef::new(
+                        ReferencedKeys::from_items(secret.items.as_ref()),
+                        secret.optional,
+                    ),
+                ))
+            });
+            let projected_refs = projected_sources(volume).filter_map(|source| {
+                let secret = source.secret.as_ref()?;
+                Some((
+                    secret.name.clone(),
+                    PodRef::new(
+                        ReferencedKeys::from_items(secret.items.as_ref()),
+                        secret.optional,
+                    ),
+                ))
+            });
+            volume_ref.into_iter().chain(projected_refs).collect()
+        },
+        |env_var| {
+            let key_ref = env_var.value_from.as_ref()?.secret_key_ref.as_ref()?;
+            Some((
+                key_ref.name.clone(),
+                PodRef::new(ReferencedKeys::from_key(&key_ref.key), key_ref.optional),
+            ))
+        },
+        |env_from| {
+            let env_ref = env_from.secret_ref.as_ref()?;
+            Some((
+                env_ref.name.clone(),
+                PodRef::new(ReferencedKeys::All, env_ref.optional),
+            ))
+        },
+    )
+}
+
+/// Returns the sources of a projected volume, or nothing if the volume is not projected.
+fn projected_sources(volume: &Volume) -> impl Iterator<Item = &VolumeProjection> {
+    volume
+        .projected
+        .iter()
+        .flat_map(|projected| &projected.sources)
+        .flatten()
+}
+
+fn find_pod_refs(
+    pod_spec: &PodSpec,
+    volume_refs: impl Fn(&Volume) -> Vec<(String, PodRef)>,
+    env_var_ref: impl Fn(&EnvVar) -> Option<(String, PodRef)>,
+    env_from_ref: impl Fn(&EnvFromSource) -> Option<(String, PodRef)>,
+) -> BTreeMap<String, PodRef> {
+    let volume_refs = pod_spec.volumes.iter().flatten().flat_map(volume_refs);
+    let pod_containers = pod_spec
+        .containers
+        .iter()
+        .chain(pod_spec.init_containers.iter().flatten());
+    let container_env_var_refs = pod_containers
+        .clone()
+        .flat_map(|container| &container.env)
+        .flatten()
+        .flat_map(env_var_ref);
+    let container_env_from_refs = pod_containers
+        .flat_map(|container| &container.env_from)
+        .flatten()
+        .flat_map(env_from_ref);
+
+    // The same object can be referenced multiple times, in which case the union of all consumed
+    // keys is relevant
+    let mut refs = BTreeMap::<String, PodRef>::new();
+    for (name, pod_ref) in volume_refs
+        .chain(container_env_var_refs)
+        .chain(container_env_from_refs)
+    {
+        match refs.get_mut(&name) {
+            Some(existing_ref) => existing_ref.merge(pod_ref),
+            None => {
+                refs.insert(name, pod_ref);
+            }
+        }
+    }
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pod_spec(pod_spec: serde_json::Value) -> PodSpec {
+        serde_json::from_value(pod_spec).expect("test Pod spec must be valid")
+    }
+
+    fn only(keys: &[&str]) -> ReferencedKeys {
+        ReferencedKeys::Only(keys.iter().map(|key| key.to_string()).collect())
+    }
+
+    fn pod_ref(keys: ReferencedKeys, optional: bool) -> PodRef {
+        PodRef { keys, optional }
+    }
+
+    #[test]
+    fn find_volume_and_env_refs() {
+        let pod_spec = pod_spec(json!({
+            "containers": [{
+                "name": "main",
+                "env": [
+                    {"name": "A", "valueFrom": {"configMapKeyRef": {"name": "cm-env", "key": "a"}}},
+                    {"name": "B", "valueFrom": {"secretKeyRef": {"name": "secret-env", "key": "b", "optional": true}}},
+                    {"name": "C", "value": "plain"},
+                ],
+                "envFrom": [
+                    {"configMapRef": {"name": "cm-env-from"}},
+                    {"secretRef": {"name": "secret-env-from"}},
+                ],
+            }],
+            "initContainers": [{
+                "name": "init",
+                "env": [
+                    {"name": "D", "valueFrom": {"configMapKeyRef": {"name": "cm-env", "key": "d"}}},
+                ],
+            }],
+            "volumes": [
+                {"name": "cm", "configMap": {"name": "cm-volume"}},
+                {"name": "cm-items", "configMap": {"name": "cm-volume-items", "items": [{"key": "x", "path": "x"}]}},
+                {"name": "secret", "secret": {"secretName": "secret-volume", "optional": true}},
+                {"name": "empty", "emptyDir": {}},
+            ],
+        }));
+
+        assert_eq!(
+            config_map_refs(&pod_spec),
+            BTreeMap::from([
+                ("cm-env".to_owned(), pod_ref(only(&["a", "d"]), false)),
+                (
+                    "cm-env-from".to_owned(),
+                    pod_ref(ReferencedKeys::All, false)
+                ),
+                ("cm-volume".to_owned(), pod_ref(ReferencedKeys::All, false)),
+                ("cm-volume-items".to_owned(), pod_ref(only(&["x"]), false)),
+            ])
+        );
+        assert_eq!(
+            secret_refs(&pod_spec),
+            BTreeMap::from([
+                ("secret-env".to_owned(), pod_ref(only(&["b"]), true)),
+                (
+                    "secret-env-from".to_owned(),
+                    pod_ref(ReferencedKeys::All, false)
+                ),
+                (
+                    "secret-volume".to_owned(),
+                    pod_ref(ReferencedKeys::All, true)
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn find_projected_volume_refs() {
+        let pod_spec = pod_spec(json!({
+            "containers": [{"name": "main"}],
+            "volumes": [{
+                "name": "bundle",
+                "projected": {
+                    "sources": [
+                        {"configMap": {"name": "config", "items": [{"key": "a", "path": "a"}, {"key": "b", "path": "b"}]}},
+                        {"configMap": {"name": "extra-config", "optional": true}},
+                        {"secret": {"name": "credentials", "items": [{"key": "password", "path": "password"}]}},
+                        {"secret": {"name": "tls", "optional": true}},
+                        {"downwardAPI": {"items": [{"path": "labels", "fieldRef": {"fieldPath": "metadata.labels"}}]}},
+                        {"serviceAccountToken": {"path": "token"}},
+                    ],
+                },
+            }],
+        }));
+
+        assert_eq!(
+            config_map_refs(&pod_spec),
+            BTreeMap::from([
+                ("config".to_owned(), pod_ref(only(&["a", "b"]), false)),
+                (
+                    "extra-config".to_owned(),
+                    pod_ref(ReferencedKeys::All, true)
+                ),
+            ])
+        );
+        assert_eq!(
+            secret_refs(&pod_spec),
+            BTreeMap::from([
+                (
+                    "credentials".to_owned(),
+                    pod_ref(only(&["password"]), false)
+                ),
+                ("tls".to_owned(), pod_ref(ReferencedKeys::All, true)),
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_refs_to_same_object() {
+        let pod_spec = pod_spec(json!({
+            "containers": [{
+                "name": "main",
+                "env": [
+                    {"name": "A", "valueFrom": {"configMapKeyRef": {"name": "only-keys", "key": "a", "optional": true}}},
+                    {"name": "B", "valueFrom": {"configMapKeyRef": {"name": "keys-and-all", "key": "b", "optional": true}}},
+                ],
+            }],
+            "volumes": [{
+                "name": "bundle",
+                "projected": {
+                    "sources": [
+                        {"configMap": {"name": "only-keys", "items": [{"key": "c", "path": "c"}], "optional": true}},
+                        {"configMap": {"name": "keys-and-all"}},
+                    ],
+                },
+            }],
+        }));
+
+        assert_eq!(
+            config_map_refs(&pod_spec),
+            BTreeMap::from([
+                (
+                    "keys-and-all".to_owned(),
+                    pod_ref(ReferencedKeys::All, false)
+                ),
+                ("only-keys".to_owned(), pod_ref(only(&["a", "c"]), true)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use stackable_operator::k8s_openapi::api::apps::v1::StatefulSet;
+
+/// Annotation listing the StatefulSets (in the same namespace) that need to complete their rollout
+/// before a StatefulSet is restarted, e.g. `zookeeper-server-default,hdfs-namenode-default`.
+pub const RESTART_AFTER_ANNOTATION: &str = "restarter.stackable.tech/restart-after";
+
+/// Returns the names of the StatefulSets listed in the [`RESTART_AFTER_ANNOTATION`].
+pub fn restart_after(annotations: &BTreeMap<String, String>) -> BTreeSet<String> {
+    annotations
+        .get(RESTART_AFTER_ANNOTATION)
+        .into_iter()
+        .flat_map(|names| names.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Whether the StatefulSet completed its rollout, i.e. all Pods run the latest revision and are
+/// ready.
+pub fn rollout_completed(stateful_set: &StatefulSet) -> bool {
+    let Some(status) = &stateful_set.status else {
+        return false;
+    };
+    let replicas = stateful_set
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1);
+    // The status might not reflect the latest changes (such as a restart) yet
+    status.observed_generation.is_some()
+        && status.observed_generation == stateful_set.metadata.generation
+        && status.update_revision.is_some()
+        && status.update_revision == status.current_revision
+        && status.updated_replicas.unwrap_or(0) >= replicas
+        && status.ready_replicas.unwrap_or(0) >= replicas
+}
+
+/// Returns a cycle of the restart order that is reachable from `start`, as the names of the
+/// StatefulSets along the cycle (starting and ending with the same one).
+///
+/// `restart_after` contains the StatefulSets each StatefulSet is restarted after, StatefulSets that
+/// are not part of it don't restart after any other.
+pub fn find_cycle(
+    start: &str,
+    restart_after: &BTreeMap<String, BTreeSet<String>>,
+) -> Option<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        restart_after: &'a BTreeMap<String, BTreeSet<String>>,
+        path: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(position) = path.iter().position(|visited| *visited == name) {
+            let mut cycle = path[position..]
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            cycle.push(name.to_owned());
+            return Some(cycle);
+        }
+        if !done.insert(name) {
+            return None;
+        }
+        path.push(name);
+        for dependency in restart_after.get(name).into_iter().flatten() {
+            if let Some(cycle) = visit(dependency, restart_after, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        None
+    }
+
+    visit(start, restart_after, &mut Vec::new(), &mut BTreeSet::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::k8s_openapi::{
+        api::apps::v1::{StatefulSetSpec, StatefulSetStatus},
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    };
+
+    use super::*;
+
+    fn restart_order(order: &[(&str, &[&str])]) -> BTreeMap<String, BTreeSet<String>> {
+        order
+            .iter()
+            .map(|(name, after)| {
+                (
+                    name.to_string(),
+                    after.iter().map(|name| name.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_restart_after() {
+        let annotations = BTreeMap::from([(
+            RESTART_AFTER_ANNOTATION.to_owned(),
+            " zookeeper, hdfs-namenode,,zookeeper ".to_owned(),
+        )]);
+        assert_eq!(
+            restart_after(&annotations),
+            BTreeSet::from(["hdfs-namenode".to_owned(), "zookeeper".to_owned()])
+        );
+        assert!(restart_after(&BTreeMap::new()).is_empty());
+    }
+
+    #[test]
+    fn rollout_completion() {
+        let stateful_set = |generation, update_revision: &str, ready_replicas| StatefulSet {
+            metadata: ObjectMeta {
+                generation: Some(generation),
+                ..ObjectMeta::default()
+            },
+            spec: Some(StatefulSetSpec {
+                replicas: Some(3),
+                ..StatefulSetSpec::default()
+            }),
+            status: Some(StatefulSetStatus {
+                observed_generation: Some(2),
+                current_revision: Some("a".to_owned()),
+                update_revision: Some(update_revision.to_owned()),
+                updated_replicas: Some(3),
+                ready_replicas: Some(ready_replicas),
+                ..StatefulSetStatus::default()
+            }),
+        };
+        assert!(rollout_completed(&stateful_set(2, "a", 3)));
+        // Not observed yet
+        assert!(!rollout_completed(&stateful_set(3, "a", 3)));
+        // Still rolling
+        assert!(!rollout_completed(&stateful_set(2, "b", 3)));
+        // Not ready
+        assert!(!rollout_completed(&stateful_set(2, "a", 2)));
+        assert!(!rollout_completed(&StatefulSet::default()));
+    }
+
+    #[test]
+    fn detect_cycles() {
+        let order = restart_order(&[
+            ("kafka", &["zookeeper"]),
+            ("zookeeper", &[]),
+            ("datanode", &["namenode", "journalnode"]),
+            ("namenode", &["journalnode"]),
+            ("journalnode", &["datanode"]),
+            ("self", &["self"]),
+        ]);
+        assert_eq!(find_cycle("kafka", &order), None);
+        assert_eq!(find_cycle("unknown", &order), None);
+        assert_eq!(
+            find_cycle("datanode", &order),
+            Some(vec![
+                "datanode".to_owned(),
+                "journalnode".to_owned(),
+                "datanode".to_owned()
+            ])
+        );
+        assert_eq!(
+            find_cycle("self", &order),
+            Some(vec!["self".to_owned(), "self".to_owned()])
+        );
+    }
+}
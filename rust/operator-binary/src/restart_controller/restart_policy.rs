@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use jiff::Timestamp;
+use snafu::{ResultExt, Snafu};
+use stackable_operator::time::{Duration, DurationParseError};
+
+use crate::utils::cron::{self, CronSchedule};
+
+/// Annotation stating how long after their creation Pods expire, e.g. `7d`.
+pub const MAX_AGE_ANNOTATION: &str = "restarter.stackable.tech/max-age";
+
+/// Annotation stating a cron schedule at which Pods expire, e.g. `0 3 * * SUN`.
+pub const RESTART_SCHEDULE_ANNOTATION: &str = "restarter.stackable.tech/restart-schedule";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("invalid duration {value:?} in annotation {MAX_AGE_ANNOTATION:?}"))]
+    InvalidMaxAge {
+        source: DurationParseError,
+        value: String,
+    },
+
+    #[snafu(display(
+        "invalid cron schedule {value:?} in annotation {RESTART_SCHEDULE_ANNOTATION:?}"
+    ))]
+    InvalidRestartSchedule { source: cron::Error, value: String },
+}
+
+/// Returns when a Pod that was created at `created_at` expires due to the [`MAX_AGE_ANNOTATION`]
+/// or the [`RESTART_SCHEDULE_ANNOTATION`], whichever is sooner.
+///
+/// With a restart schedule, the Pod expires at the first time matching the schedule after it was
+/// created, so that every replacement Pod runs until the next one.
+pub fn expires_at(
+    annotations: &BTreeMap<String, String>,
+    created_at: Timestamp,
+) -> Result<Option<Timestamp>, Error> {
+    let max_age_expires_at = annotations
+        .get(MAX_AGE_ANNOTATION)
+        .map(|value| {
+            value
+                .parse::<Duration>()
+                .context(InvalidMaxAgeSnafu { value })
+        })
+        .transpose()?
+        .and_then(|max_age| created_at.checked_add(*max_age).ok());
+    let schedule_expires_at = annotations
+        .get(RESTART_SCHEDULE_ANNOTATION)
+        .map(|value| {
+            value
+                .parse::<CronSchedule>()
+                .context(InvalidRestartScheduleSnafu { value })
+        })
+        .transpose()?
+        .and_then(|schedule| schedule.next_after(created_at));
+    Ok(max_age_expires_at
+        .into_iter()
+        .chain(schedule_expires_at)
+        .min())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        timestamp.parse().unwrap()
+    }
+
+    fn annotations(annotations: &[(&str, &str)]) -> BTreeMap<String, String> {
+        annotations
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expire_after_max_age() {
+        assert_eq!(
+            expires_at(
+                &annotations(&[(MAX_AGE_ANNOTATION, "7d")]),
+                ts("2026-03-01T12:00:00Z")
+            )
+            .unwrap(),
+            Some(ts("2026-03-08T12:00:00Z"))
+        );
+        assert_eq!(
+            expires_at(&BTreeMap::new(), ts("2026-03-01T12:00:00Z")).unwrap(),
+            None
+        );
+        assert!(
+            expires_at(
+                &annotations(&[(MAX_AGE_ANNOTATION, "a week")]),
+                ts("2026-03-01T12:00:00Z")
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn expire_on_schedule() {
+        // 2026-03-01 is a Sunday
+        let schedule = annotations(&[(RESTART_SCHEDULE_ANNOTATION, "0 3 * * SUN")]);
+        assert_eq!(
+            expires_at(&schedule, ts("2026-03-01T02:00:00Z")).unwrap(),
+            Some(ts("2026-03-01T03:00:00Z"))
+        );
+        // Replacements run until the next scheduled restart
+        assert_eq!(
+            expires_at(&schedule, ts("2026-03-01T03:00:30Z")).unwrap(),
+            Some(ts("2026-03-08T03:00:00Z"))
+        );
+        assert!(
+            expires_at(
+                &annotations(&[(RESTART_SCHEDULE_ANNOTATION, "every sunday")]),
+                ts("2026-03-01T02:00:00Z")
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn sooner_policy_wins() {
+        let policies = annotations(&[
+            (MAX_AGE_ANNOTATION, "2d"),
+            (RESTART_SCHEDULE_ANNOTATION, "0 3 * * SUN"),
+        ]);
+        assert_eq!(
+            expires_at(&policies, ts("2026-03-01T12:00:00Z")).unwrap(),
+            Some(ts("2026-03-03T12:00:00Z"))
+        );
+        assert_eq!(
+            expires_at(&policies, ts("2026-03-07T12:00:00Z")).unwrap(),
+            Some(ts("2026-03-08T03:00:00Z"))
+        );
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use stackable_operator::kube::{core::DynamicObject, runtime::reflector::ObjectRef};
+
+/// How long workloads stay queued without asking for admission again, e.g. because their restart
+/// is not needed anymore or was deferred for other reasons.
+const QUEUE_TTL: Duration = Duration::from_secs(60);
+
+/// Maximum numbers of rollouts triggered by the restarter that may be in progress at the same time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RolloutLimits {
+    /// Maximum number of rollouts in all namespaces, unlimited if [`None`].
+    pub total: Option<usize>,
+
+    /// Maximum number of rollouts in a single namespace, unlimited if [`None`].
+    pub per_namespace: Option<usize>,
+}
+
+/// The rollouts started by the restarter that didn't complete yet, and the workloads waiting to
+/// start their rollout once the [`RolloutLimits`] allow it.
+#[derive(Debug, Default)]
+pub struct RolloutBudget {
+    limits: RolloutLimits,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    in_progress: HashSet<ObjectRef<DynamicObject>>,
+    queued: HashMap<ObjectRef<DynamicObject>, QueuedRollout>,
+}
+
+#[derive(Debug)]
+struct QueuedRollout {
+    /// When the workload was queued, earlier workloads are admitted first.
+    queued_at: Instant,
+
+    /// When the workload last asked for admission.
+    seen_at: Instant,
+}
+
+/// Whether a rollout can be started.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// The rollout can be started, it counts towards the limits until it is
+    /// [finished](RolloutBudget::finish).
+    Admitted,
+
+    /// The rollout needs to wait for others to complete.
+    Queued {
+        /// The position of the workload in the queue, starting at 1.
+        position: usize,
+        queue_length: usize,
+
+        /// Whether the workload was queued just now.
+        newly_queued: bool,
+    },
+}
+
+/// The number of rollouts in progress and queued, by namespace.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RolloutCounts {
+    pub in_progress: BTreeMap<String, usize>,
+    pub queued: BTreeMap<String, usize>,
+}
+
+impl RolloutBudget {
+    pub fn new(limits: RolloutLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Checks whether the rollout of the workload can be started, queueing it otherwise.
+    ///
+    /// Queued workloads are admitted in a fair order: workloads in namespaces with fewer rollouts in
+    /// progress go first, then the ones that were queued first. Workloads need to ask for admission
+    /// again until they are admitted.
+    pub fn admit(&self, workload_ref: &ObjectRef<DynamicObject>, now: Instant) -> Admission {
+        if self.limits.total.is_none() && self.limits.per_namespace.is_none() {
+            return Admission::Admitted;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // A restart during a rollout doesn't start an additional one
+        if state.in_progress.contains(workload_ref) {
+            return Admission::Admitted;
+        }
+        state
+            .queued
+            .retain(|_, queued| now.saturating_duration_since(queued.seen_at) < QUEUE_TTL);
+        let newly_queued = match state.queued.get_mut(workload_ref) {
+            Some(queued) => {
+                queued.seen_at = now;
+                false
+            }
+            None => {
+                state.queued.insert(
+                    workload_ref.clone(),
+                    QueuedRollout {
+                        queued_at: now,
+                        seen_at: now,
+                    },
+                );
+                true
+            }
+        };
+
+        let queue = state.admission_order(&self.limits);
+        let queue_length = queue.len();
+        let (position, admitted) = queue
+            .iter()
+            .enumerate()
+            .find(|(_, (queued_ref, _))| *queued_ref == workload_ref)
+            .map(|(position, (_, admitted))| (position + 1, *admitted))
+            .expect("the workload was queued above");
+        if admitted {
+            state.queued.remove(workload_ref);
+            state.in_progress.insert(workload_ref.clone());
+            Admission::Admitted
+        } else {
+            Admission::Queued {
+                position,
+                queue_length,
+                newly_queued,
+            }
+        }
+    }
+
+    /// Counts a rollout that is already in progress towards the limits, e.g. because it was started
+    /// before the operator was restarted.
+    ///
+    /// Returns whether the rollout wasn't counted yet.
+    pub fn resume(&self, workload_ref: &ObjectRef<DynamicObject>) -> bool {
+        if self.limits.total.is_none() && self.limits.per_namespace.is_none() {
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.queued.remove(workload_ref);
+        state.in_progress.insert(workload_ref.clone())
+    }
+
+    /// Whether the rollout of the workload was admitted and didn't finish yet.
+    pub fn is_in_progress(&self, workload_ref: &ObjectRef<DynamicObject>) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .in_progress
+            .contains(workload_ref)
+    }
+
+    /// Forgets the rollout of the workload, e.g. because it completed or the workload was deleted.
+    ///
+    /// Returns whether anything was forgotten.
+    pub fn finish(&self, workload_ref: &ObjectRef<DynamicObject>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let in_progress = state.in_progress.remove(workload_ref);
+        let queued = state.queued.remove(workload_ref).is_some();
+        in_progress || queued
+    }
+
+    /// Removes the workload from the queue, e.g. because its restart is not needed anymore.
+    ///
+    /// Returns whether the workload was queued.
+    pub fn dequeue(&self, workload_ref: &ObjectRef<DynamicObject>) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .queued
+            .remove(workload_ref)
+            .is_some()
+    }
+
+    /// Returns the number of rollouts in progress and queued, by namespace.
+    pub fn counts(&self) -> RolloutCounts {
+        let state = self.state.lock().unwrap();
+        let mut counts = RolloutCounts::default();
+        for workload_ref in &state.in_progress {
+            *counts
+                .in_progress
+                .entry(namespace(workload_ref).to_owned())
+                .or_default() += 1;
+        }
+        for workload_ref in state.queued.keys() {
+            *counts
+                .queued
+                .entry(namespace(workload_ref).to_owned())
+                .or_default() += 1;
+        }
+        counts
+    }
+}
+
+impl State {
+    /// Returns the queued workloads in the order they are admitted, together with whether they fit
+    /// into the limits right now.
+    fn admission_order(&self, limits: &RolloutLimits) -> Vec<(&ObjectRef<DynamicObject>, bool)> {
+        let mut in_progress_by_namespace = HashMap::<&str, usize>::new();
+        for workload_ref in &self.in_progress {
+            *in_progress_by_namespace
+                .entry(namespace(workload_ref))
+                .or_default() += 1;
+        }
+
+        let mut queue = self.queued.iter().collect::<Vec<_>>();
+        // Namespaces with fewer rollouts in progress go first, so that a single namespace with many
+        // workloads can't take up the whole budget
+        queue.sort_by_cached_key(|(workload_ref, queued)| {
+            (
+                in_progress_by_namespace
+                    .get(namespace(workload_ref))
+                    .copied()
+                    .unwrap_or_default(),
+                queued.queued_at,
+                workload_ref.to_string(),
+            )
+        });
+
+        let mut in_progress = self.in_progress.len();
+        queue
+            .into_iter()
+            .map(|(workload_ref, _)| {
+                let in_namespace = in_progress_by_namespace
+                    .entry(namespace(workload_ref))
+                    .or_default();
+                let fits = limits.total.is_none_or(|total| in_progress < total)
+                    && limits
+                        .per_namespace
+                        .is_none_or(|per_namespace| *in_namespace < per_namespace);
+                if fits {
+                    in_progress += 1;
+                    *in_namespace += 1;
+                }
+                (workload_ref, fits)
+            })
+            .collect()
+    }
+}
+
+fn namespace(workload_ref: &ObjectRef<DynamicObject>) -> &str {
+    workload_ref.namespace.as_deref().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::{k8s_openapi::api::apps::v1::StatefulSet, kube::api::ApiResource};
+
+    use super::*;
+
+    fn workload_ref(namespace: &str, name: &str) -> ObjectRef<DynamicObject> {
+        ObjectRef::new_with(name, ApiResource::erase::<StatefulSet>(&())).within(namespace)
+    }
+
+    fn queued(position: usize, queue_length: usize, newly_queued: bool) -> Admission {
+        Admission::Queued {
+            position,
+            queue_length,
+            newly_queued,
+        }
+    }
+
+    #[test]
+    fn unlimited() {
+        let budget = RolloutBudget::default();
+        let now = Instant::now();
+        for name in ["a", "b", "c"] {
+            assert_eq!(
+                budget.admit(&workload_ref("default", name), now),
+                Admission::Admitted
+            );
+        }
+        assert_eq!(budget.counts(), RolloutCounts::default());
+    }
+
+    #[test]
+    fn queue_until_finished() {
+        let budget = RolloutBudget::new(RolloutLimits {
+            total: Some(1),
+            per_namespace: None,
+        });
+        let start = Instant::now();
+        let (a, b, c) = (
+            workload_ref("default", "a"),
+            workload_ref("default", "b"),
+            workload_ref("default", "c"),
+        );
+        assert_eq!(budget.admit(&a, start), Admission::Admitted);
+        // Restarts during the rollout don't need another slot
+        assert_eq!(budget.admit(&a, start), Admission::Admitted);
+        assert_eq!(budget.admit(&c, start), queued(1, 1, true));
+        assert_eq!(
+            budget.admit(&b, start + Duration::from_secs(1)),
+            queued(2, 2, true)
+        );
+        assert_eq!(
+            budget.admit(&b, start + Duration::from_secs(2)),
+            queued(2, 2, false)
+        );
+        assert_eq!(
+            budget.counts(),
+            RolloutCounts {
+                in_progress: BTreeMap::from([("default".to_owned(), 1)]),
+                queued: BTreeMap::from([("default".to_owned(), 2)]),
+            }
+        );
+
+        assert!(budget.finish(&a));
+        assert!(!budget.is_in_progress(&a));
+        // The first queued workload is admitted, even if another one asks first
+        assert_eq!(
+            budget.admit(&b, start + Duration::from_secs(3)),
+            queued(2, 2, false)
+        );
+        assert_eq!(
+            budget.admit(&c, start + Duration::from_secs(3)),
+            Admission::Admitted
+        );
+        assert!(budget.is_in_progress(&c));
+    }
+
+    #[test]
+    fn resume_rollouts_in_progress() {
+        let budget = RolloutBudget::new(RolloutLimits {
+            total: Some(1),
+            per_namespace: None,
+        });
+        let now = Instant::now();
+        let (a, b) = (workload_ref("default", "a"), workload_ref("default", "b"));
+        assert!(budget.resume(&a));
+        assert!(!budget.resume(&a));
+        assert!(budget.is_in_progress(&a));
+        assert_eq!(budget.admit(&b, now), queued(1, 1, true));
+
+        assert!(budget.finish(&a));
+        assert_eq!(budget.admit(&b, now), Admission::Admitted);
+
+        // Without limits, nothing is tracked
+        assert!(!RolloutBudget::default().resume(&a));
+    }
+
+    #[test]
+    fn fair_between_namespaces() {
+        let budget = RolloutBudget::new(RolloutLimits {
+            total: Some(3),
+            per_namespace: Some(2),
+        });
+        let start = Instant::now();
+        assert_eq!(
+            budget.admit(&workload_ref("a", "1"), start),
+            Admission::Admitted
+        );
+        assert_eq!(
+            budget.admit(&workload_ref("a", "2"), start),
+            Admission::Admitted
+        );
+        // The namespace limit is reached
+        assert_eq!(
+            budget.admit(&workload_ref("a", "3"), start),
+            queued(1, 1, true)
+        );
+        assert_eq!(
+            budget.admit(&workload_ref("b", "1"), start + Duration::from_secs(1)),
+            Admission::Admitted
+        );
+        // The total limit is reached
+        assert_eq!(
+            budget.admit(&workload_ref("c", "1"), start + Duration::from_secs(2)),
+            queued(1, 2, true)
+        );
+
+        budget.finish(&workload_ref("a", "1"));
+        // Namespace c has no rollouts in progress, so it goes before a, even though it was queued
+        // later
+        assert_eq!(
+            budget.admit(&workload_ref("a", "3"), start + Duration::from_secs(3)),
+            queued(2, 2, false)
+        );
+        assert_eq!(
+            budget.admit(&workload_ref("c", "1"), start + Duration::from_secs(3)),
+            Admission::Admitted
+        );
+    }
+
+    #[test]
+    fn forget_stale_queue_entries() {
+        let budget = RolloutBudget::new(RolloutLimits {
+            total: Some(1),
+            per_namespace: None,
+        });
+        let start = Instant::now();
+        let (a, b, c) = (
+            workload_ref("default", "a"),
+            workload_ref("default", "b"),
+            workload_ref("default", "c"),
+        );
+        budget.admit(&a, start);
+        budget.admit(&b, start);
+        assert_eq!(budget.admit(&c, start), queued(2, 2, true));
+        // b stopped asking for admission
+        assert_eq!(budget.admit(&c, start + QUEUE_TTL / 2), queued(2, 2, false));
+        assert_eq!(budget.admit(&c, start + QUEUE_TTL), queued(1, 1, false));
+
+        assert!(budget.dequeue(&c));
+        assert!(!budget.dequeue(&c));
+        assert_eq!(budget.admit(&c, start + QUEUE_TTL), queued(1, 1, true));
+    }
+}
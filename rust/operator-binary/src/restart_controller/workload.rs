@@ -0,0 +1,2009 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet, VecDeque},
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::Instant,
+};
+
+use futures::{FutureExt, Stream, StreamExt, TryStream, TryStreamExt, future, stream};
+use serde_json::json;
+use snafu::{OptionExt, Report, ResultExt, Snafu};
+use stackable_operator::{
+    client::Client,
+    k8s_openapi::{
+        api::{
+            apps::v1::StatefulSet,
+            core::v1::{ConfigMap, Namespace, PodTemplateSpec, Secret},
+        },
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    },
+    kube::{
+        self, Api, Resource, ResourceExt,
+        api::{ApiResource, Patch, PatchParams},
+        core::DynamicObject,
+        runtime::{
+            Config, WatchStreamExt, applier,
+            controller::{Action, ReconcileRequest, trigger_self, trigger_with},
+            events::{Event, EventType, Recorder, Reporter},
+            metadata_watcher, reflector,
+            reflector::{ObjectRef, Store},
+            watcher,
+        },
+    },
+    logging::controller::{ReconcilerError, report_controller_reconciled},
+    namespace::WatchNamespace,
+    time::{Duration, DurationParseError},
+};
+use strum::{EnumDiscriminants, EnumString, IntoStaticStr};
+use tokio::sync::broadcast;
+
+use crate::{
+    metrics::{Metrics, RestartCause},
+    restart_controller::{
+        self, EXPIRES_AT_ANNOTATION_PREFIX,
+        canary::{self, CANARY_PARTITION_ANNOTATION, Canary, CanaryProgress, StatefulSetRollout},
+        config_object::{ChangeDetection, ConfigObject, config_object_watcher},
+        ignore_patterns::{
+            self, IGNORE_CONFIG_MAP_ANNOTATION_PREFIX, IGNORE_SECRET_ANNOTATION_PREFIX,
+            IgnorePatterns,
+        },
+        maintenance_window::{self, MaintenanceWindows, duration_until},
+        namespace_selection::NamespaceSelection,
+        pending_restarts::{PendingRestarts, SettleState},
+        pod_refs::{self, PodRef, ReferencedKeys},
+        restart_order,
+        rollout_budget::{Admission, RolloutBudget, RolloutLimits},
+        workload_index::{ConfigKind, ConfigRef, WorkloadIndex},
+        workload_kind::{self, WorkloadKind},
+    },
+    utils::{
+        delayed_init::{DelayedInit, InitDropped, Initializer},
+        label_selector::LabelSelectorExpression,
+        watched_namespaces::WatchedNamespaces,
+    },
+};
+
+/// How many ConfigMap/Secret changes can be buffered for each workload controller before the
+/// oldest ones are dropped (in which case all workloads are reconciled).
+const CONFIG_CHANGE_CHANNEL_CAPACITY: usize = 1024;
+
+const CONFIG_MAP_ANNOTATION_PREFIX: &str = "configmap.restarter.stackable.tech/";
+const SECRET_ANNOTATION_PREFIX: &str = "secret.restarter.stackable.tech/";
+
+/// Version recorded for ConfigMaps and Secrets whose changes are ignored.
+const IGNORED_VERSION: &str = "changes-ignored";
+
+/// Version recorded for referenced ConfigMaps and Secrets that don't exist.
+const MISSING_VERSION: &str = "missing";
+
+/// Annotation overriding the [`MissingConfigPolicy`] of a workload.
+const MISSING_CONFIG_POLICY_ANNOTATION: &str = "restarter.stackable.tech/missing-config-policy";
+
+/// Label selector of the workloads that opted into being restarted.
+const ENABLED_LABEL_SELECTOR: &str = "restarter.stackable.tech/enabled=true";
+
+/// Annotation overriding the settle window of a workload.
+const SETTLE_WINDOW_ANNOTATION: &str = "restarter.stackable.tech/settle-window";
+
+/// Annotation requesting a restart of a workload, changing its value (e.g. to the current time)
+/// restarts the workload once.
+///
+/// The handled value is recorded in the same annotation on the Pod template.
+const RESTART_REQUESTED_AT_ANNOTATION: &str = "restarter.stackable.tech/restart-requested-at";
+
+/// Maximum length of the note of an event, longer notes are rejected by Kubernetes.
+const MAX_EVENT_NOTE_LENGTH: usize = 1024;
+
+/// How often the progress of canary rollouts is checked while Pods are restarted.
+const CANARY_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often the rollouts of the StatefulSets that a workload is restarted after are checked.
+const RESTART_ORDER_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often workloads with a cyclic restart order are checked again, the cycle might be resolved
+/// by changing other workloads, which doesn't trigger a reconciliation.
+const RESTART_ORDER_CYCLE_RECHECK_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(60);
+
+/// How often queued restarts check whether the rollout budget admits them.
+const ROLLOUT_BUDGET_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Options of the workload restarter that apply to all workloads.
+pub struct Options {
+    /// How long the referenced ConfigMaps and Secrets need to be unchanged before a workload is
+    /// restarted, unless overridden by the workload.
+    pub settle_window: Duration,
+
+    /// Only report restarts instead of applying them, unless overridden by the workload.
+    pub dry_run: bool,
+
+    /// How references to missing ConfigMaps and Secrets are handled, unless overridden by the
+    /// workload.
+    pub missing_config_policy: MissingConfigPolicy,
+
+    /// Restart all workloads, not only the ones labelled with `restarter.stackable.tech/enabled`.
+    pub all_workloads: bool,
+
+    /// Additional label selector that workloads need to match to be restarted.
+    pub workload_selector: Option<LabelSelectorExpression>,
+
+    /// Label selector that the namespaces of workloads need to match to be restarted.
+    pub namespace_selector: Option<LabelSelectorExpression>,
+
+    /// How long before their TLS certificates expire Pods are evicted, [`None`] if the expiry of
+    /// certificates in referenced Secrets is not tracked.
+    pub tls_expiry_margin: Option<Duration>,
+
+    /// How many rollouts of StatefulSets started by the restarter may be in progress at the same
+    /// time, further restarts are queued.
+    pub rollout_limits: RolloutLimits,
+
+    /// How changes of referenced ConfigMaps and Secrets are detected.
+    pub change_detection: ChangeDetection,
+}
+
+impl Options {
+    /// Returns the label selector that workloads need to match to be restarted, [`None`] if all
+    /// workloads are restarted.
+    pub fn workload_label_selector(&self) -> Option<LabelSelectorExpression> {
+        let enabled_selector = (!self.all_workloads).then(|| {
+            ENABLED_LABEL_SELECTOR
+                .parse::<LabelSelectorExpression>()
+                .expect("static label selector is always valid")
+        });
+        match (enabled_selector, &self.workload_selector) {
+            (Some(enabled_selector), Some(workload_selector)) => {
+                Some(enabled_selector.and(workload_selector))
+            }
+            (enabled_selector, workload_selector) => {
+                enabled_selector.or_else(|| workload_selector.clone())
+            }
+        }
+    }
+}
+
+/// How the restarter handles workloads referencing ConfigMaps or Secrets that don't exist (yet).
+///
+/// Missing references that are marked as `optional` are never tracked.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, EnumString, strum::Display,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum MissingConfigPolicy {
+    /// Restart the workload once the missing object is created.
+    #[default]
+    Restart,
+
+    /// Don't restart the workload when the missing object is created, it is only picked up by the
+    /// next restart due to other changes.
+    Wait,
+
+    /// Don't restart the workload at all while any referenced object is missing, pending restarts
+    /// are applied once all of them exist.
+    Block,
+}
+
+pub struct Ctx {
+    client: Client,
+    workload_kinds: Vec<WorkloadKind>,
+    options: Options,
+    cms: DelayedInit<Store<ConfigObject<ConfigMap>>>,
+    secrets: DelayedInit<Store<ConfigObject<Secret>>>,
+    pending_restarts: PendingRestarts,
+    rollout_budget: RolloutBudget,
+    namespace_selection: Option<NamespaceSelection>,
+    metrics: Arc<Metrics>,
+}
+
+impl Ctx {
+    /// Returns the registered [`WorkloadKind`] for objects of the given group and kind, if any.
+    pub fn workload_kind(&self, group: &str, kind: &str) -> Option<&WorkloadKind> {
+        self.workload_kinds.iter().find(|workload_kind| {
+            workload_kind.api_resource.group == group && workload_kind.api_resource.kind == kind
+        })
+    }
+
+    pub fn workload_kinds(&self) -> &[WorkloadKind] {
+        &self.workload_kinds
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Records the current state of the rollout budget in the metrics.
+    fn rollout_budget_changed(&self) {
+        self.metrics.rollouts_changed(&self.rollout_budget.counts());
+    }
+}
+
+#[derive(Snafu, Debug, EnumDiscriminants)]
+#[strum_discriminants(derive(IntoStaticStr))]
+pub enum Error {
+    #[snafu(display("workload has no namespace"))]
+    WorkloadHasNoNamespace,
+
+    #[snafu(display("Pod template of the workload is invalid"))]
+    InvalidPodTemplate { source: serde_json::Error },
+
+    #[snafu(display("failed to patch object {obj_ref}"))]
+    PatchFailed {
+        source: kube::Error,
+        obj_ref: Box<ObjectRef<DynamicObject>>,
+    },
+
+    #[snafu(display("configmap initializer was cancelled"))]
+    ConfigMapsUninitialized { source: InitDropped },
+
+    #[snafu(display("secrets initializer was cancelled"))]
+    SecretsUninitialized { source: InitDropped },
+
+    #[snafu(display("invalid settle window {value:?} in annotation {SETTLE_WINDOW_ANNOTATION:?}"))]
+    InvalidSettleWindow {
+        source: DurationParseError,
+        value: String,
+    },
+
+    #[snafu(display("invalid maintenance window"))]
+    InvalidMaintenanceWindow { source: maintenance_window::Error },
+
+    #[snafu(display(
+        "invalid missing config policy {value:?} in annotation {MISSING_CONFIG_POLICY_ANNOTATION:?}"
+    ))]
+    InvalidMissingConfigPolicy {
+        source: strum::ParseError,
+        value: String,
+    },
+
+    #[snafu(display("invalid canary rollout configuration"))]
+    InvalidCanary { source: canary::Error },
+
+    #[snafu(display("failed to determine the progress of the canary rollout"))]
+    CanaryProgress { source: canary::Error },
+
+    #[snafu(display("failed to get StatefulSet {name:?} that the workload is restarted after"))]
+    GetRestartDependency { source: kube::Error, name: String },
+
+    #[snafu(display("StatefulSet {name:?} that the workload is restarted after is invalid"))]
+    InvalidRestartDependency {
+        source: kube::core::dynamic::ParseDynamicObjectError,
+        name: String,
+    },
+
+    #[snafu(display("restart order contains the cycle {cycle}"))]
+    RestartOrderCycle { cycle: String },
+
+    #[snafu(display("failed to parse the StatefulSet"))]
+    InvalidStatefulSet {
+        source: kube::core::dynamic::ParseDynamicObjectError,
+    },
+}
+
+impl ReconcilerError for Error {
+    fn category(&self) -> &'static str {
+        ErrorDiscriminants::from(self).into()
+    }
+
+    fn secondary_object(&self) -> Option<ObjectRef<DynamicObject>> {
+        match self {
+            Error::WorkloadHasNoNamespace => None,
+            Error::InvalidPodTemplate { .. } => None,
+            Error::PatchFailed { obj_ref, .. } => Some(*obj_ref.clone()),
+            Error::ConfigMapsUninitialized { .. } => None,
+            Error::SecretsUninitialized { .. } => None,
+            Error::InvalidSettleWindow { .. } => None,
+            Error::InvalidMaintenanceWindow { .. } => None,
+            Error::InvalidMissingConfigPolicy { .. } => None,
+            Error::InvalidCanary { .. } => None,
+            Error::CanaryProgress { .. } => None,
+            Error::GetRestartDependency { .. } => None,
+            Error::InvalidRestartDependency { .. } => None,
+            Error::RestartOrderCycle { .. } => None,
+            Error::InvalidStatefulSet { .. } => None,
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn create_context(
+    client: Client,
+    workload_kinds: Vec<WorkloadKind>,
+    options: Options,
+    metrics: Arc<Metrics>,
+) -> (
+    Arc<Ctx>,
+    Initializer<Store<ConfigObject<ConfigMap>>>,
+    Initializer<Store<ConfigObject<Secret>>>,
+) {
+    let (cm_store_tx, cm_store_delayed) = DelayedInit::new();
+    let (secret_store_tx, secret_store_delayed) = DelayedInit::new();
+    let ctx = Arc::new(Ctx {
+        client,
+        workload_kinds,
+        cms: cm_store_delayed,
+        secrets: secret_store_delayed,
+        namespace_selection: options
+            .namespace_selector
+            .clone()
+            .map(NamespaceSelection::new),
+        rollout_budget: RolloutBudget::new(options.rollout_limits),
+        options,
+        pending_restarts: PendingRestarts::default(),
+        metrics,
+    });
+
+    (ctx, cm_store_tx, secret_store_tx)
+}
+
+pub async fn start<F>(
+    ctx: Arc<Ctx>,
+    cm_store_tx: Initializer<Store<ConfigObject<ConfigMap>>>,
+    secret_store_tx: Initializer<Store<ConfigObject<Secret>>>,
+    watched_namespaces: &WatchedNamespaces,
+    shutdown_signal: F,
+) where
+    F: Future<Output = ()>,
+{
+    let shutdown_signal = shutdown_signal.shared();
+    let change_detection = ctx.options.change_detection;
+    let cm_store = reflector::store::Writer::<ConfigObject<ConfigMap>>::new(());
+    let secret_store = reflector::store::Writer::<ConfigObject<Secret>>::new(());
+    ctx.metrics.register_store("ConfigMap", {
+        let cm_reader = cm_store.as_reader();
+        move || cm_reader.state().len()
+    });
+    ctx.metrics.register_store("Secret", {
+        let secret_reader = secret_store.as_reader();
+        move || secret_reader.state().len()
+    });
+    let mut cm_store_tx = Some(cm_store_tx);
+    let mut secret_store_tx = Some(secret_store_tx);
+
+    // Workloads in newly selected namespaces need to be reconciled, as changes were ignored so far
+    let namespace_watcher = match &ctx.namespace_selection {
+        Some(namespace_selection) => metadata_watcher(
+            Api::<Namespace>::all(ctx.client.as_kube_client()),
+            watcher::Config::default().labels(namespace_selection.label_selector().as_str()),
+        )
+        .try_filter_map(|event| {
+            future::ready(Ok(namespace_selection
+                .apply_watcher_event(&event)
+                .map(ConfigChange::NamespaceSelected)))
+        })
+        .left_stream(),
+        None => stream::empty().right_stream(),
+    };
+
+    // The ConfigMaps and Secrets are only watched once, every change is then broadcasted to the
+    // controllers of all workload kinds.
+    let (config_changes_tx, _) = broadcast::channel(CONFIG_CHANGE_CHANNEL_CAPACITY);
+    let config_object_changes = stream::select(
+        {
+            let cm_reader = cm_store.as_reader();
+            let client = ctx.client.clone();
+            reflector(
+                cm_store,
+                watched_namespaces.watch(&ctx.client, cm_reader.clone(), move |watch_namespace| {
+                    config_object_watcher(
+                        watch_namespace.get_api::<ConfigMap>(&client),
+                        watcher::Config::default()
+                            .labels("restarter.stackable.tech/ignore != true"),
+                        change_detection,
+                        false,
+                    )
+                }),
+            )
+            .inspect(move |_| {
+                if let Some(tx) = cm_store_tx.take() {
+                    tx.init(cm_reader.clone());
+                }
+            })
+            .touched_objects()
+            .map_ok(|cm| ConfigChange::Object(ConfigRef::from_obj(ConfigKind::ConfigMap, &cm)))
+        },
+        {
+            let secret_reader = secret_store.as_reader();
+            let client = ctx.client.clone();
+            let track_certificate_expiry = ctx.options.tls_expiry_margin.is_some();
+            reflector(
+                secret_store,
+                watched_namespaces.watch(
+                    &ctx.client,
+                    secret_reader.clone(),
+                    move |watch_namespace| {
+                        config_object_watcher(
+                            watch_namespace.get_api::<Secret>(&client),
+                            watcher::Config::default()
+                                .labels("restarter.stackable.tech/ignore != true"),
+                            change_detection,
+                            track_certificate_expiry,
+                        )
+                    },
+                ),
+            )
+            .inspect(move |_| {
+                if let Some(tx) = secret_store_tx.take() {
+                    tx.init(secret_reader.clone());
+                }
+            })
+            .touched_objects()
+            .map_ok(|secret| ConfigChange::Object(ConfigRef::from_obj(ConfigKind::Secret, &secret)))
+        },
+    );
+    let config_watcher = stream::select(config_object_changes, namespace_watcher)
+        .take_until(shutdown_signal.clone())
+        .for_each({
+            let config_changes_tx = config_changes_tx.clone();
+            move |result| {
+                match result {
+                    // Sending only fails if no controller is listening (anymore), which is fine
+                    Ok(config_change) => {
+                        let _ = config_changes_tx.send(config_change);
+                    }
+                    Err(error) => tracing::warn!(
+                        error = &error as &dyn std::error::Error,
+                        "failed to watch ConfigMaps, Secrets or Namespaces"
+                    ),
+                }
+                future::ready(())
+            }
+        });
+
+    let workload_controllers = ctx.workload_kinds.clone().into_iter().map(|workload_kind| {
+        start_workload_controller(
+            ctx.clone(),
+            workload_kind,
+            config_changes_tx.subscribe(),
+            watched_namespaces,
+            shutdown_signal.clone(),
+        )
+    });
+
+    futures::join!(config_watcher, future::join_all(workload_controllers));
+}
+
+async fn start_workload_controller(
+    ctx: Arc<Ctx>,
+    workload_kind: WorkloadKind,
+    config_changes: broadcast::Receiver<ConfigChange>,
+    watched_namespaces: &WatchedNamespaces,
+    shutdown_signal: impl Future<Output = ()>,
+) {
+    let controller_name = workload_kind.controller_name();
+    let api_resource = workload_kind.api_resource.clone();
+    let workload_store = reflector::store::Writer::<DynamicObject>::new(api_resource.clone());
+    let workload_reader = workload_store.as_reader();
+    ctx.metrics.register_store(&api_resource.kind, {
+        let workload_reader = workload_reader.clone();
+        move || workload_reader.state().len()
+    });
+    let workload_index = Arc::new(WorkloadIndex::new(workload_kind.clone()));
+    let event_recorder = Arc::new(Recorder::new(
+        ctx.client.as_kube_client(),
+        Reporter {
+            controller: controller_name.clone(),
+            instance: None,
+        },
+    ));
+
+    let mut workload_watcher_config = watcher::Config::default();
+    if let Some(workload_label_selector) = ctx.options.workload_label_selector() {
+        workload_watcher_config = workload_watcher_config.labels(workload_label_selector.as_str());
+    }
+    let workload_watcher = reflector(
+        workload_store,
+        watched_namespaces.watch(&ctx.client, workload_reader.clone(), {
+            let client = ctx.client.as_kube_client();
+            let api_resource = api_resource.clone();
+            move |watch_namespace| {
+                let workloads = match watch_namespace {
+                    WatchNamespace::All => {
+                        Api::<DynamicObject>::all_with(client.clone(), &api_resource)
+                    }
+                    WatchNamespace::One(namespace) => Api::<DynamicObject>::namespaced_with(
+                        client.clone(),
+                        &namespace,
+                        &api_resource,
+                    ),
+                };
+                watcher(workloads, workload_watcher_config.clone())
+            }
+        }),
+    )
+    .inspect_ok({
+        let ctx = ctx.clone();
+        let workload_index = workload_index.clone();
+        let workload_kind = workload_kind.clone();
+        let api_resource = api_resource.clone();
+        move |event| {
+            workload_index.apply_watcher_event(event);
+            // The rollout budget is only kept in memory, so rollouts that are still in progress
+            // (e.g. after the operator was restarted) need to be counted again
+            if let watcher::Event::InitApply(workload) = event
+                && restarted_rollout_in_progress(workload, &workload_kind)
+                && ctx
+                    .rollout_budget
+                    .resume(&ObjectRef::from_obj_with(workload, api_resource.clone()))
+            {
+                ctx.rollout_budget_changed();
+            }
+            if let watcher::Event::Delete(workload) = event {
+                let workload_ref = ObjectRef::from_obj_with(workload, api_resource.clone());
+                // Pending restarts of deleted workloads will never be applied
+                ctx.pending_restarts.remove(&workload_ref);
+                if ctx.rollout_budget.finish(&workload_ref) {
+                    ctx.rollout_budget_changed();
+                }
+                ctx.metrics.missing_configs_changed(
+                    &workload_ref.to_string(),
+                    workload.metadata.namespace.as_deref().unwrap_or_default(),
+                    [],
+                );
+            }
+        }
+    })
+    .applied_objects();
+
+    let reconcile_requests = stream::select(
+        trigger_config_changes(
+            config_change_stream(config_changes),
+            workload_reader.clone(),
+            workload_index,
+            api_resource.clone(),
+        ),
+        trigger_self(workload_watcher, api_resource),
+    )
+    .inspect_ok({
+        let ctx = ctx.clone();
+        let controller_name = controller_name.clone();
+        move |request| {
+            ctx.metrics
+                .reconcile_queued(&controller_name, request.obj_ref.to_string())
+        }
+    });
+
+    applier(
+        {
+            let event_recorder = event_recorder.clone();
+            let controller_name = controller_name.clone();
+            move |workload, ctx| {
+                let workload_ref =
+                    ObjectRef::from_obj_with(workload.as_ref(), workload_kind.api_resource.clone());
+                let reconciliation = reconcile(
+                    workload,
+                    ctx.clone(),
+                    workload_kind.clone(),
+                    event_recorder.clone(),
+                );
+                let controller_name = controller_name.clone();
+                Box::pin(async move {
+                    ctx.metrics
+                        .observe_reconcile(
+                            &controller_name,
+                            &workload_ref.to_string(),
+                            reconciliation,
+                        )
+                        .await
+                })
+            }
+        },
+        error_policy,
+        ctx,
+        workload_reader,
+        reconcile_requests
+            // This uses the same mechanism as kube's Controller does under the hood, see
+            // https://github.com/kube-rs/kube/blob/8bcdcb52e1e13c1c1ec59f6118fbed575ac10a4b/kube-runtime/src/controller/mod.rs#L1671
+            .take_until(shutdown_signal),
+        Config::default(),
+    )
+    // We can let the reporting happen in the background
+    .for_each_concurrent(
+        16, // concurrency limit
+        |result| {
+            // The event_recorder needs to be shared across all invocations, so that
+            // events are correctly aggregated
+            let event_recorder = event_recorder.clone();
+            let controller_name = controller_name.clone();
+            async move {
+                report_controller_reconciled(&event_recorder, &controller_name, &result).await;
+            }
+        },
+    )
+    .await;
+}
+
+/// A change of the ConfigMaps, Secrets or selected namespaces, as seen by a single workload
+/// controller.
+#[derive(Clone, Debug)]
+enum ConfigChange {
+    /// The given ConfigMap or Secret was changed.
+    Object(ConfigRef),
+
+    /// The given namespace started matching the namespace selector.
+    NamespaceSelected(String),
+
+    /// Some changes were missed, so every ConfigMap and Secret needs to be considered as changed.
+    Lagged,
+}
+
+/// Turns the receiving end of the ConfigMap/Secret change broadcast into a stream.
+fn config_change_stream(
+    config_changes: broadcast::Receiver<ConfigChange>,
+) -> impl Stream<Item = Result<ConfigChange, watcher::Error>> {
+    stream::unfold(config_changes, |mut config_changes| async move {
+        match config_changes.recv().await {
+            Ok(config_change) => Some((Ok(config_change), config_changes)),
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                Some((Ok(ConfigChange::Lagged), config_changes))
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    })
+}
+
+/// Reconciles all workloads referencing a changed ConfigMap or Secret, all workloads in a newly
+/// selected namespace, or all workloads if changes were missed.
+fn trigger_config_changes<S>(
+    stream: S,
+    store: Store<DynamicObject>,
+    workload_index: Arc<WorkloadIndex>,
+    api_resource: ApiResource,
+) -> impl Stream<Item = Result<ReconcileRequest<DynamicObject>, S::Error>>
+where
+    S: TryStream<Ok = ConfigChange>,
+{
+    trigger_with(stream, move |config_change| match config_change {
+        ConfigChange::Object(config_ref) => workload_index.workloads_referencing(&config_ref),
+        ConfigChange::NamespaceSelected(namespace) => store
+            .state()
+            .into_iter()
+            .filter(|obj| obj.metadata.namespace.as_ref() == Some(&namespace))
+            .map(|obj| ObjectRef::from_obj_with(obj.as_ref(), api_resource.clone()))
+            .collect(),
+        ConfigChange::Lagged => store
+            .state()
+            .into_iter()
+            .map(|obj| ObjectRef::from_obj_with(obj.as_ref(), api_resource.clone()))
+            .collect(),
+    })
+}
+
+/// Calculates the restarter annotations that should be set on the Pod template of a workload.
+///
+/// `workload_meta` is the metadata of the workload (such as a StatefulSet) that embeds the
+/// `pod_template`.
+pub async fn get_updated_restarter_annotations(
+    workload_meta: &ObjectMeta,
+    pod_template: &PodTemplateSpec,
+    ctx: &Ctx,
+) -> Result<BTreeMap<String, String>, Error> {
+    let ns = workload_meta
+        .namespace
+        .as_deref()
+        .context(WorkloadHasNoNamespaceSnafu)?;
+
+    let mut annotations = BTreeMap::<String, String>::new();
+    let Some(pod_spec) = &pod_template.spec else {
+        return Ok(annotations);
+    };
+
+    let cms = ctx.cms.get().await.context(ConfigMapsUninitializedSnafu)?;
+    let workload_annotations = workload_meta.annotations.clone().unwrap_or_default();
+    // Invalid patterns are reported by the reconciliation
+    let (ignored_cms, _) = IgnorePatterns::from_annotations(
+        &workload_annotations,
+        IGNORE_CONFIG_MAP_ANNOTATION_PREFIX,
+    );
+    annotations.extend(pod_refs::config_map_refs(pod_spec).into_iter().filter_map(
+        |(cm_name, pod_ref)| {
+            let cm = cms.get(&ObjectRef::<ConfigObject<ConfigMap>>::new(&cm_name).within(ns));
+            let key = format!("{CONFIG_MAP_ANNOTATION_PREFIX}{cm_name}");
+            // Missing optional ConfigMaps are only tracked once they are created
+            if cm.is_none() && pod_ref.optional {
+                return recorded_ignored_version(pod_template, &key).map(|version| (key, version));
+            }
+            let version = if ignored_cms.is_ignored(&cm_name) {
+                None
+            } else if let Some(cm) = cm {
+                cm.restarter_version(&pod_ref.keys)
+            } else {
+                Some(MISSING_VERSION.to_owned())
+            };
+            Some((key, version.unwrap_or_else(|| IGNORED_VERSION.to_owned())))
+        },
+    ));
+
+    let secrets = ctx.secrets.get().await.context(SecretsUninitializedSnafu)?;
+    let (ignored_secrets, _) =
+        IgnorePatterns::from_annotations(&workload_annotations, IGNORE_SECRET_ANNOTATION_PREFIX);
+    annotations.extend(pod_refs::secret_refs(pod_spec).into_iter().filter_map(
+        |(secret_name, pod_ref)| {
+            let secret =
+                secrets.get(&ObjectRef::<ConfigObject<Secret>>::new(&secret_name).within(ns));
+            let key = format!("{SECRET_ANNOTATION_PREFIX}{secret_name}");
+            // Missing optional Secrets are only tracked once they are created
+            if secret.is_none() && pod_ref.optional {
+                return recorded_ignored_version(pod_template, &key).map(|version| (key, version));
+            }
+            let version = if ignored_secrets.is_ignored(&secret_name) {
+                None
+            } else if let Some(secret) = secret {
+                secret.restarter_version(&pod_ref.keys)
+            } else {
+                Some(MISSING_VERSION.to_owned())
+            };
+            Some((key, version.unwrap_or_else(|| IGNORED_VERSION.to_owned())))
+        },
+    ));
+
+    // Pods are evicted by the Pod restarter before the certificates of referenced Secrets expire.
+    // The expiry only changes together with the Secret, so (apart from enabling this or changing
+    // the margin) this doesn't cause additional restarts.
+    if let Some(tls_expiry_margin) = ctx.options.tls_expiry_margin {
+        annotations.extend(
+            pod_refs::secret_refs(pod_spec)
+                .into_keys()
+                .filter_map(|secret_name| {
+                    if ignored_secrets.is_ignored(&secret_name) {
+                        return None;
+                    }
+                    let certificate_expiry = secrets
+                        .get(&ObjectRef::<ConfigObject<Secret>>::new(&secret_name).within(ns))?
+                        .certificate_expiry?;
+                    let expires_at = certificate_expiry
+                        .checked_sub(*tls_expiry_margin)
+                        .unwrap_or(jiff::Timestamp::UNIX_EPOCH);
+                    Some((
+                        format!("{EXPIRES_AT_ANNOTATION_PREFIX}{secret_name}"),
+                        expires_at.to_string(),
+                    ))
+                }),
+        );
+    }
+
+    if let Some(requested_at) = workload_annotations.get(RESTART_REQUESTED_AT_ANNOTATION) {
+        annotations.insert(
+            RESTART_REQUESTED_AT_ANNOTATION.to_owned(),
+            requested_at.clone(),
+        );
+    }
+
+    Ok(annotations)
+}
+
+async fn reconcile(
+    workload: Arc<DynamicObject>,
+    ctx: Arc<Ctx>,
+    workload_kind: WorkloadKind,
+    event_recorder: Arc<Recorder>,
+) -> Result<Action, Error> {
+    tracing::info!("Starting reconcile");
+    let Some(pod_template) = workload_kind
+        .pod_template(&workload)
+        .context(InvalidPodTemplateSnafu)?
+    else {
+        tracing::info!("Workload has no Pod template, taking no action!");
+        return Ok(Action::await_change());
+    };
+    let ns = workload
+        .metadata
+        .namespace
+        .as_deref()
+        .context(WorkloadHasNoNamespaceSnafu)?;
+
+    let workload_ref =
+        ObjectRef::from_obj_with(workload.as_ref(), workload_kind.api_resource.clone());
+    if workload_kind::is_controlled(&workload.metadata) {
+        tracing::debug!("Workload is controlled by another object, taking no action");
+        ctx.pending_restarts.remove(&workload_ref);
+        return Ok(Action::await_change());
+    }
+    if let Some(namespace_selection) = &ctx.namespace_selection
+        && !namespace_selection.contains(ns)
+    {
+        tracing::debug!(
+            namespace_selector = %namespace_selection.label_selector(),
+            "Namespace of the workload is not selected, taking no action"
+        );
+        ctx.pending_restarts.remove(&workload_ref);
+        // Rollouts in progress might have been counted when the operator started
+        if ctx.rollout_budget.finish(&workload_ref) {
+            ctx.rollout_budget_changed();
+        }
+        return Ok(Action::await_change());
+    }
+    for error in ignore_patterns::invalid_patterns(workload.annotations()) {
+        tracing::warn!(
+            error = &error as &dyn std::error::Error,
+            "Ignoring invalid ignore pattern"
+        );
+        publish_event(
+            &event_recorder,
+            &workload,
+            &workload_kind,
+            Event {
+                type_: EventType::Warning,
+                reason: "InvalidIgnorePattern".to_owned(),
+                note: Some(Report::from_error(error).to_string()),
+                action: "Restart".to_owned(),
+                secondary: None,
+            },
+        )
+        .await;
+    }
+    if ctx.options.change_detection == ChangeDetection::ResourceVersion {
+        let key_referenced = key_referenced_configs(&pod_template)
+            .iter()
+            .map(|(kind, name)| format!("{kind}/{name}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        // This is documented rather than reported by an event, as it applies to most workloads
+        if !key_referenced.is_empty() {
+            tracing::debug!(
+                key_referenced,
+                "Workload only consumes individual keys, but any change restarts it"
+            );
+        }
+    }
+    let missing_config_policy = match workload.annotations().get(MISSING_CONFIG_POLICY_ANNOTATION) {
+        Some(policy) => policy
+            .parse::<MissingConfigPolicy>()
+            .context(InvalidMissingConfigPolicySnafu { value: policy })?,
+        None => ctx.options.missing_config_policy,
+    };
+    // Only StatefulSets can restart a subset of their Pods using a partition
+    let canary = if workload_kind.is_stateful_set() {
+        Canary::from_annotations(workload.annotations()).context(InvalidCanarySnafu)?
+    } else {
+        None
+    };
+    let annotations =
+        get_updated_restarter_annotations(&workload.metadata, &pod_template, &ctx).await?;
+    let missing = missing_configs(&annotations);
+    let configs_missing = !missing.is_empty();
+    ctx.metrics.missing_configs_changed(
+        &workload_ref.to_string(),
+        ns,
+        missing.iter().map(|(kind, _)| *kind),
+    );
+    if configs_missing {
+        let missing = missing
+            .iter()
+            .map(|(kind, name)| format!("{kind}/{name}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::warn!(missing, "Workload references missing ConfigMaps or Secrets");
+        publish_event(
+            &event_recorder,
+            &workload,
+            &workload_kind,
+            Event {
+                type_: EventType::Warning,
+                reason: "MissingConfig".to_owned(),
+                note: Some(format!(
+                    "Referenced {missing} not found, handling it according to the missing config \
+                    policy {missing_config_policy}"
+                )),
+                action: "Restart".to_owned(),
+                secondary: None,
+            },
+        )
+        .await;
+    }
+
+    let current_annotations = current_restarter_annotations(&pod_template, &annotations);
+    let annotations = match missing_config_policy {
+        MissingConfigPolicy::Wait => defer_appeared_configs(&current_annotations, annotations),
+        MissingConfigPolicy::Restart | MissingConfigPolicy::Block => annotations,
+    };
+    if current_annotations == annotations {
+        tracing::debug!("Restarter annotations are up to date, skipping patch");
+        // Changes might have been reverted before a pending restart was applied
+        ctx.pending_restarts.remove(&workload_ref);
+        // Rollouts count towards the rollout budget until they are completed
+        let budget_changed = if ctx.rollout_budget.is_in_progress(&workload_ref) {
+            let stateful_set = workload
+                .as_ref()
+                .clone()
+                .try_parse::<StatefulSet>()
+                .context(InvalidStatefulSetSnafu)?;
+            restart_order::rollout_completed(&stateful_set)
+                && ctx.rollout_budget.finish(&workload_ref)
+        } else {
+            ctx.rollout_budget.dequeue(&workload_ref)
+        };
+        if budget_changed {
+            ctx.rollout_budget_changed();
+        }
+        if let Some(canary) = &canary {
+            return advance_canary(
+                &workload,
+                &workload_kind,
+                &ctx,
+                &event_recorder,
+                canary,
+                &annotations,
+            )
+            .await;
+        }
+        return Ok(Action::await_change());
+    }
+    if missing_config_policy == MissingConfigPolicy::Block && configs_missing {
+        // Creating the missing objects triggers another reconciliation
+        tracing::info!("Referenced ConfigMaps or Secrets are missing, blocking restart");
+        ctx.pending_restarts.remove(&workload_ref);
+        return Ok(Action::await_change());
+    }
+
+    let settle_window = match workload.annotations().get(SETTLE_WINDOW_ANNOTATION) {
+        Some(settle_window) => {
+            settle_window
+                .parse::<Duration>()
+                .context(InvalidSettleWindowSnafu {
+                    value: settle_window,
+                })?
+        }
+        None => ctx.options.settle_window,
+    };
+    match ctx
+        .pending_restarts
+        .settle(&workload_ref, &annotations, *settle_window, Instant::now())
+    {
+        SettleState::Settled => {}
+        SettleState::Pending { remaining, changed } => {
+            tracing::info!(
+                settle_window = %settle_window,
+                "Configuration changed, waiting for it to settle before restarting"
+            );
+            if changed {
+                publish_event(
+                    &event_recorder,
+                    &workload,
+                    &workload_kind,
+                    Event {
+                        type_: EventType::Normal,
+                        reason: "RestartPending".to_owned(),
+                        note: Some(format!(
+                            "Referenced ConfigMaps or Secrets changed, restarting once they are \
+                            unchanged for {settle_window}"
+                        )),
+                        action: "Restart".to_owned(),
+                        secondary: None,
+                    },
+                )
+                .await;
+            }
+            return Ok(Action::requeue(remaining));
+        }
+    }
+
+    let version_changes = config_version_changes(&current_annotations, &annotations);
+    let changed_configs = version_changes
+        .iter()
+        .map(|change| change.config.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    // Only StatefulSets report when their rollout is completed
+    if workload_kind.is_stateful_set() {
+        let pending_dependencies =
+            pending_restart_dependencies(&workload, &workload_kind, &ctx).await?;
+        if !pending_dependencies.is_empty() {
+            let pending_dependencies = pending_dependencies.join(", ");
+            tracing::info!(
+                pending_dependencies,
+                "Configuration changed, deferring restart until the StatefulSets restarted before \
+                completed their rollout"
+            );
+            publish_event(
+                &event_recorder,
+                &workload,
+                &workload_kind,
+                Event {
+                    type_: EventType::Normal,
+                    reason: "RestartDeferred".to_owned(),
+                    note: Some(format!(
+                        "Restart due to changed {changed_configs} is deferred until the \
+                        StatefulSets {pending_dependencies} completed their rollout"
+                    )),
+                    action: "Restart".to_owned(),
+                    secondary: None,
+                },
+            )
+            .await;
+            return Ok(Action::requeue(RESTART_ORDER_RECHECK_INTERVAL));
+        }
+    }
+
+    if let Some(maintenance_windows) = MaintenanceWindows::from_annotations(workload.annotations())
+        .context(InvalidMaintenanceWindowSnafu)?
+    {
+        let now = jiff::Timestamp::now();
+        if !maintenance_windows.is_open(now) {
+            let next_opening = maintenance_windows.next_opening(now);
+            tracing::info!(
+                %maintenance_windows,
+                ?next_opening,
+                "Configuration changed outside of the maintenance window, deferring restart"
+            );
+            publish_event(
+                &event_recorder,
+                &workload,
+                &workload_kind,
+                Event {
+                    type_: EventType::Normal,
+                    reason: "RestartDeferred".to_owned(),
+                    note: Some(match next_opening {
+                        Some(next_opening) => format!(
+                            "Restart due to changed {changed_configs} is deferred until the \
+                            maintenance window \"{maintenance_windows}\" opens at {next_opening}"
+                        ),
+                        None => format!(
+                            "Restart due to changed {changed_configs} is deferred, but the \
+                            maintenance window \"{maintenance_windows}\" never opens"
+                        ),
+                    }),
+                    action: "Restart".to_owned(),
+                    secondary: None,
+                },
+            )
+            .await;
+            return Ok(match next_opening {
+                Some(next_opening) => Action::requeue(duration_until(now, next_opening)),
+                None => Action::await_change(),
+            });
+        }
+    }
+
+    let changes = version_changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if restart_controller::is_dry_run(ctx.options.dry_run, workload.annotations()) {
+        tracing::info!(changes, "Dry run, not restarting workload");
+        publish_event(
+            &event_recorder,
+            &workload,
+            &workload_kind,
+            Event {
+                type_: EventType::Normal,
+                reason: "WouldRestart".to_owned(),
+                note: Some(format!("Dry run: would restart due to changed {changes}")),
+                action: "Restart".to_owned(),
+                secondary: None,
+            },
+        )
+        .await;
+        ctx.pending_restarts.remove(&workload_ref);
+        return Ok(Action::await_change());
+    }
+
+    // The rollout budget only applies to StatefulSets, as only they report when their rollout is
+    // completed
+    if workload_kind.is_stateful_set() {
+        let admission = ctx.rollout_budget.admit(&workload_ref, Instant::now());
+        ctx.rollout_budget_changed();
+        if let Admission::Queued {
+            position,
+            queue_length,
+            newly_queued,
+        } = admission
+        {
+            tracing::info!(
+                position,
+                queue_length,
+                "Configuration changed, queueing restart until other rollouts completed"
+            );
+            if newly_queued {
+                publish_event(
+                    &event_recorder,
+                    &workload,
+                    &workload_kind,
+                    Event {
+                        type_: EventType::Normal,
+                        reason: "RestartQueued".to_owned(),
+                        note: Some(format!(
+                            "Restart due to changed {changed_configs} is queued at position \
+                            {position} of {queue_length}, as the maximum number of concurrent \
+                            rollouts is reached"
+                        )),
+                        action: "Restart".to_owned(),
+                        secondary: None,
+                    },
+                )
+                .await;
+            }
+            return Ok(Action::requeue(ROLLOUT_BUDGET_RECHECK_INTERVAL));
+        }
+    }
+
+    // A canary rollout starts by only restarting the Pods with the highest ordinals
+    let canary_partition = match &canary {
+        Some(canary) => {
+            let rollout =
+                StatefulSetRollout::from_workload(&workload).context(InvalidCanarySnafu)?;
+            (!rollout.on_delete).then(|| canary.initial_partition(&rollout))
+        }
+        None => None,
+    };
+    apply_restarter_annotations(
+        &workload,
+        &workload_kind,
+        &ctx,
+        &annotations,
+        canary_partition,
+    )
+    .await?;
+    ctx.pending_restarts.remove(&workload_ref);
+
+    tracing::info!(
+        changes,
+        ?canary_partition,
+        "Restarted workload due to configuration changes"
+    );
+    let causes = version_changes
+        .iter()
+        .map(|change| change.cause)
+        .collect::<HashSet<_>>();
+    for cause in causes {
+        ctx.metrics
+            .workload_restarted(ns, &workload_kind.api_resource.kind, cause);
+    }
+    publish_event(
+        &event_recorder,
+        &workload,
+        &workload_kind,
+        Event {
+            type_: EventType::Normal,
+            reason: "Restarted".to_owned(),
+            note: Some(match canary_partition {
+                Some(partition) => format!(
+                    "Restarted due to changed {changes}, starting a canary rollout with the Pods \
+                    from ordinal {partition} on"
+                ),
+                None => format!("Restarted due to changed {changes}"),
+            }),
+            action: "Restart".to_owned(),
+            secondary: None,
+        },
+    )
+    .await;
+    Ok(match canary_partition {
+        Some(_) => Action::requeue(CANARY_RECHECK_INTERVAL),
+        None => Action::await_change(),
+    })
+}
+
+/// Whether the workload is a StatefulSet restarted by the restarter (i.e. its Pod template carries
+/// restarter annotations) that didn't complete its rollout yet.
+fn restarted_rollout_in_progress(workload: &DynamicObject, workload_kind: &WorkloadKind) -> bool {
+    if !workload_kind.is_stateful_set() || workload_kind::is_controlled(&workload.metadata) {
+        return false;
+    }
+    let Ok(stateful_set) = workload.clone().try_parse::<StatefulSet>() else {
+        return false;
+    };
+    let restarted = stateful_set
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.template.metadata.as_ref())
+        .and_then(|metadata| metadata.annotations.as_ref())
+        .is_some_and(|annotations| {
+            annotations
+                .keys()
+                .any(|key| parse_restarter_annotation_key(key).is_some())
+        });
+    restarted && !restart_order::rollout_completed(&stateful_set)
+}
+
+/// Applies the restarter annotations to the Pod template of the workload.
+///
+/// The `partition` of the rolling update is only applied for canary rollouts of StatefulSets, it is
+/// dropped once omitted. It is recorded in the [`CANARY_PARTITION_ANNOTATION`] of the StatefulSet
+/// in the same patch, so that only rollouts started by the restarter are continued.
+async fn apply_restarter_annotations(
+    workload: &DynamicObject,
+    workload_kind: &WorkloadKind,
+    ctx: &Ctx,
+    annotations: &BTreeMap<String, String>,
+    partition: Option<i32>,
+) -> Result<(), Error> {
+    let workloads = Api::<DynamicObject>::namespaced_with(
+        ctx.client.as_kube_client(),
+        workload
+            .metadata
+            .namespace
+            .as_deref()
+            .context(WorkloadHasNoNamespaceSnafu)?,
+        &workload_kind.api_resource,
+    );
+    let mut patch = workload_kind.nest_in_pod_template(json!({
+        "metadata": {
+            "annotations": annotations,
+        },
+    }));
+    patch["apiVersion"] = json!(workload_kind.api_resource.api_version);
+    patch["kind"] = json!(workload_kind.api_resource.kind);
+    patch["metadata"] = json!({
+        "name": workload.metadata.name,
+        "namespace": workload.metadata.namespace,
+        "uid": workload.metadata.uid,
+    });
+    if let Some(partition) = partition {
+        patch["metadata"]["annotations"] = json!({
+            CANARY_PARTITION_ANNOTATION: partition.to_string(),
+        });
+        patch["spec"]["updateStrategy"] = json!({
+            "rollingUpdate": {
+                "partition": partition,
+            },
+        });
+    }
+    workloads
+        .patch(
+            &workload.name_unchecked(),
+            &PatchParams {
+                force: true,
+                field_manager: Some(workload_kind.field_manager()),
+                ..PatchParams::default()
+            },
+            // Can't use typed API, see https://github.com/Arnavion/k8s-openapi/issues/112
+            &Patch::Apply(patch),
+        )
+        .await
+        .context(PatchFailedSnafu {
+            obj_ref: ObjectRef::from_obj_with(workload, workload_kind.api_resource.clone()).erase(),
+        })?;
+    Ok(())
+}
+
+/// Continues the canary rollout of a StatefulSet once the Pods restarted so far are ready for the
+/// soak time, if a rollout is in progress.
+async fn advance_canary(
+    workload: &DynamicObject,
+    workload_kind: &WorkloadKind,
+    ctx: &Ctx,
+    event_recorder: &Recorder,
+    canary: &Canary,
+    annotations: &BTreeMap<String, String>,
+) -> Result<Action, Error> {
+    let rollout = StatefulSetRollout::from_workload(workload).context(InvalidCanarySnafu)?;
+    if !rollout.canary_in_progress() {
+        return Ok(Action::await_change());
+    }
+    let ns = workload
+        .metadata
+        .namespace
+        .as_deref()
+        .context(WorkloadHasNoNamespaceSnafu)?;
+    let pods = rollout
+        .list_pods(&ctx.client, ns)
+        .await
+        .context(CanaryProgressSnafu)?;
+
+    match canary.progress(&rollout, &pods, jiff::Timestamp::now()) {
+        CanaryProgress::Updating => {
+            tracing::debug!(
+                partition = rollout.partition,
+                "Canary Pods are still being restarted"
+            );
+            Ok(Action::requeue(CANARY_RECHECK_INTERVAL))
+        }
+        CanaryProgress::Soaking { remaining } => {
+            tracing::info!(
+                partition = rollout.partition,
+                ?remaining,
+                "Canary Pods are ready, waiting for the soak time to pass"
+            );
+            Ok(Action::requeue(remaining))
+        }
+        CanaryProgress::Paused { not_ready_pods } => {
+            let not_ready_pods = not_ready_pods.join(", ");
+            tracing::warn!(
+                partition = rollout.partition,
+                not_ready_pods,
+                "Canary Pods are not ready, pausing the canary rollout"
+            );
+            publish_event(
+                event_recorder,
+                workload,
+                workload_kind,
+                Event {
+                    type_: EventType::Warning,
+                    reason: "CanaryPaused".to_owned(),
+                    note: Some(format!(
+                        "Canary rollout is paused, as the restarted Pods {not_ready_pods} are not \
+                        ready within {ready_timeout}",
+                        ready_timeout = canary.ready_timeout
+                    )),
+                    action: "Restart".to_owned(),
+                    secondary: None,
+                },
+            )
+            .await;
+            Ok(Action::requeue(CANARY_RECHECK_INTERVAL))
+        }
+        CanaryProgress::Step { partition } => {
+            apply_restarter_annotations(workload, workload_kind, ctx, annotations, Some(partition))
+                .await?;
+            tracing::info!(partition, "Continued canary rollout");
+            publish_event(
+                event_recorder,
+                workload,
+                workload_kind,
+                Event {
+                    type_: EventType::Normal,
+                    reason: "CanaryProgressed".to_owned(),
+                    note: Some(format!(
+                        "Restarted Pods were ready for {soak_time}, continuing the canary rollout \
+                        with the Pods from ordinal {partition} on",
+                        soak_time = canary.soak_time
+                    )),
+                    action: "Restart".to_owned(),
+                    secondary: None,
+                },
+            )
+            .await;
+            Ok(Action::requeue(CANARY_RECHECK_INTERVAL))
+        }
+    }
+}
+
+/// Returns the StatefulSets that the StatefulSet is restarted after (see
+/// [`restart_order::RESTART_AFTER_ANNOTATION`]) and that didn't complete their rollout yet.
+///
+/// StatefulSets with a pending restart of their own are also not completed, as their rollout only
+/// starts once it is applied.
+async fn pending_restart_dependencies(
+    workload: &DynamicObject,
+    workload_kind: &WorkloadKind,
+    ctx: &Ctx,
+) -> Result<Vec<String>, Error> {
+    let restart_after = restart_order::restart_after(workload.annotations());
+    if restart_after.is_empty() {
+        return Ok(Vec::new());
+    }
+    let stateful_sets = Api::<DynamicObject>::namespaced_with(
+        ctx.client.as_kube_client(),
+        workload
+            .metadata
+            .namespace
+            .as_deref()
+            .context(WorkloadHasNoNamespaceSnafu)?,
+        &workload_kind.api_resource,
+    );
+
+    // The whole restart order is needed to detect cycles, not only the direct dependencies
+    let name = workload.name_any();
+    let mut restart_order = BTreeMap::from([(name.clone(), restart_after.clone())]);
+    let mut dependencies = BTreeMap::new();
+    let mut unvisited = restart_after.iter().cloned().collect::<VecDeque<_>>();
+    while let Some(dependency_name) = unvisited.pop_front() {
+        if restart_order.contains_key(&dependency_name) {
+            continue;
+        }
+        let dependency =
+            stateful_sets
+                .get_opt(&dependency_name)
+                .await
+                .context(GetRestartDependencySnafu {
+                    name: &dependency_name,
+                })?;
+        let dependency_restart_after = dependency
+            .as_ref()
+            .map(|dependency| restart_order::restart_after(dependency.annotations()))
+            .unwrap_or_default();
+        unvisited.extend(dependency_restart_after.iter().cloned());
+        restart_order.insert(dependency_name.clone(), dependency_restart_after);
+        if let Some(dependency) = dependency {
+            dependencies.insert(dependency_name, dependency);
+        }
+    }
+    if let Some(cycle) = restart_order::find_cycle(&name, &restart_order) {
+        return RestartOrderCycleSnafu {
+            cycle: cycle.join(" -> "),
+        }
+        .fail();
+    }
+
+    let mut pending_dependencies = Vec::new();
+    for dependency_name in restart_after {
+        let Some(dependency) = dependencies.get(&dependency_name) else {
+            tracing::warn!(
+                stateful_set = dependency_name,
+                "StatefulSet to restart after does not exist, ignoring it"
+            );
+            continue;
+        };
+        let rollout_completed = restart_order::rollout_completed(
+            &dependency.clone().try_parse::<StatefulSet>().context(
+                InvalidRestartDependencySnafu {
+                    name: &dependency_name,
+                },
+            )?,
+        );
+        if !rollout_completed || restart_due(dependency, workload_kind, ctx).await? {
+            pending_dependencies.push(dependency_name);
+        }
+    }
+    Ok(pending_dependencies)
+}
+
+/// Whether the restarter annotations of the workload are outdated and will be applied eventually,
+/// e.g. once the settle window passed.
+///
+/// This is never the case for workloads that are not restarted by this operator.
+async fn restart_due(
+    workload: &DynamicObject,
+    workload_kind: &WorkloadKind,
+    ctx: &Ctx,
+) -> Result<bool, Error> {
+    let not_selected = ctx
+        .options
+        .workload_label_selector()
+        .is_some_and(|selector| !selector.matches(workload.labels()));
+    if not_selected || restart_controller::is_dry_run(ctx.options.dry_run, workload.annotations()) {
+        return Ok(false);
+    }
+    let Some(pod_template) = workload_kind
+        .pod_template(workload)
+        .context(InvalidPodTemplateSnafu)?
+    else {
+        return Ok(false);
+    };
+    // Invalid policies are reported by the reconciliation of the workload itself
+    let missing_config_policy = workload
+        .annotations()
+        .get(MISSING_CONFIG_POLICY_ANNOTATION)
+        .and_then(|policy| policy.parse().ok())
+        .unwrap_or(ctx.options.missing_config_policy);
+
+    let annotations =
+        get_updated_restarter_annotations(&workload.metadata, &pod_template, ctx).await?;
+    let current_annotations = current_restarter_annotations(&pod_template, &annotations);
+    let annotations = match missing_config_policy {
+        MissingConfigPolicy::Wait => defer_appeared_configs(&current_annotations, annotations),
+        MissingConfigPolicy::Restart | MissingConfigPolicy::Block => annotations,
+    };
+    let blocked = missing_config_policy == MissingConfigPolicy::Block
+        && !missing_configs(&annotations).is_empty();
+    Ok(current_annotations != annotations && !blocked)
+}
+
+/// Publishes an event about the workload, failures are only logged.
+async fn publish_event(
+    event_recorder: &Recorder,
+    workload: &DynamicObject,
+    workload_kind: &WorkloadKind,
+    mut event: Event,
+) {
+    if let Some(note) = &mut event.note
+        && note.len() > MAX_EVENT_NOTE_LENGTH
+    {
+        let mut end = MAX_EVENT_NOTE_LENGTH - "...".len();
+        while !note.is_char_boundary(end) {
+            end -= 1;
+        }
+        note.truncate(end);
+        note.push_str("...");
+    }
+
+    let object_ref = workload.object_ref(&workload_kind.api_resource);
+    if let Err(error) = event_recorder.publish(&event, &object_ref).await {
+        tracing::warn!(
+            error = &error as &dyn std::error::Error,
+            reason = %event.reason,
+            "failed to publish event"
+        );
+    }
+}
+
+/// Returns the restarter annotations that are currently set on the Pod template.
+///
+/// Expiry annotations are only considered if they are part of the `updated_annotations`, as they
+/// might also be set by others (e.g. by users). Outdated ones are removed with the next restart.
+/// The same applies to handled restart requests, so that removing the request from the workload
+/// doesn't restart it.
+///
+/// Previous versions recorded missing ConfigMaps and Secrets as ignored, which is considered to be
+/// the same as recording them as missing, so that upgrading the operator doesn't restart the
+/// workload.
+fn current_restarter_annotations(
+    pod_template: &PodTemplateSpec,
+    updated_annotations: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    pod_template
+        .metadata
+        .iter()
+        .flat_map(|metadata| &metadata.annotations)
+        .flatten()
+        .filter(|(key, _)| {
+            key.starts_with(CONFIG_MAP_ANNOTATION_PREFIX)
+                || key.starts_with(SECRET_ANNOTATION_PREFIX)
+                || ((key.starts_with(EXPIRES_AT_ANNOTATION_PREFIX)
+                    || *key == RESTART_REQUESTED_AT_ANNOTATION)
+                    && updated_annotations.contains_key(*key))
+        })
+        .map(|(key, value)| {
+            let value = match updated_annotations.get(key) {
+                Some(updated_value)
+                    if value == IGNORED_VERSION && updated_value == MISSING_VERSION =>
+                {
+                    updated_value
+                }
+                _ => value,
+            };
+            (key.clone(), value.clone())
+        })
+        .collect()
+}
+
+/// Returns the [`IGNORED_VERSION`] if it is recorded in the restarter annotation `key` of the Pod
+/// template.
+///
+/// Previous versions recorded missing optional ConfigMaps and Secrets as ignored instead of not
+/// tracking them, which is kept so that upgrading the operator doesn't restart the workload.
+fn recorded_ignored_version(pod_template: &PodTemplateSpec, key: &str) -> Option<String> {
+    pod_template
+        .metadata
+        .as_ref()?
+        .annotations
+        .as_ref()?
+        .get(key)
+        .filter(|version| *version == IGNORED_VERSION)
+        .cloned()
+}
+
+/// Returns the kind and name of the ConfigMap or Secret that a restarter annotation refers to.
+fn parse_restarter_annotation_key(key: &str) -> Option<(ConfigKind, &str)> {
+    if let Some(name) = key.strip_prefix(CONFIG_MAP_ANNOTATION_PREFIX) {
+        Some((ConfigKind::ConfigMap, name))
+    } else {
+        Some((
+            ConfigKind::Secret,
+            key.strip_prefix(SECRET_ANNOTATION_PREFIX)?,
+        ))
+    }
+}
+
+/// Returns the ConfigMaps and Secrets of which the Pod template only consumes individual keys.
+///
+/// Only [`ChangeDetection::ContentHash`] can restrict the changes that are considered to these
+/// keys.
+fn key_referenced_configs(pod_template: &PodTemplateSpec) -> Vec<(ConfigKind, String)> {
+    let Some(pod_spec) = &pod_template.spec else {
+        return Vec::new();
+    };
+    let only_keys = |refs: BTreeMap<String, PodRef>, kind| {
+        refs.into_iter()
+            .filter(|(_, pod_ref)| matches!(pod_ref.keys, ReferencedKeys::Only(_)))
+            .map(move |(name, _)| (kind, name))
+    };
+    only_keys(pod_refs::config_map_refs(pod_spec), ConfigKind::ConfigMap)
+        .chain(only_keys(
+            pod_refs::secret_refs(pod_spec),
+            ConfigKind::Secret,
+        ))
+        .collect()
+}
+
+/// Returns the referenced ConfigMaps and Secrets that are recorded as missing.
+fn missing_configs(annotations: &BTreeMap<String, String>) -> Vec<(ConfigKind, &str)> {
+    annotations
+        .iter()
+        .filter(|(_, version)| *version == MISSING_VERSION)
+        .filter_map(|(key, _)| parse_restarter_annotation_key(key))
+        .collect()
+}
+
+/// Keeps ConfigMaps and Secrets that appeared since the last restart recorded as missing, unless
+/// the workload is restarted anyway (see [`MissingConfigPolicy::Wait`]).
+fn defer_appeared_configs(
+    current_annotations: &BTreeMap<String, String>,
+    updated_annotations: BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    let deferred_annotations = updated_annotations
+        .iter()
+        .map(|(key, version)| match current_annotations.get(key) {
+            Some(current_version) if current_version == MISSING_VERSION => {
+                (key.clone(), current_version.clone())
+            }
+            _ => (key.clone(), version.clone()),
+        })
+        .collect::<BTreeMap<_, _>>();
+    if &deferred_annotations == current_annotations {
+        deferred_annotations
+    } else {
+        updated_annotations
+    }
+}
+
+/// A changed version of a ConfigMap or Secret (or a changed restart request), as recorded in the
+/// restarter annotations.
+#[derive(Debug, PartialEq, Eq)]
+struct ConfigVersionChange<'a> {
+    cause: RestartCause,
+
+    /// The changed object, e.g. `ConfigMap/foo`
+    config: String,
+    from: Option<&'a str>,
+    to: Option<&'a str>,
+}
+
+impl Display for ConfigVersionChange<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let config = &self.config;
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => write!(f, "{config} ({from} -> {to})"),
+            (None, Some(to)) if self.cause == RestartCause::Requested => {
+                write!(f, "{config} ({to})")
+            }
+            (None, Some(to)) => write!(f, "{config} (newly referenced: {to})"),
+            (Some(from), None) => write!(f, "{config} (no longer referenced, was {from})"),
+            (None, None) => write!(f, "{config}"),
+        }
+    }
+}
+
+/// Returns the ConfigMaps and Secrets whose restarter annotations differ.
+fn config_version_changes<'a>(
+    current_annotations: &'a BTreeMap<String, String>,
+    updated_annotations: &'a BTreeMap<String, String>,
+) -> Vec<ConfigVersionChange<'a>> {
+    current_annotations
+        .keys()
+        .chain(updated_annotations.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|key| {
+            let from = current_annotations.get(key).map(String::as_str);
+            let to = updated_annotations.get(key).map(String::as_str);
+            if from == to {
+                return None;
+            }
+            let (cause, config) = if key == RESTART_REQUESTED_AT_ANNOTATION {
+                (RestartCause::Requested, "restart request".to_owned())
+            } else if let Some(name) = key.strip_prefix(EXPIRES_AT_ANNOTATION_PREFIX) {
+                (
+                    RestartCause::Secret,
+                    format!("certificate expiry of Secret/{name}"),
+                )
+            } else {
+                let (kind, name) = parse_restarter_annotation_key(key)?;
+                (kind.into(), format!("{kind}/{name}"))
+            };
+            Some(ConfigVersionChange {
+                cause,
+                config,
+                from,
+                to,
+            })
+        })
+        .collect()
+}
+
+fn error_policy(_obj: Arc<DynamicObject>, error: &Error, _ctx: Arc<Ctx>) -> Action {
+    match error {
+        // root object is invalid, will be requeued when modified anyway
+        Error::InvalidPodTemplate { .. }
+        | Error::InvalidSettleWindow { .. }
+        | Error::InvalidMaintenanceWindow { .. }
+        | Error::InvalidMissingConfigPolicy { .. }
+        | Error::InvalidCanary { .. } => Action::await_change(),
+
+        Error::RestartOrderCycle { .. } => Action::requeue(RESTART_ORDER_CYCLE_RECHECK_INTERVAL),
+
+        _ => Action::requeue(std::time::Duration::from_secs(5)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotations(annotations: &[(&str, &str)]) -> BTreeMap<String, String> {
+        annotations
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn combine_workload_label_selectors() {
+        let options = |all_workloads, workload_selector: Option<&str>| Options {
+            settle_window: Duration::from_secs(0),
+            dry_run: false,
+            missing_config_policy: MissingConfigPolicy::default(),
+            all_workloads,
+            workload_selector: workload_selector.map(|selector| selector.parse().unwrap()),
+            namespace_selector: None,
+            tls_expiry_margin: None,
+            rollout_limits: RolloutLimits::default(),
+            change_detection: ChangeDetection::default(),
+        };
+        let selector = |options: Options| {
+            options
+                .workload_label_selector()
+                .map(|selector| selector.to_string())
+        };
+
+        assert_eq!(
+            selector(options(false, None)).as_deref(),
+            Some("restarter.stackable.tech/enabled=true")
+        );
+        assert_eq!(
+            selector(options(false, Some("tenant=a"))).as_deref(),
+            Some("restarter.stackable.tech/enabled=true,tenant=a")
+        );
+        assert_eq!(
+            selector(options(true, Some("tenant=a"))).as_deref(),
+            Some("tenant=a")
+        );
+        assert_eq!(selector(options(true, None)), None);
+    }
+
+    #[test]
+    fn find_key_referenced_configs() {
+        let pod_template = serde_json::from_value::<PodTemplateSpec>(serde_json::json!({
+            "spec": {
+                "containers": [{
+                    "name": "main",
+                    "env": [
+                        {"name": "A", "valueFrom": {"configMapKeyRef": {"name": "env", "key": "a"}}},
+                        {"name": "B", "valueFrom": {"secretKeyRef": {"name": "creds", "key": "b"}}},
+                    ],
+                    "envFrom": [{"configMapRef": {"name": "all"}}],
+                }],
+                "volumes": [
+                    {"name": "config", "configMap": {"name": "env"}},
+                    {"name": "tls", "secret": {"secretName": "tls", "items": [{"key": "ca.crt", "path": "ca.crt"}]}},
+                ],
+            },
+        }))
+        .unwrap();
+        // `env` is consumed as a whole by the volume
+        assert_eq!(
+            key_referenced_configs(&pod_template),
+            vec![
+                (ConfigKind::Secret, "creds".to_owned()),
+                (ConfigKind::Secret, "tls".to_owned()),
+            ]
+        );
+        assert_eq!(key_referenced_configs(&PodTemplateSpec::default()), vec![]);
+    }
+
+    #[test]
+    fn defer_appeared_configs_until_next_restart() {
+        let current = annotations(&[
+            ("configmap.restarter.stackable.tech/a", "missing"),
+            ("secret.restarter.stackable.tech/b", "uid/1"),
+        ]);
+
+        // A ConfigMap appearing on its own doesn't cause a restart
+        let appeared = annotations(&[
+            ("configmap.restarter.stackable.tech/a", "uid/1"),
+            ("secret.restarter.stackable.tech/b", "uid/1"),
+        ]);
+        assert_eq!(defer_appeared_configs(&current, appeared), current);
+
+        // ... but is picked up by restarts due to other changes
+        let changed = annotations(&[
+            ("configmap.restarter.stackable.tech/a", "uid/1"),
+            ("secret.restarter.stackable.tech/b", "uid/2"),
+        ]);
+        assert_eq!(defer_appeared_configs(&current, changed.clone()), changed);
+    }
+
+    #[test]
+    fn describe_config_version_changes() {
+        let current = annotations(&[
+            ("configmap.restarter.stackable.tech/unchanged", "1"),
+            ("configmap.restarter.stackable.tech/changed", "1"),
+            ("secret.restarter.stackable.tech/removed", "1"),
+        ]);
+        let updated = annotations(&[
+            ("configmap.restarter.stackable.tech/unchanged", "1"),
+            ("configmap.restarter.stackable.tech/changed", "2"),
+            ("secret.restarter.stackable.tech/added", "1"),
+        ]);
+        assert_eq!(
+            config_version_changes(&current, &updated)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "ConfigMap/changed (1 -> 2)",
+                "Secret/added (newly referenced: 1)",
+                "Secret/removed (no longer referenced, was 1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn only_consider_derived_expiry_annotations() {
+        let pod_template = PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(annotations(&[
+                    ("secret.restarter.stackable.tech/tls", "uid/1"),
+                    (
+                        "restarter.stackable.tech/expires-at.tls",
+                        "2026-01-01T00:00:00Z",
+                    ),
+                    (
+                        "restarter.stackable.tech/expires-at.user-defined",
+                        "2026-01-01T00:00:00Z",
+                    ),
+                    ("unrelated", "foo"),
+                ])),
+                ..ObjectMeta::default()
+            }),
+            spec: None,
+        };
+        let updated = annotations(&[
+            ("secret.restarter.stackable.tech/tls", "uid/2"),
+            (
+                "restarter.stackable.tech/expires-at.tls",
+                "2027-01-01T00:00:00Z",
+            ),
+        ]);
+        let current = current_restarter_annotations(&pod_template, &updated);
+        assert_eq!(
+            current,
+            annotations(&[
+                ("secret.restarter.stackable.tech/tls", "uid/1"),
+                (
+                    "restarter.stackable.tech/expires-at.tls",
+                    "2026-01-01T00:00:00Z",
+                ),
+            ])
+        );
+        assert_eq!(
+            config_version_changes(&current, &updated)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "certificate expiry of Secret/tls (2026-01-01T00:00:00Z -> 2027-01-01T00:00:00Z)",
+                "Secret/tls (uid/1 -> uid/2)",
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_ignored_missing_optional_configs() {
+        let pod_template = PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(annotations(&[
+                    (
+                        "configmap.restarter.stackable.tech/ignored",
+                        "changes-ignored",
+                    ),
+                    ("secret.restarter.stackable.tech/deleted", "uid/1"),
+                ])),
+                ..ObjectMeta::default()
+            }),
+            spec: None,
+        };
+        assert_eq!(
+            recorded_ignored_version(&pod_template, "configmap.restarter.stackable.tech/ignored"),
+            Some("changes-ignored".to_owned())
+        );
+        // Deleted and newly referenced optional objects are not tracked
+        assert_eq!(
+            recorded_ignored_version(&pod_template, "secret.restarter.stackable.tech/deleted"),
+            None
+        );
+        assert_eq!(
+            recorded_ignored_version(&pod_template, "configmap.restarter.stackable.tech/new"),
+            None
+        );
+
+        // The workload is not rolled, as the annotation recorded by previous versions is kept
+        let updated = annotations(&[
+            (
+                "configmap.restarter.stackable.tech/ignored",
+                "changes-ignored",
+            ),
+            ("secret.restarter.stackable.tech/deleted", "uid/1"),
+        ]);
+        assert_eq!(
+            current_restarter_annotations(&pod_template, &updated),
+            updated
+        );
+    }
+
+    #[test]
+    fn treat_ignored_missing_configs_as_missing() {
+        let pod_template = PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(annotations(&[
+                    (
+                        "configmap.restarter.stackable.tech/missing",
+                        "changes-ignored",
+                    ),
+                    ("secret.restarter.stackable.tech/ignored", "changes-ignored"),
+                    ("secret.restarter.stackable.tech/created", "changes-ignored"),
+                ])),
+                ..ObjectMeta::default()
+            }),
+            spec: None,
+        };
+        let updated = annotations(&[
+            ("configmap.restarter.stackable.tech/missing", "missing"),
+            ("secret.restarter.stackable.tech/ignored", "changes-ignored"),
+            ("secret.restarter.stackable.tech/created", "uid/1"),
+        ]);
+        let current = current_restarter_annotations(&pod_template, &updated);
+        assert_eq!(
+            current,
+            annotations(&[
+                ("configmap.restarter.stackable.tech/missing", "missing"),
+                ("secret.restarter.stackable.tech/ignored", "changes-ignored"),
+                ("secret.restarter.stackable.tech/created", "changes-ignored"),
+            ])
+        );
+        // Only objects that appeared are considered to be changed
+        assert_eq!(
+            config_version_changes(&current, &updated)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["Secret/created (changes-ignored -> uid/1)"]
+        );
+    }
+
+    #[test]
+    fn handle_restart_requests() {
+        let pod_template = |template_annotations: &[(&str, &str)]| PodTemplateSpec {
+            metadata: Some(ObjectMeta {
+                annotations: Some(annotations(template_annotations)),
+                ..ObjectMeta::default()
+            }),
+            spec: None,
+        };
+        let request = |requested_at| {
+            annotations(&[
+                ("configmap.restarter.stackable.tech/config", "uid/1"),
+                (
+                    "restarter.stackable.tech/restart-requested-at",
+                    requested_at,
+                ),
+            ])
+        };
+        let changes = |current: &BTreeMap<String, String>, updated: &BTreeMap<String, String>| {
+            config_version_changes(current, updated)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // A new request restarts the workload once
+        let handled = pod_template(&[("configmap.restarter.stackable.tech/config", "uid/1")]);
+        let current = current_restarter_annotations(&handled, &request("token-1"));
+        assert_eq!(
+            changes(&current, &request("token-1")),
+            ["restart request (token-1)"]
+        );
+
+        let handled = pod_template(&[
+            ("configmap.restarter.stackable.tech/config", "uid/1"),
+            ("restarter.stackable.tech/restart-requested-at", "token-1"),
+        ]);
+        let current = current_restarter_annotations(&handled, &request("token-1"));
+        assert_eq!(current, request("token-1"));
+        assert_eq!(
+            changes(&current, &request("token-2")),
+            ["restart request (token-1 -> token-2)"]
+        );
+
+        // Removing the request doesn't restart the workload
+        let updated = annotations(&[("configmap.restarter.stackable.tech/config", "uid/1")]);
+        let current = current_restarter_annotations(&handled, &updated);
+        assert_eq!(current, updated);
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+};
+
+use prometheus_client::encoding::EncodeLabelValue;
+use stackable_operator::kube::{
+    ResourceExt,
+    core::DynamicObject,
+    runtime::{reflector::ObjectRef, watcher},
+};
+use strum::Display;
+
+use crate::restart_controller::{pod_refs, workload_kind::WorkloadKind};
+
+/// The kinds of objects that can be referenced by the Pod template of a workload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display, EncodeLabelValue)]
+pub enum ConfigKind {
+    ConfigMap,
+    Secret,
+}
+
+/// A reference to a ConfigMap or Secret.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConfigRef {
+    pub kind: ConfigKind,
+    pub namespace: String,
+    pub name: String,
+}
+
+impl ConfigRef {
+    pub fn from_obj(kind: ConfigKind, obj: &impl ResourceExt) -> Self {
+        Self {
+            kind,
+            namespace: obj.namespace().unwrap_or_default(),
+            name: obj.name_any(),
+        }
+    }
+}
+
+/// Reverse index from ConfigMaps and Secrets to the workloads (of a single [`WorkloadKind`])
+/// referencing them, so that a change only needs to reconcile the affected workloads.
+///
+/// It is kept up to date by feeding it the same watcher events as the reflector of the workloads.
+#[derive(Debug)]
+pub struct WorkloadIndex {
+    workload_kind: WorkloadKind,
+    state: Mutex<IndexState>,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    workloads_by_config: HashMap<ConfigRef, HashSet<ObjectRef<DynamicObject>>>,
+    configs_by_workload: HashMap<ObjectRef<DynamicObject>, Vec<ConfigRef>>,
+
+    /// The workloads seen during the current (re-)list, [`None`] if no list is in progress
+    relisted_workloads: Option<HashSet<ObjectRef<DynamicObject>>>,
+}
+
+impl WorkloadIndex {
+    pub fn new(workload_kind: WorkloadKind) -> Self {
+        Self {
+            workload_kind,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Updates the index according to a watcher event of the workloads.
+    pub fn apply_watcher_event(&self, event: &watcher::Event<DynamicObject>) {
+        let mut state = self.state.lock().unwrap();
+        match event {
+            watcher::Event::Apply(workload) => {
+                state.update(
+                    self.workload_ref(workload),
+                    self.referenced_configs(workload),
+                );
+            }
+            watcher::Event::Delete(workload) => {
+                state.update(self.workload_ref(workload), Vec::new());
+            }
+            watcher::Event::Init => state.relisted_workloads = Some(HashSet::new()),
+            watcher::Event::InitApply(workload) => {
+                let workload_ref = self.workload_ref(workload);
+                if let Some(relisted_workloads) = &mut state.relisted_workloads {
+                    relisted_workloads.insert(workload_ref.clone());
+                }
+                state.update(workload_ref, self.referenced_configs(workload));
+            }
+            watcher::Event::InitDone => {
+                // Workloads that were not part of the relist have been deleted in the meantime
+                if let Some(relisted_workloads) = state.relisted_workloads.take() {
+                    let deleted_workloads = state
+                        .configs_by_workload
+                        .keys()
+                        .filter(|workload_ref| !relisted_workloads.contains(workload_ref))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    for workload_ref in deleted_workloads {
+                        state.update(workload_ref, Vec::new());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns all workloads referencing the given ConfigMap or Secret.
+    pub fn workloads_referencing(&self, config: &ConfigRef) -> Vec<ObjectRef<DynamicObject>> {
+        let state = self.state.lock().unwrap();
+        state
+            .workloads_by_config
+            .get(config)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    fn workload_ref(&self, workload: &DynamicObject) -> ObjectRef<DynamicObject> {
+        ObjectRef::from_obj_with(workload, self.workload_kind.api_resource.clone())
+    }
+
+    /// Returns all ConfigMaps and Secrets referenced by the Pod template of the workload.
+    ///
+    /// Ignore annotations are deliberately not taken into account, as they only affect the value
+    /// of the restarter annotations.
+    fn referenced_configs(&self, workload: &DynamicObject) -> Vec<ConfigRef> {
+        let namespace = workload.namespace().unwrap_or_default();
+        // Invalid Pod templates are reported by the reconciler
+        let Ok(Some(pod_template)) = self.workload_kind.pod_template(workload) else {
+            return Vec::new();
+        };
+        let Some(pod_spec) = &pod_template.spec else {
+            return Vec::new();
+        };
+
+        let config_maps = pod_refs::config_map_refs(pod_spec)
+            .into_keys()
+            .map(|name| (ConfigKind::ConfigMap, name));
+        let secrets = pod_refs::secret_refs(pod_spec)
+            .into_keys()
+            .map(|name| (ConfigKind::Secret, name));
+        config_maps
+            .chain(secrets)
+            .map(|(kind, name)| ConfigRef {
+                kind,
+                namespace: namespace.clone(),
+                name,
+            })
+            .collect()
+    }
+}
+
+impl IndexState {
+    /// Replaces the referenced configs of the workload.
+    fn update(&mut self, workload_ref: ObjectRef<DynamicObject>, configs: Vec<ConfigRef>) {
+        for config in self
+            .configs_by_workload
+            .remove(&workload_ref)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(workloads) = self.workloads_by_config.get_mut(&config) {
+                workloads.remove(&workload_ref);
+                if workloads.is_empty() {
+                    self.workloads_by_config.remove(&config);
+                }
+            }
+        }
+
+        if configs.is_empty() {
+            return;
+        }
+        for config in &configs {
+            self.workloads_by_config
+                .entry(config.clone())
+                .or_default()
+                .insert(workload_ref.clone());
+        }
+        self.configs_by_workload.insert(workload_ref, configs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use stackable_operator::{k8s_openapi::api::apps::v1::StatefulSet, kube::api::ApiResource};
+
+    use super::*;
+
+    fn index() -> WorkloadIndex {
+        WorkloadIndex::new(
+            WorkloadKind::builtin()
+                .into_iter()
+                .find(|workload_kind| workload_kind.api_resource.kind == "StatefulSet")
+                .unwrap(),
+        )
+    }
+
+    fn sts(name: &str, volumes: serde_json::Value) -> DynamicObject {
+        DynamicObject::new(name, &ApiResource::erase::<StatefulSet>(&()))
+            .within("default")
+            .data(json!({
+                "spec": {
+                    "template": {
+                        "spec": {
+                            "containers": [{"name": "main"}],
+                            "volumes": volumes,
+                        },
+                    },
+                },
+            }))
+    }
+
+    fn config_ref(kind: ConfigKind, name: &str) -> ConfigRef {
+        ConfigRef {
+            kind,
+            namespace: "default".to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    fn referencing(index: &WorkloadIndex, kind: ConfigKind, name: &str) -> Vec<String> {
+        let mut names = index
+            .workloads_referencing(&config_ref(kind, name))
+            .into_iter()
+            .map(|workload_ref| workload_ref.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn index_tracks_references() {
+        let index = index();
+        index.apply_watcher_event(&watcher::Event::Apply(sts(
+            "a",
+            json!([
+                {"name": "config", "configMap": {"name": "config"}},
+                {"name": "creds", "secret": {"secretName": "creds"}},
+            ]),
+        )));
+        index.apply_watcher_event(&watcher::Event::Apply(sts(
+            "b",
+            json!([{"name": "config", "configMap": {"name": "config"}}]),
+        )));
+
+        assert_eq!(
+            referencing(&index, ConfigKind::ConfigMap, "config"),
+            ["a", "b"]
+        );
+        assert_eq!(referencing(&index, ConfigKind::Secret, "creds"), ["a"]);
+        // Same name, but different kind
+        assert!(referencing(&index, ConfigKind::Secret, "config").is_empty());
+        // Same name, but different namespace
+        assert!(
+            index
+                .workloads_referencing(&ConfigRef {
+                    namespace: "other".to_owned(),
+                    ..config_ref(ConfigKind::ConfigMap, "config")
+                })
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn index_forgets_removed_references() {
+        let index = index();
+        index.apply_watcher_event(&watcher::Event::Apply(sts(
+            "a",
+            json!([{"name": "config", "configMap": {"name": "config"}}]),
+        )));
+        index.apply_watcher_event(&watcher::Event::Apply(sts(
+            "a",
+            json!([{"name": "config", "configMap": {"name": "new-config"}}]),
+        )));
+        assert!(referencing(&index, ConfigKind::ConfigMap, "config").is_empty());
+        assert_eq!(
+            referencing(&index, ConfigKind::ConfigMap, "new-config"),
+            ["a"]
+        );
+
+        index.apply_watcher_event(&watcher::Event::Delete(sts(
+            "a",
+            json!([{"name": "config", "configMap": {"name": "new-config"}}]),
+        )));
+        assert!(referencing(&index, ConfigKind::ConfigMap, "new-config").is_empty());
+        assert!(index.state.lock().unwrap().workloads_by_config.is_empty());
+    }
+
+    #[test]
+    fn relist_drops_deleted_workloads() {
+        let index = index();
+        let volumes = json!([{"name": "config", "configMap": {"name": "config"}}]);
+        index.apply_watcher_event(&watcher::Event::Apply(sts("a", volumes.clone())));
+        index.apply_watcher_event(&watcher::Event::Apply(sts("b", volumes.clone())));
+
+        index.apply_watcher_event(&watcher::Event::Init);
+        index.apply_watcher_event(&watcher::Event::InitApply(sts("b", volumes)));
+        // The old state is kept until the relist is complete
+        assert_eq!(
+            referencing(&index, ConfigKind::ConfigMap, "config"),
+            ["a", "b"]
+        );
+        index.apply_watcher_event(&watcher::Event::InitDone);
+        assert_eq!(referencing(&index, ConfigKind::ConfigMap, "config"), ["b"]);
+    }
+
+    #[test]
+    fn workloads_without_pod_template_have_no_references() {
+        let index = index();
+        let mut sts = sts("a", json!([]));
+        sts.data = json!({"spec": {}});
+        index.apply_watcher_event(&watcher::Event::Apply(sts));
+        assert!(index.state.lock().unwrap().configs_by_workload.is_empty());
+    }
+}
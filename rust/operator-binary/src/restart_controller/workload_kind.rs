@@ -0,0 +1,317 @@
+use std::{fs::File, path::Path, str::FromStr};
+
+use json_patch::jsonptr::{ParseError as PointerParseError, PointerBuf};
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+use stackable_operator::{
+    k8s_openapi::{
+        api::{
+            apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
+            core::v1::PodTemplateSpec,
+        },
+        apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    },
+    kube::{
+        self,
+        api::ApiResource,
+        core::{DynamicObject, GroupVersionKind},
+        discovery::{self, Scope},
+    },
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "workload registration {registration:?} is not of the form \
+        \"<group>/<version>/<kind>=<pod-template-json-pointer>\""
+    ))]
+    MalformedRegistration { registration: String },
+
+    #[snafu(display("Pod template path {path:?} is not a valid JSON pointer"))]
+    InvalidPodTemplatePath {
+        source: PointerParseError,
+        path: String,
+    },
+
+    #[snafu(display("failed to open workload registrations file {path:?}"))]
+    OpenRegistrationsFile {
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[snafu(display("failed to parse workload registrations file {path:?}"))]
+    ParseRegistrationsFile {
+        source: serde_yaml::Error,
+        path: String,
+    },
+
+    #[snafu(display("failed to discover the API resource of {gvk:?}"))]
+    DiscoverApiResource {
+        source: kube::Error,
+        gvk: GroupVersionKind,
+    },
+
+    #[snafu(display("{gvk:?} is cluster-scoped, but only namespaced workloads are supported"))]
+    ClusterScopedWorkload { gvk: GroupVersionKind },
+}
+
+/// A kind of workload (such as a StatefulSet) that embeds a Pod template, which is rolling-restarted
+/// by updating the annotations of the Pod template.
+#[derive(Clone, Debug)]
+pub struct WorkloadKind {
+    pub api_resource: ApiResource,
+
+    /// JSON pointer to the `PodTemplateSpec` within objects of this kind.
+    pub pod_template_path: PointerBuf,
+}
+
+impl WorkloadKind {
+    /// The workload kinds of Kubernetes itself, which are always supported.
+    pub fn builtin() -> Vec<Self> {
+        let spec_template = || PointerBuf::from_tokens(["spec", "template"]);
+        vec![
+            Self {
+                api_resource: ApiResource::erase::<StatefulSet>(&()),
+                pod_template_path: spec_template(),
+            },
+            Self {
+                api_resource: ApiResource::erase::<Deployment>(&()),
+                pod_template_path: spec_template(),
+            },
+            Self {
+                api_resource: ApiResource::erase::<DaemonSet>(&()),
+                pod_template_path: spec_template(),
+            },
+            Self {
+                api_resource: ApiResource::erase::<ReplicaSet>(&()),
+                pod_template_path: spec_template(),
+            },
+        ]
+    }
+
+    /// Whether this is the kind of the builtin StatefulSets (regardless of its Pod template path).
+    pub fn is_stateful_set(&self) -> bool {
+        let stateful_set = ApiResource::erase::<StatefulSet>(&());
+        self.api_resource.group == stateful_set.group && self.api_resource.kind == stateful_set.kind
+    }
+
+    /// The name of the controller restarting workloads of this kind, e.g.
+    /// `statefulset.restarter.commons.stackable.tech`.
+    pub fn controller_name(&self) -> String {
+        format!(
+            "{kind}.restarter.commons.stackable.tech",
+            kind = self.api_resource.kind.to_lowercase()
+        )
+    }
+
+    /// The field manager used to apply the restarter annotations, e.g.
+    /// `restarter.stackable.tech/statefulset`.
+    pub fn field_manager(&self) -> String {
+        format!(
+            "restarter.stackable.tech/{kind}",
+            kind = self.api_resource.kind.to_lowercase()
+        )
+    }
+
+    /// Returns the Pod template of the workload, or [`None`] if it has none (yet).
+    pub fn pod_template(
+        &self,
+        workload: &DynamicObject,
+    ) -> Result<Option<PodTemplateSpec>, serde_json::Error> {
+        workload
+            .data
+            .pointer(self.pod_template_path.as_str())
+            .map(|pod_template| serde_json::from_value(pod_template.clone()))
+            .transpose()
+    }
+
+    /// Wraps `value` into the nested objects leading up to the Pod template, so that it can be
+    /// used as a (server-side apply) patch of the workload.
+    pub fn nest_in_pod_template(&self, value: serde_json::Value) -> serde_json::Value {
+        let tokens = self
+            .pod_template_path
+            .tokens()
+            .map(|token| token.decoded().into_owned())
+            .collect::<Vec<_>>();
+        tokens.into_iter().rev().fold(value, |value, token| {
+            serde_json::Value::Object(serde_json::Map::from_iter([(token, value)]))
+        })
+    }
+}
+
+/// Whether the workload is controlled by another object, such as the ReplicaSets of a Deployment.
+///
+/// Such workloads are restarted by restarting their controller. Their Pod template is managed by
+/// the controller, which would revert (or, for the ReplicaSets of a Deployment, replace) any change
+/// made by the restarter.
+pub fn is_controlled(workload: &ObjectMeta) -> bool {
+    workload
+        .owner_references
+        .iter()
+        .flatten()
+        .any(|owner| owner.controller == Some(true))
+}
+
+/// A user-provided workload kind, which still needs to be resolved against the Kubernetes API to
+/// become a [`WorkloadKind`].
+///
+/// It is given as `<group>/<version>/<kind>=<pod-template-json-pointer>`, e.g.
+/// `argoproj.io/v1alpha1/Rollout=/spec/template`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorkloadRegistration {
+    pub gvk: GroupVersionKind,
+    pub pod_template_path: PointerBuf,
+}
+
+impl FromStr for WorkloadRegistration {
+    type Err = Error;
+
+    fn from_str(registration: &str) -> Result<Self, Self::Err> {
+        let (gvk, pod_template_path) = registration
+            .split_once('=')
+            .context(MalformedRegistrationSnafu { registration })?;
+        let (group_version, kind) = gvk
+            .rsplit_once('/')
+            .context(MalformedRegistrationSnafu { registration })?;
+        ensure!(
+            !kind.is_empty() && !group_version.is_empty(),
+            MalformedRegistrationSnafu { registration }
+        );
+
+        Self::new(group_version, kind, pod_template_path)
+    }
+}
+
+impl WorkloadRegistration {
+    fn new(api_version: &str, kind: &str, pod_template_path: &str) -> Result<Self, Error> {
+        let (group, version) = api_version.split_once('/').unwrap_or(("", api_version));
+        Ok(Self {
+            gvk: GroupVersionKind::gvk(group, version, kind),
+            pod_template_path: PointerBuf::parse(pod_template_path).context(
+                InvalidPodTemplatePathSnafu {
+                    path: pod_template_path,
+                },
+            )?,
+        })
+    }
+
+    /// Reads a list of workload registrations from a YAML (or JSON) file, such as
+    ///
+    /// ```yaml
+    /// - apiVersion: argoproj.io/v1alpha1
+    ///   kind: Rollout
+    ///   podTemplatePath: /spec/template
+    /// ```
+    pub fn from_file(path: &Path) -> Result<Vec<Self>, Error> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct FileEntry {
+            api_version: String,
+            kind: String,
+            pod_template_path: String,
+        }
+
+        let path_display = path.display().to_string();
+        let file = File::open(path).context(OpenRegistrationsFileSnafu {
+            path: &path_display,
+        })?;
+        let entries: Vec<FileEntry> =
+            serde_yaml::from_reader(file).context(ParseRegistrationsFileSnafu {
+                path: &path_display,
+            })?;
+        entries
+            .iter()
+            .map(|entry| Self::new(&entry.api_version, &entry.kind, &entry.pod_template_path))
+            .collect()
+    }
+
+    /// Looks up the API resource (most importantly its plural name) of the registered kind.
+    pub async fn resolve(&self, client: &kube::Client) -> Result<WorkloadKind, Error> {
+        let (api_resource, capabilities) = discovery::pinned_kind(client, &self.gvk)
+            .await
+            .context(DiscoverApiResourceSnafu {
+                gvk: self.gvk.clone(),
+            })?;
+        ensure!(
+            capabilities.scope == Scope::Namespaced,
+            ClusterScopedWorkloadSnafu {
+                gvk: self.gvk.clone(),
+            }
+        );
+
+        Ok(WorkloadKind {
+            api_resource,
+            pod_template_path: self.pod_template_path.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+
+    use super::*;
+
+    #[test]
+    fn parse_registration() {
+        let registration: WorkloadRegistration = "argoproj.io/v1alpha1/Rollout=/spec/template"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            registration.gvk,
+            GroupVersionKind::gvk("argoproj.io", "v1alpha1", "Rollout")
+        );
+        assert_eq!(registration.pod_template_path.as_str(), "/spec/template");
+
+        let registration: WorkloadRegistration = "v1/PodTemplate=/template".parse().unwrap();
+        assert_eq!(
+            registration.gvk,
+            GroupVersionKind::gvk("", "v1", "PodTemplate")
+        );
+    }
+
+    #[test]
+    fn reject_malformed_registrations() {
+        for registration in [
+            "argoproj.io/v1alpha1/Rollout",
+            "Rollout=/spec/template",
+            "argoproj.io/v1alpha1/=/spec/template",
+            "argoproj.io/v1alpha1/Rollout=spec/template",
+        ] {
+            assert!(
+                registration.parse::<WorkloadRegistration>().is_err(),
+                "{registration:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn skip_controlled_workloads() {
+        let owned_by = |controller| ObjectMeta {
+            owner_references: Some(vec![OwnerReference {
+                api_version: "apps/v1".to_owned(),
+                kind: "Deployment".to_owned(),
+                name: "nginx".to_owned(),
+                controller,
+                ..OwnerReference::default()
+            }]),
+            ..ObjectMeta::default()
+        };
+        assert!(is_controlled(&owned_by(Some(true))));
+        assert!(!is_controlled(&owned_by(Some(false))));
+        assert!(!is_controlled(&owned_by(None)));
+        assert!(!is_controlled(&ObjectMeta::default()));
+    }
+
+    #[test]
+    fn nest_in_pod_template() {
+        let workload_kind = WorkloadKind {
+            api_resource: ApiResource::erase::<StatefulSet>(&()),
+            pod_template_path: PointerBuf::parse("/spec/workload/template").unwrap(),
+        };
+        assert_eq!(
+            workload_kind.nest_in_pod_template(serde_json::json!({"metadata": {}})),
+            serde_json::json!({"spec": {"workload": {"template": {"metadata": {}}}}})
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Minimal support for cron expressions, as used by e.g. maintenance windows.
+//!
+//! Expressions consist of the five fields `<minute> <hour> <day-of-month> <month> <day-of-week>`,
+//! each of which can be `*`, a value, a range (`1-5`), a step (`*/15`, `0-30/10`) or a list of them
+//! (`1,15`). Months and days of the week can also be given by their (English) abbreviations, such as
+//! `JAN` or `MON`. Just like in the classic cron, a time matches if either the day of the month or
+//! the day of the week matches, if both of them are restricted.
+//!
+//! The expression can be prefixed with `CRON_TZ=<time-zone>` (such as `CRON_TZ=Europe/Berlin`), in
+//! which case it is evaluated in the given time zone rather than in UTC.
+
+use std::{fmt::Display, str::FromStr};
+
+use jiff::{
+    Timestamp,
+    civil::{Date, Time},
+    tz::TimeZone,
+};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+
+/// How many days are searched (forwards or backwards) for a matching time, which is enough to
+/// find e.g. the next February 29th.
+const MAX_SEARCH_DAYS: usize = 366 * 9;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "cron expression {expression:?} must consist of the five fields \
+        <minute> <hour> <day-of-month> <month> <day-of-week>"
+    ))]
+    InvalidFieldCount { expression: String },
+
+    #[snafu(display("invalid value {value:?} for the {field} field"))]
+    InvalidValue { field: &'static str, value: String },
+
+    #[snafu(display("{value} is out of the range {min}-{max} of the {field} field"))]
+    OutOfRange {
+        field: &'static str,
+        value: u8,
+        min: u8,
+        max: u8,
+    },
+
+    #[snafu(display("unknown time zone {time_zone:?}"))]
+    UnknownTimeZone {
+        source: jiff::Error,
+        time_zone: String,
+    },
+}
+
+/// A parsed cron expression, see the [module documentation](self) for the syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+    time_zone: TimeZone,
+}
+
+struct Field {
+    name: &'static str,
+    min: u8,
+    max: u8,
+    /// Names of the values, starting at `min`
+    value_names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    value_names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    value_names: &[],
+};
+const DAY_OF_MONTH: Field = Field {
+    name: "day-of-month",
+    min: 1,
+    max: 31,
+    value_names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    value_names: &[
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ],
+};
+// Both 0 and 7 are Sunday
+const DAY_OF_WEEK: Field = Field {
+    name: "day-of-week",
+    min: 0,
+    max: 7,
+    value_names: &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"],
+};
+
+impl Field {
+    /// Parses the field into a bit set of the matching values, along with whether it is restricted
+    /// (as opposed to `*`).
+    fn parse(&self, field: &str) -> Result<(u64, bool), Error> {
+        let mut values = 0u64;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    Some(step.parse::<u8>().ok().filter(|step| *step > 0).context(
+                        InvalidValueSnafu {
+                            field: self.name,
+                            value: part,
+                        },
+                    )?),
+                ),
+                None => (part, None),
+            };
+            let (start, end) = match range.split_once('-') {
+                _ if range == "*" => (self.min, self.max),
+                Some((start, end)) => (self.parse_value(start)?, self.parse_value(end)?),
+                // `5/10` is equivalent to `5-<max>/10`
+                None if step.is_some() => (self.parse_value(range)?, self.max),
+                None => {
+                    let value = self.parse_value(range)?;
+                    (value, value)
+                }
+            };
+            ensure!(
+                start <= end,
+                InvalidValueSnafu {
+                    field: self.name,
+                    value: part,
+                }
+            );
+            for value in (start..=end).step_by(step.unwrap_or(1).into()) {
+                values |= 1 << value;
+            }
+        }
+        Ok((values, field != "*"))
+    }
+
+    fn parse_value(&self, value: &str) -> Result<u8, Error> {
+        if let Some(index) = self
+            .value_names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+        {
+            return Ok(self.min + index as u8);
+        }
+
+        let parsed = value.parse::<u8>().ok().context(InvalidValueSnafu {
+            field: self.name,
+            value,
+        })?;
+        ensure!(
+            (self.min..=self.max).contains(&parsed),
+            OutOfRangeSnafu {
+                field: self.name,
+                value: parsed,
+                min: self.min,
+                max: self.max,
+            }
+        );
+        Ok(parsed)
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let mut fields = expression.split_whitespace().peekable();
+        let time_zone = match fields
+            .peek()
+            .and_then(|field| field.strip_prefix("CRON_TZ="))
+        {
+            Some(time_zone) => {
+                fields.next();
+                TimeZone::get(time_zone).context(UnknownTimeZoneSnafu { time_zone })?
+            }
+            None => TimeZone::UTC,
+        };
+        let fields = fields.collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return InvalidFieldCountSnafu { expression }.fail();
+        };
+
+        let (days_of_week, days_of_week_restricted) = DAY_OF_WEEK.parse(day_of_week)?;
+        let (days_of_month, days_of_month_restricted) = DAY_OF_MONTH.parse(day_of_month)?;
+        Ok(Self {
+            expression: expression.trim().to_owned(),
+            minutes: MINUTE.parse(minute)?.0,
+            hours: HOUR.parse(hour)?.0,
+            days_of_month,
+            months: MONTH.parse(month)?.0,
+            // Fold the second Sunday (7) into the first one (0)
+            days_of_week: (days_of_week | days_of_week >> 7) & 0x7f,
+            days_of_month_restricted,
+            days_of_week_restricted,
+            time_zone,
+        })
+    }
+}
+
+impl Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl CronSchedule {
+    /// Returns the first time matching the schedule that is strictly after `after`, or [`None`]
+    /// if there is none in the foreseeable future (e.g. for February 30th).
+    pub fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        let mut date = after.to_zoned(self.time_zone.clone()).date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if let Some(time) = self.times_on(date).find(|time| *time > after) {
+                return Some(time);
+            }
+            date = date.tomorrow().ok()?;
+        }
+        None
+    }
+
+    /// Returns the last time matching the schedule that is at or before `at`, but not before
+    /// `not_before` (and not too far in the past).
+    pub fn last_at_or_before(&self, at: Timestamp, not_before: Timestamp) -> Option<Timestamp> {
+        let mut date = at.to_zoned(self.time_zone.clone()).date();
+        let first_date = not_before.to_zoned(self.time_zone.clone()).date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if date < first_date {
+                break;
+            }
+            if let Some(time) = self
+                .times_on(date)
+                .filter(|time| *time <= at && *time >= not_before)
+                .last()
+            {
+                return Some(time);
+            }
+            date = date.yesterday().ok()?;
+        }
+        None
+    }
+
+    /// Returns all matching times on the given date (in the time zone of the schedule) in
+    /// ascending order.
+    fn times_on(&self, date: Date) -> impl Iterator<Item = Timestamp> + '_ {
+        let matches_date = self.matches_date(date);
+        bits(self.hours)
+            .filter(move |_| matches_date)
+            .flat_map(move |hour| bits(self.minutes).map(move |minute| (hour, minute)))
+            .filter_map(move |(hour, minute)| {
+                let time = Time::new(hour as i8, minute as i8, 0, 0).ok()?;
+                // Times that are skipped due to daylight saving time are shifted forward
+                self.time_zone
+                    .to_ambiguous_timestamp(date.to_datetime(time))
+                    .compatible()
+                    .ok()
+            })
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        let matches_month = self.months & (1 << date.month()) != 0;
+        let matches_day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let matches_day_of_week =
+            self.days_of_week & (1 << date.weekday().to_sunday_zero_offset()) != 0;
+        matches_month
+            && match (self.days_of_month_restricted, self.days_of_week_restricted) {
+                (true, true) => matches_day_of_month || matches_day_of_week,
+                (true, false) => matches_day_of_month,
+                (false, true) => matches_day_of_week,
+                (false, false) => true,
+            }
+    }
+}
+
+/// Returns the indices of all set bits in ascending order.
+fn bits(bits: u64) -> impl Iterator<Item = u8> {
+    (0..64).filter(move |bit| bits & (1 << bit) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(timestamp: &str) -> Timestamp {
+        timestamp.parse().unwrap()
+    }
+
+    fn next_after(expression: &str, after: &str) -> Option<Timestamp> {
+        expression
+            .parse::<CronSchedule>()
+            .unwrap()
+            .next_after(ts(after))
+    }
+
+    #[test]
+    fn parse_fields() {
+        let schedule: CronSchedule = "*/15 2,4-6 1 JAN-mar/2 SUN,7".parse().unwrap();
+        assert_eq!(schedule.minutes, 1 << 0 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(schedule.hours, 1 << 2 | 1 << 4 | 1 << 5 | 1 << 6);
+        assert_eq!(schedule.days_of_month, 1 << 1);
+        assert_eq!(schedule.months, 1 << 1 | 1 << 3);
+        assert_eq!(schedule.days_of_week, 1 << 0);
+        assert!(schedule.days_of_month_restricted);
+        assert!(schedule.days_of_week_restricted);
+    }
+
+    #[test]
+    fn reject_invalid_expressions() {
+        for expression in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "* * * FOO *",
+            "CRON_TZ=Mars/Olympus_Mons * * * * *",
+        ] {
+            assert!(
+                expression.parse::<CronSchedule>().is_err(),
+                "{expression:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn next_after_in_utc() {
+        assert_eq!(
+            next_after("30 2 * * *", "2024-03-01T02:30:00Z"),
+            Some(ts("2024-03-02T02:30:00Z"))
+        );
+        assert_eq!(
+            next_after("30 2 * * *", "2024-03-01T02:29:59Z"),
+            Some(ts("2024-03-01T02:30:00Z"))
+        );
+        // 2024-03-01 is a Friday
+        assert_eq!(
+            next_after("0 22 * * SAT,SUN", "2024-03-01T12:00:00Z"),
+            Some(ts("2024-03-02T22:00:00Z"))
+        );
+        assert_eq!(
+            next_after("0 0 29 2 *", "2024-03-01T00:00:00Z"),
+            Some(ts("2028-02-29T00:00:00Z"))
+        );
+        assert_eq!(next_after("0 0 30 2 *", "2024-03-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn next_after_matches_day_of_month_or_day_of_week() {
+        // The 15th of March 2024 or the next Monday (2024-03-04)
+        assert_eq!(
+            next_after("0 0 15 * MON", "2024-03-01T12:00:00Z"),
+            Some(ts("2024-03-04T00:00:00Z"))
+        );
+        assert_eq!(
+            next_after("0 0 15 * MON", "2024-03-11T12:00:00Z"),
+            Some(ts("2024-03-15T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn next_after_in_time_zone() {
+        // Berlin is at UTC+1 in winter and UTC+2 in summer
+        assert_eq!(
+            next_after("CRON_TZ=Europe/Berlin 0 2 * * *", "2024-01-01T00:00:00Z"),
+            Some(ts("2024-01-01T01:00:00Z"))
+        );
+        assert_eq!(
+            next_after("CRON_TZ=Europe/Berlin 0 2 * * *", "2024-06-30T23:00:00Z"),
+            Some(ts("2024-07-01T00:00:00Z"))
+        );
+        // 02:30 does not exist on 2024-03-31 in Berlin, so it's shifted to 03:30 (CEST)
+        assert_eq!(
+            next_after("CRON_TZ=Europe/Berlin 30 2 * * *", "2024-03-30T12:00:00Z"),
+            Some(ts("2024-03-31T01:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn last_at_or_before() {
+        let schedule: CronSchedule = "0 */6 * * *".parse().unwrap();
+        assert_eq!(
+            schedule.last_at_or_before(ts("2024-03-01T05:59:00Z"), ts("2024-02-29T00:00:00Z")),
+            Some(ts("2024-03-01T00:00:00Z"))
+        );
+        assert_eq!(
+            schedule.last_at_or_before(ts("2024-03-01T06:00:00Z"), ts("2024-02-29T00:00:00Z")),
+            Some(ts("2024-03-01T06:00:00Z"))
+        );
+        assert_eq!(
+            schedule.last_at_or_before(ts("2024-03-01T05:59:00Z"), ts("2024-03-01T00:00:01Z")),
+            None
+        );
+    }
+}
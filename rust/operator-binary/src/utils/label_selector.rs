@@ -0,0 +1,318 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use snafu::{OptionExt, Snafu, ensure};
+use stackable_operator::k8s_openapi::apimachinery::pkg::apis::meta::v1::{
+    LabelSelector, LabelSelectorRequirement,
+};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("label selector {selector:?} contains an empty requirement"))]
+    EmptyRequirement { selector: String },
+
+    #[snafu(display("requirement {requirement:?} of label selector is malformed"))]
+    MalformedRequirement { requirement: String },
+
+    #[snafu(display("label selector {selector:?} contains unbalanced parentheses"))]
+    UnbalancedParentheses { selector: String },
+}
+
+/// A label selector in the string form that is understood by the Kubernetes API (e.g.
+/// `app=trino,tier in (frontend,backend),!canary`), together with its structured form (as used by
+/// e.g. webhook configurations).
+#[derive(Clone, Debug)]
+pub struct LabelSelectorExpression {
+    expression: String,
+    label_selector: LabelSelector,
+}
+
+// The structured form is derived from the expression
+impl PartialEq for LabelSelectorExpression {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression
+    }
+}
+
+impl Eq for LabelSelectorExpression {}
+
+impl FromStr for LabelSelectorExpression {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let mut match_labels = BTreeMap::<String, String>::new();
+        let mut match_expressions = Vec::new();
+        for requirement in split_requirements(expression)? {
+            let requirement = requirement.trim();
+            ensure!(
+                !requirement.is_empty(),
+                EmptyRequirementSnafu {
+                    selector: expression
+                }
+            );
+            let malformed = || MalformedRequirementSnafu { requirement };
+
+            let set_operator = [(" notin ", "NotIn"), (" in ", "In")].into_iter().find_map(
+                |(separator, operator)| {
+                    let (key, values) = requirement.split_once(separator)?;
+                    Some((key, values, operator))
+                },
+            );
+            let (key, operator, values) = if let Some((key, values, operator)) = set_operator {
+                let values = values
+                    .trim()
+                    .strip_prefix('(')
+                    .and_then(|values| values.strip_suffix(')'))
+                    .with_context(malformed)?
+                    .split(',')
+                    .map(|value| value.trim().to_owned())
+                    .collect();
+                (key, operator, Some(values))
+            } else if let Some((key, value)) = requirement.split_once("!=") {
+                (key, "NotIn", Some(vec![value.trim().to_owned()]))
+            } else if let Some((key, value)) = requirement
+                .split_once("==")
+                .or_else(|| requirement.split_once('='))
+            {
+                let (key, value) = (key.trim(), value.trim());
+                ensure!(is_label_part(key) && is_label_part(value), malformed());
+                // A key can only be part of `matchLabels` once
+                if !match_labels.contains_key(key) {
+                    match_labels.insert(key.to_owned(), value.to_owned());
+                    continue;
+                }
+                (key, "In", Some(vec![value.to_owned()]))
+            } else if let Some(key) = requirement.strip_prefix('!') {
+                (key, "DoesNotExist", None)
+            } else {
+                (requirement, "Exists", None)
+            };
+
+            let key = key.trim();
+            ensure!(
+                !key.is_empty()
+                    && is_label_part(key)
+                    && values.iter().flatten().all(|value| is_label_part(value)),
+                malformed()
+            );
+            match_expressions.push(LabelSelectorRequirement {
+                key: key.to_owned(),
+                operator: operator.to_owned(),
+                values,
+            });
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_owned(),
+            label_selector: LabelSelector {
+                match_labels: (!match_labels.is_empty()).then_some(match_labels),
+                match_expressions: (!match_expressions.is_empty()).then_some(match_expressions),
+            },
+        })
+    }
+}
+
+impl Display for LabelSelectorExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl LabelSelectorExpression {
+    pub fn as_str(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn label_selector(&self) -> &LabelSelector {
+        &self.label_selector
+    }
+
+    /// Returns a label selector matching objects that are matched by both `self` and `other`.
+    pub fn and(&self, other: &Self) -> Self {
+        format!("{self},{other}")
+            .parse()
+            .expect("the combination of valid label selectors is always valid")
+    }
+
+    /// Whether an object with the given `labels` is matched by the label selector.
+    pub fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let LabelSelector {
+            match_labels,
+            match_expressions,
+        } = &self.label_selector;
+        match_labels
+            .iter()
+            .flatten()
+            .all(|(key, value)| labels.get(key) == Some(value))
+            && match_expressions.iter().flatten().all(|requirement| {
+                let value = labels.get(&requirement.key);
+                let in_values = || {
+                    value.is_some_and(|value| {
+                        requirement.values.iter().flatten().any(|v| v == value)
+                    })
+                };
+                match requirement.operator.as_str() {
+                    "In" => in_values(),
+                    "NotIn" => !in_values(),
+                    "Exists" => value.is_some(),
+                    "DoesNotExist" => value.is_none(),
+                    _ => false,
+                }
+            })
+    }
+}
+
+/// Splits the requirements of a label selector at commas that are not part of a set of values.
+fn split_requirements(selector: &str) -> Result<Vec<&str>, Error> {
+    let mut requirements = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .context(UnbalancedParenthesesSnafu { selector })?
+            }
+            ',' if depth == 0 => {
+                requirements.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ensure!(depth == 0, UnbalancedParenthesesSnafu { selector });
+    requirements.push(&selector[start..]);
+    Ok(requirements)
+}
+
+/// Whether the label key or value only contains characters that are allowed in labels.
+///
+/// This only rejects obviously malformed requirements, the Kubernetes API server does the full
+/// validation.
+fn is_label_part(part: &str) -> bool {
+    part.chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requirement(key: &str, operator: &str, values: Option<&[&str]>) -> LabelSelectorRequirement {
+        LabelSelectorRequirement {
+            key: key.to_owned(),
+            operator: operator.to_owned(),
+            values: values.map(|values| values.iter().map(|value| value.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn parse_selector() {
+        let selector: LabelSelectorExpression =
+            "app=trino, tier in (frontend, backend),env notin (dev),stage==prod,canary!=true,managed,!legacy"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            selector.label_selector(),
+            &LabelSelector {
+                match_labels: Some(BTreeMap::from([
+                    ("app".to_owned(), "trino".to_owned()),
+                    ("stage".to_owned(), "prod".to_owned()),
+                ])),
+                match_expressions: Some(vec![
+                    requirement("tier", "In", Some(&["frontend", "backend"])),
+                    requirement("env", "NotIn", Some(&["dev"])),
+                    requirement("canary", "NotIn", Some(&["true"])),
+                    requirement("managed", "Exists", None),
+                    requirement("legacy", "DoesNotExist", None),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn combine_selectors() {
+        let enabled: LabelSelectorExpression =
+            "restarter.stackable.tech/enabled=true".parse().unwrap();
+        let combined = enabled.and(&"restarter.stackable.tech/enabled=false".parse().unwrap());
+        assert_eq!(
+            combined.as_str(),
+            "restarter.stackable.tech/enabled=true,restarter.stackable.tech/enabled=false"
+        );
+        assert_eq!(
+            combined.label_selector().match_expressions,
+            Some(vec![requirement(
+                "restarter.stackable.tech/enabled",
+                "In",
+                Some(&["false"])
+            )])
+        );
+    }
+
+    #[test]
+    fn match_labels() {
+        let selector: LabelSelectorExpression =
+            "app=trino,tier in (frontend,backend),canary!=true,managed,!legacy"
+                .parse()
+                .unwrap();
+        let labels = |labels: &[(&str, &str)]| {
+            labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert!(selector.matches(&labels(&[
+            ("app", "trino"),
+            ("tier", "frontend"),
+            ("managed", ""),
+        ])));
+        assert!(selector.matches(&labels(&[
+            ("app", "trino"),
+            ("tier", "backend"),
+            ("canary", "false"),
+            ("managed", "true"),
+        ])));
+        for unmatched in [
+            labels(&[("app", "hive"), ("tier", "frontend"), ("managed", "")]),
+            labels(&[("app", "trino"), ("tier", "db"), ("managed", "")]),
+            labels(&[("app", "trino"), ("managed", "")]),
+            labels(&[
+                ("app", "trino"),
+                ("tier", "frontend"),
+                ("canary", "true"),
+                ("managed", ""),
+            ]),
+            labels(&[("app", "trino"), ("tier", "frontend")]),
+            labels(&[
+                ("app", "trino"),
+                ("tier", "frontend"),
+                ("managed", ""),
+                ("legacy", ""),
+            ]),
+        ] {
+            assert!(
+                !selector.matches(&unmatched),
+                "{unmatched:?} should not match"
+            );
+        }
+    }
+
+    #[test]
+    fn reject_malformed_selectors() {
+        for selector in [
+            "",
+            "app=trino,",
+            "tier in frontend",
+            "tier in (frontend",
+            "tier)",
+            "app=trino=coordinator",
+            "app trino",
+        ] {
+            assert!(
+                selector.parse::<LabelSelectorExpression>().is_err(),
+                "{selector:?} should be rejected"
+            );
+        }
+    }
+}
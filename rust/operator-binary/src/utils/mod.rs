@@ -1 +1,4 @@
+pub mod cron;
 pub mod delayed_init;
+pub mod label_selector;
+pub mod watched_namespaces;
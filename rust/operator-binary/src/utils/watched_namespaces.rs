@@ -0,0 +1,487 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    hash::Hash,
+};
+
+use futures::{
+    Stream, StreamExt, TryStreamExt,
+    future::{self, Either},
+    stream::{self, AbortHandle, BoxStream, SelectAll},
+};
+use snafu::{ResultExt, Snafu};
+use stackable_operator::{
+    client::Client,
+    k8s_openapi::api::core::v1::Namespace,
+    kube::{
+        self, Api, Resource, ResourceExt,
+        api::ListParams,
+        runtime::{metadata_watcher, reflector::Store, watcher},
+    },
+    namespace::WatchNamespace,
+};
+
+use crate::utils::label_selector::LabelSelectorExpression;
+
+/// Label that Kubernetes sets on every namespace, containing the name of the namespace.
+const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "only one of the watch namespace, the list of watch namespaces and the watch namespace \
+        selector can be given"
+    ))]
+    ConflictingWatchNamespaces,
+
+    #[snafu(display("failed to list the namespaces matching the watch namespace selector"))]
+    ListNamespaces { source: kube::Error },
+}
+
+/// The namespaces whose objects are watched by the controllers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchedNamespaces {
+    /// All namespaces of the cluster.
+    All,
+
+    /// A fixed set of namespaces.
+    Static(BTreeSet<String>),
+
+    /// All namespaces matching the label selector.
+    ///
+    /// Namespaces are watched as soon as they start matching, and no longer watched once they stop
+    /// matching.
+    Selector(LabelSelectorExpression),
+}
+
+impl WatchedNamespaces {
+    /// Combines the `watch_namespace` of the common operator arguments with the list of
+    /// `namespaces` and the namespace `selector`, of which only one may be given.
+    pub fn new(
+        watch_namespace: WatchNamespace,
+        namespaces: Vec<String>,
+        selector: Option<LabelSelectorExpression>,
+    ) -> Result<Self, Error> {
+        Ok(match (watch_namespace, namespaces.is_empty(), selector) {
+            (WatchNamespace::All, true, None) => Self::All,
+            (WatchNamespace::One(namespace), true, None) => {
+                Self::Static(BTreeSet::from([namespace]))
+            }
+            (WatchNamespace::All, false, None) => Self::Static(namespaces.into_iter().collect()),
+            (WatchNamespace::All, true, Some(selector)) => Self::Selector(selector),
+            _ => ConflictingWatchNamespacesSnafu.fail()?,
+        })
+    }
+
+    /// Returns the label selector matching the watched namespaces, [`None`] if all namespaces are
+    /// watched.
+    pub fn label_selector(&self) -> Option<LabelSelectorExpression> {
+        match self {
+            Self::All => None,
+            Self::Static(namespaces) => Some(
+                format!(
+                    "{NAMESPACE_NAME_LABEL} in ({})",
+                    namespaces.iter().cloned().collect::<Vec<_>>().join(",")
+                )
+                .parse()
+                .expect("namespace names are always valid label values"),
+            ),
+            Self::Selector(selector) => Some(selector.clone()),
+        }
+    }
+
+    /// Returns the namespaces that are watched right now, or [`WatchNamespace::All`] if all
+    /// namespaces are watched.
+    pub async fn current(&self, client: &Client) -> Result<Vec<WatchNamespace>, Error> {
+        Ok(match self {
+            Self::All => vec![WatchNamespace::All],
+            Self::Static(namespaces) => namespaces
+                .iter()
+                .cloned()
+                .map(WatchNamespace::One)
+                .collect(),
+            Self::Selector(selector) => Api::<Namespace>::all(client.as_kube_client())
+                .list_metadata(&ListParams::default().labels(selector.as_str()))
+                .await
+                .context(ListNamespacesSnafu)?
+                .items
+                .iter()
+                .map(|namespace| WatchNamespace::One(namespace.name_any()))
+                .collect(),
+        })
+    }
+
+    /// Watches the objects in all watched namespaces, as a single stream that can be fed into a
+    /// reflector.
+    ///
+    /// `watch` is called with the namespace to watch, or [`WatchNamespace::All`] if all namespaces
+    /// are watched.
+    /// The `store` must be the store of the reflector that the stream is fed into.
+    ///
+    /// As every namespace is listed on its own, the `Init*` events of the namespaced streams are
+    /// translated into `Apply` and `Delete` events, so that a relist of one namespace doesn't
+    /// affect the objects of the other namespaces in the store.
+    pub fn watch<K, S>(
+        &self,
+        client: &Client,
+        store: Store<K>,
+        watch: impl Fn(WatchNamespace) -> S + Send + 'static,
+    ) -> BoxStream<'static, watcher::Result<watcher::Event<K>>>
+    where
+        K: Resource + Clone + Send + Sync + 'static,
+        K::DynamicType: Eq + Hash + Clone,
+        S: Stream<Item = watcher::Result<watcher::Event<K>>> + Send + 'static,
+    {
+        match self {
+            Self::All => watch(WatchNamespace::All).boxed(),
+            Self::Static(namespaces) => merge_namespace_watchers(
+                stream::iter(
+                    namespaces
+                        .iter()
+                        .map(|namespace| Ok(watcher::Event::Apply(namespace.clone())))
+                        .collect::<Vec<_>>(),
+                )
+                // The set of namespaces never changes
+                .chain(stream::pending()),
+                store,
+                watch,
+            )
+            .boxed(),
+            Self::Selector(selector) => merge_namespace_watchers(
+                metadata_watcher(
+                    Api::<Namespace>::all(client.as_kube_client()),
+                    watcher::Config::default().labels(selector.as_str()),
+                )
+                .map_ok(|event| match event {
+                    watcher::Event::Apply(namespace) => watcher::Event::Apply(namespace.name_any()),
+                    watcher::Event::Delete(namespace) => {
+                        watcher::Event::Delete(namespace.name_any())
+                    }
+                    watcher::Event::Init => watcher::Event::Init,
+                    watcher::Event::InitApply(namespace) => {
+                        watcher::Event::InitApply(namespace.name_any())
+                    }
+                    watcher::Event::InitDone => watcher::Event::InitDone,
+                })
+                .boxed(),
+                store,
+                watch,
+            )
+            .boxed(),
+        }
+    }
+}
+
+impl Display for WatchedNamespaces {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => f.write_str("all namespaces"),
+            Self::Static(namespaces) => write!(
+                f,
+                "namespaces {}",
+                namespaces.iter().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            Self::Selector(selector) => write!(f, "namespaces matching \"{selector}\""),
+        }
+    }
+}
+
+/// Events of the watcher of a single namespace, tagged with the namespace.
+type NamespacedEvents<K> =
+    stream::Abortable<BoxStream<'static, (String, watcher::Result<watcher::Event<K>>)>>;
+
+struct MergeState<K, N, W>
+where
+    K: Resource + Clone + 'static,
+    K::DynamicType: Eq + Hash + Clone,
+{
+    /// Events of the watched namespaces (by name)
+    namespace_events: N,
+
+    /// Namespaces seen during the current relist of the namespaces, [`None`] if no relist is in
+    /// progress
+    relisted_namespaces: Option<HashSet<String>>,
+
+    /// The watchers of the individual namespaces
+    watchers: SelectAll<NamespacedEvents<K>>,
+    watcher_handles: HashMap<String, AbortHandle>,
+
+    /// Names of the objects seen during the current relist of each namespace
+    relisted_objects: HashMap<String, HashSet<String>>,
+
+    /// Translated events that still need to be emitted
+    queue: VecDeque<watcher::Result<watcher::Event<K>>>,
+    store: Store<K>,
+    watch: W,
+}
+
+/// Starts and stops a watcher for every namespace according to the `namespace_events`, and merges
+/// their (translated) events.
+fn merge_namespace_watchers<K, S>(
+    namespace_events: impl Stream<Item = watcher::Result<watcher::Event<String>>>
+    + Send
+    + Unpin
+    + 'static,
+    store: Store<K>,
+    watch: impl Fn(WatchNamespace) -> S + Send + 'static,
+) -> impl Stream<Item = watcher::Result<watcher::Event<K>>> + Send
+where
+    K: Resource + Clone + Send + Sync + 'static,
+    K::DynamicType: Eq + Hash + Clone,
+    S: Stream<Item = watcher::Result<watcher::Event<K>>> + Send + 'static,
+{
+    let state = MergeState {
+        namespace_events,
+        relisted_namespaces: None,
+        watchers: SelectAll::new(),
+        watcher_handles: HashMap::new(),
+        relisted_objects: HashMap::new(),
+        queue: VecDeque::new(),
+        store,
+        watch,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.queue.pop_front() {
+                return Some((event, state));
+            }
+
+            let namespace_event = if state.watchers.is_empty() {
+                Either::Left(state.namespace_events.next().await?)
+            } else {
+                match future::select(state.namespace_events.next(), state.watchers.next()).await {
+                    Either::Left((namespace_event, _)) => Either::Left(namespace_event?),
+                    Either::Right((Some(object_event), _)) => Either::Right(object_event),
+                    // All watchers were stopped
+                    Either::Right((None, _)) => continue,
+                }
+            };
+            match namespace_event {
+                Either::Left(namespace_event) => state.apply_namespace_event(namespace_event),
+                Either::Right((namespace, object_event)) => {
+                    state.apply_object_event(namespace, object_event)
+                }
+            }
+        }
+    })
+}
+
+impl<K, N, W, S> MergeState<K, N, W>
+where
+    K: Resource + Clone + Send + Sync + 'static,
+    K::DynamicType: Eq + Hash + Clone,
+    W: Fn(WatchNamespace) -> S,
+    S: Stream<Item = watcher::Result<watcher::Event<K>>> + Send + 'static,
+{
+    fn apply_namespace_event(&mut self, event: watcher::Result<watcher::Event<String>>) {
+        match event {
+            Ok(watcher::Event::Apply(namespace)) => self.start_watcher(namespace),
+            Ok(watcher::Event::Delete(namespace)) => self.stop_watcher(&namespace),
+            Ok(watcher::Event::Init) => self.relisted_namespaces = Some(HashSet::new()),
+            Ok(watcher::Event::InitApply(namespace)) => {
+                if let Some(relisted_namespaces) = &mut self.relisted_namespaces {
+                    relisted_namespaces.insert(namespace.clone());
+                }
+                self.start_watcher(namespace);
+            }
+            Ok(watcher::Event::InitDone) => {
+                if let Some(relisted_namespaces) = self.relisted_namespaces.take() {
+                    let stale_namespaces = self
+                        .watcher_handles
+                        .keys()
+                        .filter(|namespace| !relisted_namespaces.contains(*namespace))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    for namespace in stale_namespaces {
+                        self.stop_watcher(&namespace);
+                    }
+                }
+            }
+            Err(error) => self.queue.push_back(Err(error)),
+        }
+    }
+
+    fn start_watcher(&mut self, namespace: String) {
+        if self.watcher_handles.contains_key(&namespace) {
+            return;
+        }
+        tracing::info!(namespace, "Starting to watch namespace");
+        let watcher = (self.watch)(WatchNamespace::One(namespace.clone()))
+            .map({
+                let namespace = namespace.clone();
+                move |event| (namespace.clone(), event)
+            })
+            .boxed();
+        let (watcher, handle) = stream::abortable(watcher);
+        self.watchers.push(watcher);
+        self.watcher_handles.insert(namespace, handle);
+    }
+
+    /// Stops watching the namespace, its objects are deleted from the store.
+    fn stop_watcher(&mut self, namespace: &str) {
+        let Some(handle) = self.watcher_handles.remove(namespace) else {
+            return;
+        };
+        tracing::info!(namespace, "Stopping to watch namespace");
+        handle.abort();
+        self.relisted_objects.remove(namespace);
+        self.delete_stale_objects(namespace, &HashSet::new());
+    }
+
+    fn apply_object_event(&mut self, namespace: String, event: watcher::Result<watcher::Event<K>>) {
+        let event = match event {
+            Ok(watcher::Event::Init) => {
+                self.relisted_objects.insert(namespace, HashSet::new());
+                return;
+            }
+            Ok(watcher::Event::InitApply(obj)) => {
+                if let Some(relisted_objects) = self.relisted_objects.get_mut(&namespace) {
+                    relisted_objects.insert(obj.name_any());
+                }
+                Ok(watcher::Event::Apply(obj))
+            }
+            Ok(watcher::Event::InitDone) => {
+                if let Some(relisted_objects) = self.relisted_objects.remove(&namespace) {
+                    self.delete_stale_objects(&namespace, &relisted_objects);
+                }
+                return;
+            }
+            event => event,
+        };
+        self.queue.push_back(event);
+    }
+
+    /// Deletes all objects of the namespace from the store, except for the ones in `keep`.
+    fn delete_stale_objects(&mut self, namespace: &str, keep: &HashSet<String>) {
+        // All events of the namespace that were emitted so far have already been applied to the
+        // store, as the events are consumed one by one.
+        let stale_objects = self.store.state().into_iter().filter(|obj| {
+            obj.meta().namespace.as_deref() == Some(namespace) && !keep.contains(&obj.name_any())
+        });
+        self.queue
+            .extend(stale_objects.map(|obj| Ok(watcher::Event::Delete(obj.as_ref().clone()))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stackable_operator::{k8s_openapi::api::core::v1::ConfigMap, kube::runtime::reflector};
+
+    use super::*;
+
+    fn config_map(namespace: &str, name: &str) -> ConfigMap {
+        let mut cm = ConfigMap::default();
+        cm.metadata.namespace = Some(namespace.to_owned());
+        cm.metadata.name = Some(name.to_owned());
+        cm
+    }
+
+    fn store_contents(store: &Store<ConfigMap>) -> BTreeSet<String> {
+        store
+            .state()
+            .iter()
+            .map(|cm| format!("{}/{}", cm.namespace().unwrap(), cm.name_any()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn relist_of_one_namespace_keeps_other_namespaces() {
+        let writer = reflector::store::Writer::<ConfigMap>::default();
+        let store = writer.as_reader();
+        let (namespace_tx, namespace_rx) = futures::channel::mpsc::unbounded();
+        let mut events = reflector(
+            writer,
+            merge_namespace_watchers(namespace_rx, store.clone(), |watch_namespace| {
+                let WatchNamespace::One(namespace) = watch_namespace else {
+                    panic!("only single namespaces are watched");
+                };
+                let events = match namespace.as_str() {
+                    "a" => vec![
+                        watcher::Event::Init,
+                        watcher::Event::InitApply(config_map("a", "stale")),
+                        watcher::Event::InitApply(config_map("a", "kept")),
+                        watcher::Event::InitDone,
+                        // Relist, where "stale" is gone
+                        watcher::Event::Init,
+                        watcher::Event::InitApply(config_map("a", "kept")),
+                        watcher::Event::InitDone,
+                    ],
+                    _ => vec![
+                        watcher::Event::Init,
+                        watcher::Event::InitApply(config_map(&namespace, "other")),
+                        watcher::Event::InitDone,
+                    ],
+                };
+                stream::iter(events.into_iter().map(Ok)).chain(stream::pending())
+            }),
+        )
+        .boxed();
+
+        namespace_tx
+            .unbounded_send(Ok(watcher::Event::Apply("b".to_owned())))
+            .unwrap();
+        events.next().await.unwrap().unwrap();
+        assert_eq!(
+            store_contents(&store),
+            BTreeSet::from(["b/other".to_owned()])
+        );
+
+        namespace_tx
+            .unbounded_send(Ok(watcher::Event::Apply("a".to_owned())))
+            .unwrap();
+        let deleted = (&mut events)
+            .take(4)
+            .try_filter_map(|event| {
+                future::ok(match event {
+                    watcher::Event::Delete(cm) => Some(cm.name_any()),
+                    _ => None,
+                })
+            })
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(deleted, vec!["stale".to_owned()]);
+        assert_eq!(
+            store_contents(&store),
+            BTreeSet::from(["a/kept".to_owned(), "b/other".to_owned()])
+        );
+
+        // Namespaces that are no longer watched are removed from the store
+        namespace_tx
+            .unbounded_send(Ok(watcher::Event::Delete("b".to_owned())))
+            .unwrap();
+        events.next().await.unwrap().unwrap();
+        assert_eq!(
+            store_contents(&store),
+            BTreeSet::from(["a/kept".to_owned()])
+        );
+    }
+
+    #[test]
+    fn conflicting_watch_namespaces() {
+        assert_eq!(
+            WatchedNamespaces::new(WatchNamespace::All, vec![], None).unwrap(),
+            WatchedNamespaces::All
+        );
+        assert_eq!(
+            WatchedNamespaces::new(WatchNamespace::One("a".to_owned()), vec![], None).unwrap(),
+            WatchedNamespaces::Static(BTreeSet::from(["a".to_owned()]))
+        );
+        assert!(
+            WatchedNamespaces::new(
+                WatchNamespace::One("a".to_owned()),
+                vec!["b".to_owned()],
+                None
+            )
+            .is_err()
+        );
+        assert!(
+            WatchedNamespaces::new(
+                WatchNamespace::All,
+                vec!["b".to_owned()],
+                Some("tenant=a".parse().unwrap())
+            )
+            .is_err()
+        );
+    }
+}
@@ -7,10 +7,11 @@ use stackable_operator::{
     webhook::{WebhookServer, WebhookServerError, WebhookServerOptions, webhooks::Webhook},
 };
 
-use crate::restart_controller::statefulset::Ctx;
+use crate::{restart_controller::workload::Ctx, utils::watched_namespaces::WatchedNamespaces};
 
 mod conversion;
-mod restarter_mutate_sts;
+mod restarter_mutate_pod;
+mod restarter_mutate_workload;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -20,20 +21,31 @@ pub enum Error {
 
 pub async fn create_webhook_server(
     ctx: Arc<Ctx>,
+    watched_namespaces: &WatchedNamespaces,
     operator_environment: &OperatorEnvironmentOptions,
     disable_restarter_mutating_webhook: bool,
+    disable_restarter_pod_mutating_webhook: bool,
     disable_crd_maintenance: bool,
     client: Client,
 ) -> Result<WebhookServer, Error> {
     let mut webhooks: Vec<Box<dyn Webhook>> = vec![];
 
-    if let Some(webhook) = restarter_mutate_sts::create_webhook(
-        ctx,
+    if let Some(webhook) = restarter_mutate_workload::create_webhook(
+        ctx.clone(),
+        watched_namespaces,
         disable_restarter_mutating_webhook,
         client.clone(),
     ) {
         webhooks.push(webhook);
     }
+    if let Some(webhook) = restarter_mutate_pod::create_webhook(
+        ctx,
+        watched_namespaces,
+        disable_restarter_pod_mutating_webhook,
+        client.clone(),
+    ) {
+        webhooks.push(webhook);
+    }
 
     // TODO (@Techassi): The conversion webhook should also allow to be disabled, rework the
     // granularity of these options.
@@ -0,0 +1,177 @@
+use std::{ops::Not, sync::Arc, time::Instant};
+
+use json_patch::{AddOperation, Patch, PatchOperation, jsonptr::PointerBuf};
+use stackable_operator::{
+    builder::meta::ObjectMetaBuilder,
+    k8s_openapi::{
+        api::admissionregistration::v1::{
+            MatchCondition, MutatingWebhook, MutatingWebhookConfiguration, RuleWithOperations,
+            WebhookClientConfig,
+        },
+        apimachinery::pkg::apis::meta::v1::{LabelSelector, LabelSelectorRequirement},
+    },
+    kube::{
+        Client, ResourceExt,
+        core::{
+            DynamicObject,
+            admission::{AdmissionRequest, AdmissionResponse},
+        },
+    },
+    kvp::Label,
+    webhook::webhooks::{MutatingWebhookOptions, Webhook},
+};
+
+use crate::{
+    FIELD_MANAGER, OPERATOR_NAME,
+    metrics::AdmissionOutcome,
+    restart_controller::{
+        self, EXPIRES_AT_ANNOTATION_PREFIX, EXPIRY_MANAGED_LABEL, restart_policy, workload::Ctx,
+    },
+    utils::watched_namespaces::WatchedNamespaces,
+};
+
+const WEBHOOK_NAME: &str = "restarter-pod-labeler.stackable.tech";
+
+pub fn create_webhook(
+    ctx: Arc<Ctx>,
+    watched_namespaces: &WatchedNamespaces,
+    disable_restarter_pod_mutating_webhook: bool,
+    client: Client,
+) -> Option<Box<impl Webhook>> {
+    disable_restarter_pod_mutating_webhook.not().then(|| {
+        let mutating_webhook_options = MutatingWebhookOptions {
+            disable_mwc_maintenance: disable_restarter_pod_mutating_webhook,
+            field_manager: FIELD_MANAGER.to_owned(),
+        };
+
+        Box::new(stackable_operator::webhook::webhooks::MutatingWebhook::new(
+            get_pod_mutating_webhook_configuration(watched_namespaces),
+            add_expiry_managed_label_handler,
+            ctx,
+            client,
+            mutating_webhook_options,
+        ))
+    })
+}
+
+fn get_pod_mutating_webhook_configuration(
+    watched_namespaces: &WatchedNamespaces,
+) -> MutatingWebhookConfiguration {
+    let metadata = ObjectMetaBuilder::new()
+        .name(WEBHOOK_NAME)
+        .with_label(Label::stackable_vendor())
+        .with_label(
+            Label::managed_by(OPERATOR_NAME, WEBHOOK_NAME).expect("static label is always valid"),
+        )
+        .build();
+
+    MutatingWebhookConfiguration {
+        metadata,
+        webhooks: Some(vec![MutatingWebhook {
+            name: WEBHOOK_NAME.to_owned(),
+            // This is checked by the stackable_webhook code
+            admission_review_versions: vec!["v1".to_owned()],
+            // Expiry annotations are usually added after the Pod was created (e.g. by the
+            // secret-operator once the volumes are provisioned), so updates need to be handled as
+            // well. Status updates are not affected, as they use the `pods/status` subresource.
+            rules: Some(vec![RuleWithOperations {
+                api_groups: Some(vec!["".to_owned()]),
+                api_versions: Some(vec!["v1".to_owned()]),
+                resources: Some(vec!["pods".to_owned()]),
+                operations: Some(vec!["CREATE".to_owned(), "UPDATE".to_owned()]),
+                scope: Some("Namespaced".to_owned()),
+            }]),
+            // Pods that are already labelled (or explicitly opted out) don't need to be mutated
+            object_selector: Some(LabelSelector {
+                match_expressions: Some(vec![LabelSelectorRequirement {
+                    key: EXPIRY_MANAGED_LABEL.to_owned(),
+                    operator: "DoesNotExist".to_owned(),
+                    values: None,
+                }]),
+                match_labels: None,
+            }),
+            // Only Pods that expire need to be sent to the webhook at all, so that other Pod writes
+            // in the cluster don't depend on the operator. This is the same condition as
+            // `restart_controller::expires`.
+            match_conditions: Some(vec![MatchCondition {
+                name: "expires".to_owned(),
+                expression: format!(
+                    "has(object.metadata.annotations) && object.metadata.annotations.exists(key, \
+                    key.startsWith('{EXPIRES_AT_ANNOTATION_PREFIX}') \
+                    || key == '{max_age}' || key == '{restart_schedule}')",
+                    max_age = restart_policy::MAX_AGE_ANNOTATION,
+                    restart_schedule = restart_policy::RESTART_SCHEDULE_ANNOTATION,
+                ),
+            }]),
+            // Pods in namespaces that are not watched are never evicted anyway
+            namespace_selector: watched_namespaces
+                .label_selector()
+                .map(|selector| selector.label_selector().clone()),
+            // Will be set by the stackable_webhook code
+            client_config: WebhookClientConfig::default(),
+            // Pods must never be blocked by this webhook. Unlabelled Pods are not evicted, but are
+            // labelled by their next update that reaches the webhook.
+            failure_policy: Some("Ignore".to_owned()),
+            // Other mutating webhooks might add expiry annotations
+            reinvocation_policy: Some("IfNeeded".to_owned()),
+            side_effects: "None".to_owned(),
+            ..Default::default()
+        }]),
+    }
+}
+
+async fn add_expiry_managed_label_handler(
+    ctx: Arc<Ctx>,
+    request: AdmissionRequest<DynamicObject>,
+) -> AdmissionResponse {
+    let started_at = Instant::now();
+    let response = add_expiry_managed_label(&request);
+    let outcome = if !response.allowed {
+        AdmissionOutcome::Rejected
+    } else if response.patch.is_some() {
+        AdmissionOutcome::Mutated
+    } else {
+        AdmissionOutcome::Allowed
+    };
+    ctx.metrics()
+        .webhook_admitted(WEBHOOK_NAME, outcome, started_at.elapsed());
+    response
+}
+
+fn add_expiry_managed_label(request: &AdmissionRequest<DynamicObject>) -> AdmissionResponse {
+    let Some(pod) = &request.object else {
+        return AdmissionResponse::invalid(
+            "object (of kind Pod) missing - for operations CREATE and UPDATE it must be always \
+            present",
+        );
+    };
+    if !restart_controller::expires(pod.annotations())
+        || pod.labels().contains_key(EXPIRY_MANAGED_LABEL)
+    {
+        return AdmissionResponse::from(request);
+    }
+
+    let labels_path = PointerBuf::from_tokens(["metadata", "labels"]);
+    let create_labels = pod.metadata.labels.is_none().then(|| {
+        PatchOperation::Add(AddOperation {
+            path: labels_path.clone(),
+            value: serde_json::Value::Object(serde_json::Map::new()),
+        })
+    });
+    let mut label_path = labels_path;
+    // The slash in the label key is escaped by `PointerBuf::push_back`
+    label_path.push_back(EXPIRY_MANAGED_LABEL);
+    let add_label = PatchOperation::Add(AddOperation {
+        path: label_path,
+        value: serde_json::Value::String("true".to_owned()),
+    });
+
+    match AdmissionResponse::from(request).with_patch(Patch(
+        create_labels.into_iter().chain([add_label]).collect(),
+    )) {
+        Ok(response) => response,
+        Err(err) => {
+            AdmissionResponse::invalid(format!("failed to add patch to AdmissionResponse: {err:#}"))
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Not,
+    sync::Arc,
+    time::Instant,
+};
+
+use json_patch::{AddOperation, Patch, PatchOperation, jsonptr::PointerBuf};
+use stackable_operator::{
+    builder::meta::ObjectMetaBuilder,
+    k8s_openapi::api::admissionregistration::v1::{
+        MutatingWebhook, MutatingWebhookConfiguration, RuleWithOperations, WebhookClientConfig,
+    },
+    kube::{
+        Client,
+        core::{
+            DynamicObject,
+            admission::{AdmissionRequest, AdmissionResponse},
+        },
+    },
+    kvp::Label,
+    webhook::webhooks::{MutatingWebhookOptions, Webhook},
+};
+
+use crate::{
+    FIELD_MANAGER, OPERATOR_NAME,
+    metrics::AdmissionOutcome,
+    restart_controller::{
+        workload::{Ctx, Options, get_updated_restarter_annotations},
+        workload_kind::{self, WorkloadKind},
+    },
+    utils::watched_namespaces::WatchedNamespaces,
+};
+
+// NOTE: The name is kept from the times where only StatefulSets were supported, otherwise we
+// would leave the old MutatingWebhookConfiguration behind.
+const WEBHOOK_NAME: &str = "restarter-sts-enricher.stackable.tech";
+
+pub fn create_webhook(
+    ctx: Arc<Ctx>,
+    watched_namespaces: &WatchedNamespaces,
+    disable_restarter_mutating_webhook: bool,
+    client: Client,
+) -> Option<Box<impl Webhook>> {
+    disable_restarter_mutating_webhook.not().then(|| {
+        let mutating_webhook_options = MutatingWebhookOptions {
+            // TODO (@Techassi): Make disabling webhooks and maintenance options more granular.
+            // NOTE: Technically this can never not be "false", so we could hard-code it. I'll leave
+            // it as is for now.
+            disable_mwc_maintenance: disable_restarter_mutating_webhook,
+            field_manager: FIELD_MANAGER.to_owned(),
+        };
+
+        Box::new(stackable_operator::webhook::webhooks::MutatingWebhook::new(
+            get_restarter_mutating_webhook_configuration(
+                ctx.workload_kinds(),
+                ctx.options(),
+                watched_namespaces,
+            ),
+            add_restarter_annotations_handler,
+            ctx,
+            client,
+            mutating_webhook_options,
+        ))
+    })
+}
+
+fn get_restarter_mutating_webhook_configuration(
+    workload_kinds: &[WorkloadKind],
+    options: &Options,
+    watched_namespaces: &WatchedNamespaces,
+) -> MutatingWebhookConfiguration {
+    let metadata = ObjectMetaBuilder::new()
+        .name(WEBHOOK_NAME)
+        .with_label(Label::stackable_vendor())
+        .with_label(
+            Label::managed_by(OPERATOR_NAME, WEBHOOK_NAME).expect("static label is always valid"),
+        )
+        .build();
+
+    // One rule per API group and version, matching all workload kinds within it
+    let mut resources_by_group_version = BTreeMap::<(&str, &str), BTreeSet<&str>>::new();
+    for workload_kind in workload_kinds {
+        let api_resource = &workload_kind.api_resource;
+        resources_by_group_version
+            .entry((&api_resource.group, &api_resource.version))
+            .or_default()
+            .insert(&api_resource.plural);
+    }
+    let rules = resources_by_group_version
+        .into_iter()
+        .map(|((group, version), resources)| RuleWithOperations {
+            api_groups: Some(vec![group.to_owned()]),
+            api_versions: Some(vec![version.to_owned()]),
+            resources: Some(resources.into_iter().map(str::to_owned).collect()),
+            operations: Some(vec!["CREATE".to_owned()]),
+            scope: Some("Namespaced".to_owned()),
+        })
+        .collect();
+
+    // Workloads in namespaces that are not watched can't be enriched, as their ConfigMaps and
+    // Secrets are unknown
+    let namespace_selector = match (
+        watched_namespaces.label_selector(),
+        &options.namespace_selector,
+    ) {
+        (Some(watched_selector), Some(restarter_selector)) => {
+            Some(watched_selector.and(restarter_selector))
+        }
+        (watched_selector, restarter_selector) => {
+            watched_selector.or_else(|| restarter_selector.clone())
+        }
+    };
+
+    MutatingWebhookConfiguration {
+        metadata,
+        webhooks: Some(vec![MutatingWebhook {
+            name: WEBHOOK_NAME.to_owned(),
+            // This is checked by the stackable_webhook code
+            admission_review_versions: vec!["v1".to_owned()],
+            rules: Some(rules),
+            // We only need to care about the workloads (and namespaces) that are restarted, which
+            // are by default the ones with the `restarter.stackable.tech/enabled` label set to
+            // `true`.
+            object_selector: options
+                .workload_label_selector()
+                .map(|selector| selector.label_selector().clone()),
+            namespace_selector: namespace_selector
+                .map(|selector| selector.label_selector().clone()),
+            // Will be set by the stackable_webhook code
+            client_config: WebhookClientConfig::default(),
+            // Worst case if the annotations are missing they cause a restart of Pod 0, basically
+            // the same behavior which we had for years.
+            // See https://github.com/stackabletech/commons-operator/issues/111 for details
+            failure_policy: Some("Ignore".to_owned()),
+            // It could be the case that other mutating webhooks add more ConfigMap/Secret mounts,
+            // in which case it would be nice if we detect that.
+            reinvocation_policy: Some("IfNeeded".to_owned()),
+            // > Webhooks typically operate only on the content of the AdmissionReview sent to them.
+            // > Some webhooks, however, make out-of-band changes as part of processing admission requests.
+            //
+            // We read in the state of the world using the ConfigMap and Secret store.
+            // So, technically our outcome depends on external factors, *but* this webhook is not
+            // creating any external objects, so from our understanding it's side-effect free.
+            side_effects: "None".to_owned(),
+            ..Default::default()
+        }]),
+    }
+}
+
+async fn add_restarter_annotations_handler(
+    ctx: Arc<Ctx>,
+    request: AdmissionRequest<DynamicObject>,
+) -> AdmissionResponse {
+    let started_at = Instant::now();
+    let response = add_restarter_annotations(&ctx, &request).await;
+    let outcome = if !response.allowed {
+        AdmissionOutcome::Rejected
+    } else if response.patch.is_some() {
+        AdmissionOutcome::Mutated
+    } else {
+        AdmissionOutcome::Allowed
+    };
+    ctx.metrics()
+        .webhook_admitted(WEBHOOK_NAME, outcome, started_at.elapsed());
+    response
+}
+
+async fn add_restarter_annotations(
+    ctx: &Ctx,
+    request: &AdmissionRequest<DynamicObject>,
+) -> AdmissionResponse {
+    let Some(workload) = &request.object else {
+        return AdmissionResponse::invalid(
+            "object (of a workload kind) missing - for operation CREATE it must be always present",
+        );
+    };
+    // The Pod template of controlled workloads (such as the ReplicaSets of a Deployment) is managed
+    // by their controller, which annotated it already
+    if workload_kind::is_controlled(&workload.metadata) {
+        return AdmissionResponse::from(request);
+    }
+    let Some(workload_kind) = ctx.workload_kind(&request.kind.group, &request.kind.kind) else {
+        return AdmissionResponse::invalid(format!(
+            "object of kind {kind:?} in API group {group:?} is not a supported workload",
+            kind = request.kind.kind,
+            group = request.kind.group,
+        ));
+    };
+    let pod_template = match workload_kind.pod_template(workload) {
+        Ok(Some(pod_template)) => pod_template,
+        // Nothing to annotate, the workload will be rejected (or not) by Kubernetes
+        Ok(None) => return AdmissionResponse::from(request),
+        Err(err) => {
+            return AdmissionResponse::invalid(format!("failed to parse Pod template: {err:#}"));
+        }
+    };
+
+    let annotations_path = PointerBuf::from_tokens(
+        workload_kind
+            .pod_template_path
+            .tokens()
+            .map(|token| token.decoded().into_owned())
+            .chain(["metadata".to_owned(), "annotations".to_owned()]),
+    );
+
+    // The Pod template exists, but its metadata and annotations might not, in which case they need
+    // to be created first.
+    let paths_to_be_created = annotations_path
+        .tokens()
+        .scan(PointerBuf::new(), |path, token| {
+            path.push_back(token);
+            Some(path.clone())
+        })
+        .filter(|path| workload.data.pointer(path.as_str()).is_none())
+        .collect::<Vec<_>>();
+    let create_paths = paths_to_be_created.into_iter().map(|path| {
+        PatchOperation::Add(AddOperation {
+            path,
+            value: serde_json::Value::Object(serde_json::Map::new()),
+        })
+    });
+
+    // The namespace is not necessarily part of the object on creation
+    let mut workload_meta = workload.metadata.clone();
+    if workload_meta.namespace.is_none() {
+        workload_meta.namespace = request.namespace.clone();
+    }
+    let annotations =
+        match get_updated_restarter_annotations(&workload_meta, &pod_template, ctx).await {
+            Ok(annotations) => annotations,
+            Err(err) => {
+                return AdmissionResponse::invalid(format!(
+                    "failed to get updated restarted annotations: {err:#}"
+                ));
+            }
+        };
+
+    let add_annotations = annotations.iter().map(|(k, v)| {
+        let mut path = annotations_path.clone();
+        // It's totally fine (and even expected) that the annotations contains slashes ("/"),
+        // as `PointerBuf::push_back` escapes them
+        path.push_back(k.as_str());
+        PatchOperation::Add(AddOperation {
+            path,
+            value: serde_json::Value::String(v.to_owned()),
+        })
+    });
+
+    match AdmissionResponse::from(request)
+        .with_patch(Patch(create_paths.chain(add_annotations).collect()))
+    {
+        Ok(response) => response,
+        Err(err) => {
+            AdmissionResponse::invalid(format!("failed to add patch to AdmissionResponse: {err:#}"))
+        }
+    }
+}